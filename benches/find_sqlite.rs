@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use renamelayer::clip;
+use std::fs;
+use std::io::Write;
+
+const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resource/sample.clip");
+
+/// Scanning the sample file.
+fn sample(c: &mut Criterion) {
+  c.bench_function("find_sqlite sample.clip", |b| {
+    b.iter(|| clip::find_sqlite(SAMPLE).unwrap().unwrap())
+  });
+}
+
+/// Scanning a big canvas: the sqlite3 chunk is placed after 64MiB of layer data.
+fn big_canvas(c: &mut Criterion) {
+  let dir = tempfile::tempdir().unwrap();
+  let path = dir.path().join("big.clip");
+  let sample = fs::read(SAMPLE).unwrap();
+  let mut f = fs::File::create(&path).unwrap();
+  // pseudo layer data with many 'C' to exercise the prefilter
+  let block: Vec<u8> = (0..1024 * 1024)
+    .map(|i| if i % 97 == 0 { b'C' } else { (i % 251) as u8 })
+    .collect();
+  for _ in 0..64 {
+    f.write_all(&block).unwrap();
+  }
+  f.write_all(&sample).unwrap();
+  drop(f);
+
+  let mut group = c.benchmark_group("find_sqlite big");
+  group.sample_size(10);
+  group.bench_function("64MiB", |b| {
+    b.iter(|| clip::find_sqlite(&path).unwrap().unwrap())
+  });
+  group.finish();
+}
+
+criterion_group!(benches, sample, big_canvas);
+criterion_main!(benches);
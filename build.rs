@@ -0,0 +1,26 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Brief.
+///
+/// Embed the git commit hash as `RENAMELAYER_GIT_HASH` (`unknown` outside a git work tree).
+fn main() {
+  let hash = Command::new("git")
+    .args(["rev-parse", "--short=12", "HEAD"])
+    .output()
+    .ok()
+    .filter(|x| x.status.success())
+    .and_then(|x| String::from_utf8(x.stdout).ok())
+    .map(|x| x.trim().to_string())
+    .filter(|x| !x.is_empty())
+    .unwrap_or_else(|| "unknown".to_string());
+  println!("cargo:rustc-env=RENAMELAYER_GIT_HASH={}", hash);
+
+  println!("cargo:rerun-if-changed=build.rs");
+  // paths which do not exist would run this every time
+  for p in [".git/HEAD", ".git/refs/heads"].iter() {
+    if Path::new(p).exists() {
+      println!("cargo:rerun-if-changed={}", p);
+    }
+  }
+}
@@ -0,0 +1,307 @@
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// Brief.
+///
+/// Command line option definition.
+///
+/// * `0`: option name (e.g. `--folder`)
+/// * `1`: whether the option takes a value
+pub type OptionSpec = (&'static str, bool);
+
+/// prefix of environment variables giving default values of options
+const ENV_PREFIX: &str = "RENAMELAYER_";
+
+/// Parsed command line arguments.
+///
+/// Arguments are kept as `OsString` so that file names which are not valid UTF-8 can be used.
+pub struct Args {
+  /// arguments which are not options
+  pub positional: Vec<OsString>,
+  options: Vec<(&'static str, Option<OsString>)>,
+}
+
+impl Args {
+  /// Brief.
+  ///
+  /// Parse arguments.
+  ///
+  /// Options are written as `--name value` or `--name=value`.
+  /// Short options in `spec` (e.g. `-o`) are written as `-o value`.
+  /// Arguments after `--` are always positional.
+  ///
+  /// * `args`: arguments without the program name and the sub command name
+  /// * `spec`: accepted options
+  pub fn parse(args: &[OsString], spec: &[OptionSpec]) -> Result<Args, String> {
+    let mut positional: Vec<OsString> = Vec::new();
+    let mut options: Vec<(&'static str, Option<OsString>)> = Vec::new();
+    let mut itr = args.iter();
+    let mut only_positional = false;
+    while let Some(os_arg) = itr.next() {
+      let short = spec
+        .iter()
+        .any(|(n, _)| !n.starts_with("--") && os_arg.as_os_str() == OsStr::new(n));
+      if only_positional || !(short || os_arg.as_encoded_bytes().starts_with(b"--")) {
+        positional.push(os_arg.clone());
+        continue;
+      }
+      let arg = match os_arg.to_str() {
+        Some(x) => x,
+        None => {
+          return Err(format!(
+            "{}: use `--name value` for values which are not UTF-8",
+            os_arg.to_string_lossy()
+          ))
+        }
+      };
+      if arg == "--" {
+        only_positional = true;
+        continue;
+      }
+
+      let (name, inline_value) = match arg.find('=') {
+        Some(i) => (&arg[..i], Some(OsString::from(&arg[i + 1..]))),
+        None => (arg, None),
+      };
+      let (name, takes_value) = match spec.iter().find(|(n, _)| *n == name) {
+        Some(x) => *x,
+        None => return Err(format!("unknown option: {}", name)),
+      };
+      if !takes_value {
+        if inline_value.is_some() {
+          return Err(format!("{} does not take a value", name));
+        }
+        options.push((name, None));
+        continue;
+      }
+      let value = match inline_value {
+        Some(x) => x,
+        None => match itr.next() {
+          Some(x) => x.clone(),
+          None => return Err(format!("{} requires a value", name)),
+        },
+      };
+      options.push((name, Some(value)));
+    }
+    return Ok(Args {
+      positional,
+      options,
+    });
+  }
+
+  /// Brief.
+  ///
+  /// `parse`, and read the allowed options which are not given from environment variables
+  /// (`environment_name`, e.g. `RENAMELAYER_PATTERN` for `--pattern`).
+  ///
+  /// Only options with a value are read. Flags are never read, so that a variable left in the
+  /// environment cannot turn on options such as `--in-place`.
+  ///
+  /// * `args`: arguments without the program name and the sub command name
+  /// * `spec`: accepted options
+  /// * `environment`: options which may be given by environment variables
+  pub fn parse_with_environment(
+    args: &[OsString],
+    spec: &[OptionSpec],
+    environment: &[&str],
+  ) -> Result<Args, String> {
+    let mut args = Args::parse(args, spec)?;
+    for (name, takes_value) in spec.iter() {
+      if !takes_value || !environment.contains(name) || args.flag(name) {
+        continue;
+      }
+      if let Some(value) = std::env::var_os(environment_name(name)) {
+        args.options.push((name, Some(value)));
+      }
+    }
+    return Ok(args);
+  }
+
+  /// Whether the option is given.
+  pub fn flag(&self, name: &str) -> bool {
+    return self.options.iter().any(|(n, _)| *n == name);
+  }
+
+  /// The last value of the option. Values which are not UTF-8 are ignored; use `path` for file names.
+  pub fn value(&self, name: &str) -> Option<&str> {
+    return self.values(name).pop();
+  }
+
+  /// All values of the option in the given order.
+  pub fn values(&self, name: &str) -> Vec<&str> {
+    return self
+      .values_os(name)
+      .into_iter()
+      .filter_map(|v| v.to_str())
+      .collect();
+  }
+
+  /// The last value of the option as a file path.
+  pub fn path(&self, name: &str) -> Option<&Path> {
+    return self.values_os(name).pop().map(Path::new);
+  }
+
+  /// All given options as `(name, value)` in the given order, including options from environment variables.
+  pub fn options(&self) -> Vec<(&str, Option<&OsStr>)> {
+    return self
+      .options
+      .iter()
+      .map(|(n, v)| (*n, v.as_deref()))
+      .collect();
+  }
+
+  fn values_os(&self, name: &str) -> Vec<&OsStr> {
+    return self
+      .options
+      .iter()
+      .filter(|(n, _)| *n == name)
+      .filter_map(|(_, v)| v.as_deref())
+      .collect();
+  }
+}
+
+/// Brief.
+///
+/// Remove the `--temp-dir DIR` (or `--temp-dir=DIR`) option, which is accepted by all sub commands,
+/// from the arguments. Arguments after `--` are not looked at.
+///
+/// * `args`: arguments without the program name
+///
+/// Return.
+///
+/// The last given directory
+pub fn take_temp_dir(args: &mut Vec<OsString>) -> Result<Option<OsString>, String> {
+  let mut dir = None;
+  let mut i = 0;
+  while i < args.len() {
+    let arg = args[i].as_os_str();
+    if arg == "--" {
+      break;
+    }
+    if arg == "--temp-dir" {
+      if i + 1 >= args.len() {
+        return Err("--temp-dir requires a value".to_string());
+      }
+      dir = Some(args.remove(i + 1));
+      args.remove(i);
+    } else if arg.as_encoded_bytes().starts_with(b"--temp-dir=") {
+      // the value is kept as given: only a UTF-8 argument can be split
+      match arg.to_str() {
+        Some(x) => dir = Some(OsString::from(&x["--temp-dir=".len()..])),
+        None => return Err("use `--temp-dir DIR` for directories which are not UTF-8".to_string()),
+      }
+      args.remove(i);
+    } else {
+      i += 1;
+    }
+  }
+  return Ok(dir);
+}
+
+/// `--out-dir` → `RENAMELAYER_OUT_DIR`
+fn environment_name(option: &str) -> String {
+  return format!(
+    "{}{}",
+    ENV_PREFIX,
+    option
+      .trim_start_matches('-')
+      .replace('-', "_")
+      .to_uppercase()
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SPEC: &[OptionSpec] = &[("--pattern", true), ("--dry-run", false), ("-o", true)];
+
+  fn os(args: &[&str]) -> Vec<OsString> {
+    return args.iter().map(OsString::from).collect();
+  }
+
+  #[test]
+  fn options_and_positional() {
+    let args = Args::parse(
+      &os(&[
+        "a",
+        "--pattern",
+        "x",
+        "--dry-run",
+        "-o",
+        "out",
+        "--pattern=y",
+        "--",
+        "--dry-run",
+      ]),
+      SPEC,
+    )
+    .unwrap();
+    assert_eq!(args.positional, os(&["a", "--dry-run"]));
+    assert!(args.flag("--dry-run"));
+    assert_eq!(args.values("--pattern"), vec!["x", "y"]);
+    assert_eq!(args.value("--pattern"), Some("y"));
+    assert_eq!(args.path("-o"), Some(Path::new("out")));
+    assert_eq!(args.value("--missing"), None);
+  }
+
+  #[test]
+  fn invalid_options() {
+    for (args, error) in [
+      (&["--unknown"][..], "unknown option: --unknown"),
+      (&["--dry-run=1"][..], "--dry-run does not take a value"),
+      (&["--pattern"][..], "--pattern requires a value"),
+    ]
+    .iter()
+    {
+      assert_eq!(Args::parse(&os(args), SPEC).err().as_deref(), Some(*error));
+    }
+  }
+
+  #[test]
+  fn environment_names() {
+    assert_eq!(environment_name("--out-dir"), "RENAMELAYER_OUT_DIR");
+    assert_eq!(environment_name("--pattern"), "RENAMELAYER_PATTERN");
+  }
+
+  #[test]
+  fn environment_allowlist() {
+    // option names used only by this test, so that other tests do not see the variables
+    let spec: &[OptionSpec] = &[
+      ("--args-test-allowed", true),
+      ("--args-test-given", true),
+      ("--args-test-other", true),
+      ("--args-test-flag", false),
+    ];
+    for (name, _) in spec.iter() {
+      std::env::set_var(environment_name(name), "env");
+    }
+    let allowed = [
+      "--args-test-allowed",
+      "--args-test-given",
+      "--args-test-flag",
+    ];
+    let args =
+      Args::parse_with_environment(&os(&["--args-test-given", "arg"]), spec, &allowed).unwrap();
+    assert_eq!(args.value("--args-test-allowed"), Some("env"));
+    assert_eq!(args.values("--args-test-given"), vec!["arg"]);
+    assert!(!args.flag("--args-test-other"));
+    assert!(!args.flag("--args-test-flag"));
+  }
+
+  #[test]
+  fn temp_dir() {
+    let mut args = os(&[
+      "a",
+      "--temp-dir",
+      "x",
+      "--temp-dir=y",
+      "--",
+      "--temp-dir",
+      "z",
+    ]);
+    assert_eq!(take_temp_dir(&mut args), Ok(Some(OsString::from("y"))));
+    assert_eq!(args, os(&["a", "--", "--temp-dir", "z"]));
+    assert!(take_temp_dir(&mut os(&["--temp-dir"])).is_err());
+  }
+}
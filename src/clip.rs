@@ -1,14 +1,110 @@
+use memchr::memmem;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
-use std::mem;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use std::u64;
-use tempfile::tempdir;
 use thiserror::Error;
 
+mod affix;
+/// Async variants of the file operations for tokio (`async` feature).
+///
+/// Each operation runs on the blocking thread pool of tokio (`spawn_blocking`),
+/// so that many files are processed concurrently without blocking the async worker threads.
+/// A tokio runtime must be running.
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod cancel;
+mod cleanup;
+mod clipping;
+mod collation;
+mod collision;
+mod color;
+mod comment;
+mod conform;
+mod creation;
+mod dictionary;
+mod document;
+mod info;
+mod journal;
+mod layer_tree;
+mod layers;
+mod lint;
+mod locale;
+mod lock;
+mod long_path;
+mod material;
+mod naming;
+mod normalize;
+mod old_name;
+mod options;
+mod panel;
+mod pixels;
+mod plan;
+#[cfg(feature = "preview")]
+mod preview;
+mod profile;
+mod project;
+mod renumber;
+mod report;
+mod rule;
+mod schema;
+mod share;
+mod sniff;
+mod sort;
+mod space;
+mod special;
+mod swap;
+mod temp;
+mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod timeline;
+mod tree;
+mod vector;
+mod visibility;
+pub use affix::add_affix_in_sqlite;
+pub use cancel::CancelToken;
+pub use cleanup::remove_temporary_files;
+pub use collation::Collation;
+pub use collision::CollisionPolicy;
+pub use conform::plan_conform_in_sqlite;
+pub use dictionary::{load_dictionary, parse_dictionary};
+pub use document::{ClipDocument, LoadStatistics};
+pub use info::{info, ChunkInfo, ClipInfo};
+pub use journal::DatabaseState;
+pub use layer_tree::{LayerTree, TreeIssue};
+pub use layers::{LayerIter, LayerRecord, Layers};
+pub use lint::{lint_layers_in_sqlite, LintIssue, NamingConvention};
+pub use locale::{detect_layer_name_locale, LayerNameLocale, LAYER_NAME_LOCALES};
+pub use lock::lock_layers_in_sqlite;
+pub use long_path::extended_path;
+pub use naming::{plan_with_in_sqlite, LayerView, RenameContext};
+pub use old_name::OldNameStyle;
+pub use options::{MatchMode, RenameOptions, DEFAULT_PATTERN, DEFAULT_ROOT_LAYER_NAME};
+pub use panel::{load_panels, parse_panels, plan_panels_in_sqlite, Panel};
+pub use plan::RenamePlan;
+#[cfg(feature = "preview")]
+pub use preview::{load_preview_font, write_rename_preview};
+pub use profile::{Profile, PROFILES};
+pub use renumber::{DEFAULT_FOLDER_PATTERN, DEFAULT_SCENE_TEMPLATE};
+pub use report::{RenameReport, RenamedLayer, Warning};
+pub use rule::{LayerKind, NumberingOrder, RenameRules, RuleAction, DEFAULT_PROTECT_PREFIX};
+pub use rusqlite;
+pub use schema::{describe_schema_in_sqlite, known_schema_names, DatabaseSchema, TableInfo};
+pub use share::open_shared;
+pub use sniff::{is_clip_file, CLIP_EXTENSIONS};
+pub use sort::sort_layers_in_sqlite;
+pub use space::{available_space, format_size};
+pub use special::SpecialLayer;
+pub use temp::{set_temp_dir, temp_dir, TEMP_DIR_ENV};
+pub use template::{NameTemplate, DEFAULT_TEMPLATE};
+pub use tree::{list_layers_in_sqlite, LayerInfo};
+pub use visibility::{list_layer_states_in_sqlite, LayerState};
+
 #[derive(Debug)]
 pub struct ClipLayer {
+  #[allow(dead_code)]
   pw_id: u64,
   main_id: u64,
   layer_name: String,
@@ -16,9 +112,12 @@ pub struct ClipLayer {
   layer_folder: u64,
   layer_next_index: u64,
   layer_first_child_index: u64,
+  kind: LayerKind,
+  special: Option<SpecialLayer>,
 }
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ClipError {
   #[error("file open failed")]
   FileOpenError,
@@ -38,13 +137,123 @@ pub enum ClipError {
   IOError,
   #[error("not a clip studio file format.")]
   NotClipFile,
+  #[error("the file is empty (0 bytes).")]
+  EmptyFile,
+  #[error("file appears truncated at byte {0} (expected {1} bytes).")]
+  Truncated(u64, u64),
+  #[error("not a clip studio file: this looks like a {0} file.")]
+  UnexpectedFileType(&'static str),
+  #[error("root folder is ambiguous ({0} candidates).")]
+  AmbiguousRoot(usize),
+  #[error("invalid name template: {0}")]
+  InvalidTemplate(String),
+  #[error("invalid rename rule: {0}")]
+  InvalidRule(String),
+  #[error("layer {0} not found.")]
+  LayerNotFound(u64),
+  #[error("layer {0} cannot be moved there.")]
+  InvalidLayerMove(u64),
+  #[error("the embedded data base is broken.")]
+  CorruptDatabase,
+  #[error("no usable font found.")]
+  FontNotFound,
+  #[error("invalid dictionary: {0}")]
+  InvalidDictionary(String),
+  #[error("invalid panels: {0}")]
+  InvalidPanels(String),
+  #[error("this file has no layer comment field.")]
+  NoCommentField,
+  #[error("unknown data base schema: {0}")]
+  UnknownSchema(String),
+  #[error("layer {0} was renamed after the plan was made.")]
+  StalePlan(u64),
+  #[error("layer {0} cannot be renamed to {1}: a layer of the same name is in the folder.")]
+  NameCollision(u64, String),
+  #[error("cancelled.")]
+  Cancelled,
+  #[error("canvas {0} not found: the file has {1} canvases.")]
+  CanvasNotFound(usize, usize),
+  #[error("not enough disk space: need ~{} free in {} ({} available).", space::format_size(*.1), .0.display(), space::format_size(*.2))]
+  NotEnoughSpace(std::path::PathBuf, u64, u64),
+}
+
+/// Brief.
+///
+/// Rough classification of `ClipError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+  /// Reading or writing the input/output files failed.
+  Io,
+  /// The input is not a clip file, or its structure is not supported.
+  Format,
+  /// The embedded sqlite3 data base could not be read or updated.
+  Sql,
+  /// The runtime environment is not usable (temporary directory, output directories).
+  Environment,
+  /// The arguments given by the caller are invalid.
+  Usage,
+}
+
+impl ClipError {
+  /// Returns the category of this error.
+  pub fn category(&self) -> ErrorCategory {
+    return match self {
+      ClipError::FileOpenError => ErrorCategory::Io,
+      ClipError::FileSaveError => ErrorCategory::Io,
+      ClipError::FileReadError => ErrorCategory::Io,
+      ClipError::IOError => ErrorCategory::Io,
+      ClipError::TmpDirError => ErrorCategory::Environment,
+      ClipError::CreateDirectoryError => ErrorCategory::Environment,
+      ClipError::SQLError => ErrorCategory::Sql,
+      ClipError::UnknownFileStruct => ErrorCategory::Format,
+      ClipError::NotClipFile => ErrorCategory::Format,
+      ClipError::EmptyFile => ErrorCategory::Format,
+      ClipError::Truncated(_, _) => ErrorCategory::Format,
+      ClipError::UnexpectedFileType(_) => ErrorCategory::Format,
+      ClipError::AmbiguousRoot(_) => ErrorCategory::Format,
+      ClipError::InvalidTemplate(_) => ErrorCategory::Usage,
+      ClipError::InvalidRule(_) => ErrorCategory::Usage,
+      ClipError::LayerNotFound(_) => ErrorCategory::Usage,
+      ClipError::InvalidLayerMove(_) => ErrorCategory::Usage,
+      ClipError::CorruptDatabase => ErrorCategory::Format,
+      ClipError::FontNotFound => ErrorCategory::Environment,
+      ClipError::InvalidDictionary(_) => ErrorCategory::Usage,
+      ClipError::InvalidPanels(_) => ErrorCategory::Usage,
+      ClipError::NoCommentField => ErrorCategory::Format,
+      ClipError::UnknownSchema(_) => ErrorCategory::Format,
+      ClipError::StalePlan(_) => ErrorCategory::Usage,
+      ClipError::NameCollision(_, _) => ErrorCategory::Usage,
+      ClipError::Cancelled => ErrorCategory::Usage,
+      ClipError::CanvasNotFound(_, _) => ErrorCategory::Usage,
+      ClipError::NotEnoughSpace(_, _, _) => ErrorCategory::Environment,
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Whether running the same operation again may succeed.
+  ///
+  /// IO and environment errors are often transient (file locked by Clip Studio Paint, full disk, network drive),
+  /// while format and SQL errors depend only on the input file and will fail again.
+  pub fn is_retryable(&self) -> bool {
+    return match self.category() {
+      ErrorCategory::Io | ErrorCategory::Environment => true,
+      ErrorCategory::Format | ErrorCategory::Sql | ErrorCategory::Usage => false,
+    };
+  }
 }
 
 const SQL_CHANK: &[u8; 8] = b"CHNKSQLi";
 const SQL_CHANK_LEN: usize = SQL_CHANK.len();
 const SQL_HEADER: &[u8; 16] = b"SQLite format 3\0";
 const SQL_HEADER_LEN: usize = SQL_HEADER.len();
-const SQL_HEADER_TOTAL_SIZE: usize = SQL_CHANK_LEN + 8 + SQL_HEADER_LEN;
+/// size of the length field of a chunk
+const CHUNK_LENGTH_SIZE: usize = 8;
+/// maximum bytes of extra fields between the chunk length and the sqlite3 data
+const SQL_CHUNK_MAX_EXTRA: usize = 16;
+const SQL_HEADER_TOTAL_SIZE: usize =
+  SQL_CHANK_LEN + CHUNK_LENGTH_SIZE + SQL_CHUNK_MAX_EXTRA + SQL_HEADER_LEN;
 const FOOT_CHANK_DATA: [u8; 16] = [
   0x43, 0x48, 0x4E, 0x4B, 0x46, 0x6F, 0x6F, 0x74, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
@@ -55,120 +264,213 @@ const FOOT_CHANK_DATA: [u8; 16] = [
 ///
 /// * `src`: input file
 /// * `dst`: output file
-/// * `root_layer_base_name`: top level layer name.
-/// * `rename_layer`: A function that takes a layer name as an argument and decides whether to change the layer name.
-pub fn create_layer_renamed_clip_file<P1: AsRef<Path>, P2: AsRef<Path>, F>(
+/// * `options`: options of renaming
+pub fn create_layer_renamed_clip_file<P1: AsRef<Path>, P2: AsRef<Path>>(
   src: P1,
   dst: P2,
-  root_layer_base_name: &str,
-  rename_layer: F,
+  options: &RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  let mut doc = ClipDocument::open(src)?;
+  let report = doc.rename_layers(options)?;
+  doc.save(dst)?;
+  return Ok(report);
+}
+
+/// Brief.
+///
+/// `create_layer_renamed_clip_file` for any reader and writer,
+/// e.g. files in archives or memory buffers.
+///
+/// * `reader`: input clip data
+/// * `writer`: output clip data
+/// * `options`: options of renaming
+pub fn create_layer_renamed_clip<R: Read + Seek, W: Write + Seek>(
+  reader: R,
+  writer: W,
+  options: &RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  let mut doc = ClipDocument::from_reader(reader)?;
+  let report = doc.rename_layers(options)?;
+  doc.save_to(writer)?;
+  return Ok(report);
+}
+
+/// Brief.
+///
+/// Extract the sqlite3 data of the clip file, edit it and write a new clip file.
+///
+/// * `src`: input file
+/// * `dst`: output file
+/// * `edit`: A function that takes the path of the extracted sqlite3 file and modifies it.
+pub fn edit_clip_file<P1: AsRef<Path>, P2: AsRef<Path>, F>(
+  src: P1,
+  dst: P2,
+  edit: F,
 ) -> Result<(), ClipError>
 where
-  F: Fn(&str) -> bool + Copy,
+  F: FnOnce(&Path) -> Result<(), ClipError>,
 {
-  let dir = match tempdir() {
+  let doc = ClipDocument::open(src)?;
+  edit(doc.sql_path())?;
+  return doc.save(dst);
+}
+
+/// Brief.
+///
+/// Move `src` to `dst` in two phases.
+///
+/// The data is first staged in a temporary file in the destination directory
+/// (renamed there, or copied when `src` is on another file system) and synced.
+/// Then the staged file is atomically swapped with `dst` (see `swap::exchange`),
+/// or renamed over it where swapping is not available,
+/// so `dst` holds either the old or the new complete data even if the process crashes.
+/// `dst` is never written directly, because it may be hard linked to the backup of the input:
+/// if no temporary file can be created there, nothing is written.
+/// `src` is removed after a successful copy.
+///
+/// * `src`: file to move
+/// * `dst`: destination file path
+fn move_file(src: &Path, dst: &Path) -> Result<(), ClipError> {
+  let metadata = match std::fs::metadata(src) {
     Ok(x) => x,
-    Err(_) => return Err(ClipError::TmpDirError),
+    Err(_) => return Err(ClipError::FileReadError),
   };
-  let dir_path = dir.path();
-
-  let sql_pathbuf = dir_path.join("sql.sql");
-  let sql_path = sql_pathbuf.as_path();
-  let out_pathbuf = dir_path.join("out.clip");
-  let out_path = out_pathbuf.as_path();
-
-  let (sqlsize, index) = match find_sqlite(&src)? {
-    Some(x) => x,
-    None => return Err(ClipError::NotClipFile),
+  let expected = metadata.len();
+  let dst_dir = match dst.parent() {
+    Some(x) if !x.as_os_str().is_empty() => x,
+    _ => Path::new("."),
+  };
+  // keep the permissions of the replaced file
+  let permissions = match std::fs::metadata(dst) {
+    Ok(x) => x.permissions(),
+    Err(_) => metadata.permissions(),
   };
-  save_sql_only(&src, sql_path, sqlsize, index)?;
-  rename_layers_in_sqlite(&sql_path, root_layer_base_name, rename_layer)?;
-  concat_sql(&src, &sql_path, &out_path, index)?;
-
-  let dst_path: &Path = dst.as_ref();
-  if let Some(parent) = dst_path.parent() {
-    if !parent.exists() {
-      if let Err(_) = std::fs::create_dir_all(parent) {
-        return Err(ClipError::CreateDirectoryError);
-      }
-    }
-  }
 
-  if let Err(_) = std::fs::rename(&out_path, &dst_path) {
-    if let Err(_) = std::fs::copy(out_path, dst_path) {
+  let mut tmp = match tempfile::NamedTempFile::new_in(dst_dir) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  let _registered = cleanup::Registered::new(tmp.path());
+  if let Ok(_) = std::fs::rename(src, tmp.path()) {
+    let staged = match std::fs::OpenOptions::new().write(true).open(tmp.path()) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::FileSaveError),
+    };
+    if let Err(_) = staged.sync_all() {
       return Err(ClipError::FileSaveError);
     }
+  } else {
+    copy_synced(src, tmp.as_file_mut(), expected)?;
+    if let Err(_) = std::fs::remove_file(src) {
+      return Err(ClipError::IOError);
+    }
   }
+  // NamedTempFile is created with restricted permissions.
+  let _ = std::fs::set_permissions(tmp.path(), permissions);
 
-  if let Err(_) = dir.close() {
-    return Err(ClipError::IOError);
+  if dst.is_file() && swap::exchange(tmp.path(), dst) {
+    // the old data is at the temporary path now and removed with it
+    drop(tmp);
+  } else if let Err(_) = tmp.persist(dst) {
+    return Err(ClipError::FileSaveError);
   }
+  swap::sync_directory(dst_dir);
+  return Ok(());
+}
 
+/// Brief.
+///
+/// Copy `src` into `outf`, fsync it and check that `expected` bytes were written.
+fn copy_synced(src: &Path, outf: &mut File, expected: u64) -> Result<(), ClipError> {
+  let mut inf = match File::open(src) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  let copied = match std::io::copy(&mut inf, outf) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if let Err(_) = outf.sync_all() {
+    return Err(ClipError::FileSaveError);
+  }
+  let written = match outf.metadata() {
+    Ok(x) => x.len(),
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if copied != expected || written != expected {
+    return Err(ClipError::FileSaveError);
+  }
   return Ok(());
 }
 
-const BUFFER_SIZE: usize = 1024;
+/// size of the buffer used to copy data between files
+const COPY_BUFFER_SIZE: usize = 1024;
+/// size of a block read at once while scanning a clip file
+const SCAN_BLOCK_SIZE: usize = 64 * 1024;
 const READ_BLOCK_SIZE: usize = SQL_HEADER_TOTAL_SIZE;
-struct Buffer {
-  io: BufReader<File>,
+
+/// Brief.
+///
+/// Reads a file as a sequence of large windows.
+///
+/// Consecutive windows overlap by `READ_BLOCK_SIZE - 1` bytes,
+/// so that every `READ_BLOCK_SIZE` bytes sequence of the file is contained in one window.
+struct Buffer<R> {
+  io: R,
   pos: usize,
-  bufidx: usize,
-  bufsize: usize,
+  len: usize,
   eof: bool,
-  buf: [u8; BUFFER_SIZE],
+  buf: Vec<u8>,
 }
 
-impl Buffer {
+impl<R: Read> Buffer<R> {
+  /// Brief.
+  ///
+  /// Read the next window.
+  ///
+  /// Return.
+  ///
+  /// `(position, window)`
+  ///
+  /// * `position`: position of the window in the file
+  /// * `window`: data
   fn next(&mut self) -> Result<Option<(usize, &[u8])>, ClipError> {
-    if self.bufsize == 0 && !self.eof {
-      self.bufsize = match self.io.read(&mut self.buf) {
-        Ok(x) => x,
-        Err(_) => return Err(ClipError::FileReadError),
-      };
-      self.eof = self.bufsize < READ_BLOCK_SIZE;
+    if self.eof {
+      return Ok(None);
     }
-
-    if self.bufidx + READ_BLOCK_SIZE > self.bufsize {
-      if self.eof {
-        return Ok(None);
-      }
-      //move
-      let idx = self.bufidx;
-      let rest = self.bufsize - idx;
-      self.bufidx = 0;
-
-      for i in 0..rest {
-        self.buf[i] = self.buf[idx + i];
-      }
-      let read_size = match self.io.read(&mut self.buf[idx..]) {
-        Ok(x) => x,
+    if self.len != 0 {
+      let keep = std::cmp::min(self.len, READ_BLOCK_SIZE - 1);
+      self.buf.copy_within(self.len - keep..self.len, 0);
+      self.pos += self.len - keep;
+      self.len = keep;
+    }
+    let filled = self.len;
+    while self.len < self.buf.len() {
+      match self.io.read(&mut self.buf[self.len..]) {
+        Ok(0) => {
+          self.eof = true;
+          break;
+        }
+        Ok(x) => self.len += x,
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
         Err(_) => return Err(ClipError::FileReadError),
-      };
-      self.bufsize = rest + read_size;
-      self.eof = self.bufsize < READ_BLOCK_SIZE;
-      if self.eof {
-        return Ok(None);
       }
     }
-    let idx = self.bufidx;
-    let pos = self.pos;
-    self.bufidx = idx + 1;
-    self.pos += 1;
-    return Ok(Some((pos, &self.buf[idx..idx + READ_BLOCK_SIZE])));
+    if self.eof && self.len == filled && filled != 0 {
+      // nothing new since the last window
+      return Ok(None);
+    }
+    return Ok(Some((self.pos, &self.buf[..self.len])));
   }
 
-  fn new(path: &Path) -> Result<Buffer, ClipError> {
-    return Ok(Buffer {
-      io: BufReader::new(match File::open(path) {
-        Ok(x) => x,
-        Err(_) => return Err(ClipError::FileOpenError),
-      }),
+  fn new(io: R) -> Buffer<R> {
+    return Buffer {
+      io,
       pos: 0,
-      bufidx: 0,
-      bufsize: 0,
+      len: 0,
       eof: false,
-      buf: unsafe { std::mem::MaybeUninit::zeroed().assume_init() },
-    });
+      buf: vec![0; SCAN_BLOCK_SIZE],
+    };
   }
 }
 
@@ -176,6 +478,9 @@ impl Buffer {
 ///
 /// find sqlite3 data size & start position
 ///
+/// Each window is searched for the chunk magic (`CHNKSQLi`) with memchr,
+/// and only the hits are checked for the sqlite3 header.
+///
 /// * `path`: clip file path
 ///
 /// Return.
@@ -184,29 +489,171 @@ impl Buffer {
 ///
 /// * `size`: sqlite3 data size
 /// * `position` : sqlite3 data position in the file
-fn find_sqlite<P: AsRef<Path>>(path: P) -> Result<Option<(u64, usize)>, ClipError> {
-  let mut buf = Buffer::new(path.as_ref())?;
-  'outer: loop {
-    let (pos, data) = match buf.next()? {
-      Some(x) => x,
-      None => return Ok(None),
-    };
-    for i in 0..SQL_CHANK_LEN {
-      if data[i] != SQL_CHANK[i] {
-        continue 'outer;
+pub fn find_sqlite<P: AsRef<Path>>(path: P) -> Result<Option<(u64, usize)>, ClipError> {
+  return find_sqlite_with(path, &CancelToken::new());
+}
+
+/// `find_sqlite` checking the cancel token for each window.
+fn find_sqlite_with<P: AsRef<Path>>(
+  path: P,
+  cancel: &CancelToken,
+) -> Result<Option<(u64, usize)>, ClipError> {
+  let mut file = match open_shared(path) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  let size = match file.metadata() {
+    Ok(x) => x.len(),
+    Err(_) => return Err(ClipError::FileReadError),
+  };
+  info::check_truncated(&mut file, size)?;
+  if let Err(_) = file.seek(SeekFrom::Start(0)) {
+    return Err(ClipError::IOError);
+  }
+  return scan_sqlite(file, cancel);
+}
+
+/// Brief.
+///
+/// Parse the header of the sqlite3 chunk: `CHNKSQLi`, the chunk length,
+/// and optional extra fields before the sqlite3 data.
+///
+/// * `data`: data starting with `CHNKSQLi`
+///
+/// Return.
+///
+/// `(length, extra)`. `None` if the sqlite3 data does not follow.
+///
+/// * `length`: chunk length written in the header
+/// * `extra`: bytes between the chunk length and the sqlite3 data
+fn parse_sql_chunk_header(data: &[u8]) -> Option<(u64, usize)> {
+  if !data.starts_with(SQL_CHANK) {
+    return None;
+  }
+  let fields = SQL_CHANK_LEN + CHUNK_LENGTH_SIZE;
+  let length = u64::from_be_bytes(data.get(SQL_CHANK_LEN..fields)?.try_into().unwrap());
+  let extra = (0..=SQL_CHUNK_MAX_EXTRA).find(|x| {
+    data
+      .get(fields + x..)
+      .is_some_and(|d| d.starts_with(SQL_HEADER))
+  })?;
+  if length < extra as u64 {
+    return None;
+  }
+  return Some((length, extra));
+}
+
+/// Brief.
+///
+/// Find the length field of the sqlite3 chunk from the position of the sqlite3 data.
+///
+/// * `inf`: clip file data
+/// * `index`: sqlite3 data position
+///
+/// Return.
+///
+/// `(position, extra)`
+///
+/// * `position`: position of the chunk length field
+/// * `extra`: bytes between the chunk length and the sqlite3 data
+fn find_sql_chunk_length<R: Read + Seek>(
+  inf: &mut R,
+  index: u64,
+) -> Result<(u64, usize), ClipError> {
+  let start =
+    index.saturating_sub((SQL_CHANK_LEN + CHUNK_LENGTH_SIZE + SQL_CHUNK_MAX_EXTRA) as u64);
+  let mut data = vec![0u8; (index - start) as usize + SQL_HEADER_LEN];
+  if let Err(_) = inf.seek(SeekFrom::Start(start)) {
+    return Err(ClipError::IOError);
+  }
+  if let Err(_) = inf.read_exact(&mut data) {
+    return Err(ClipError::FileReadError);
+  }
+  // the nearest header before the data
+  for i in memmem::rfind_iter(&data, SQL_CHANK) {
+    if let Some((_, extra)) = parse_sql_chunk_header(&data[i..]) {
+      let position = start + (i + SQL_CHANK_LEN) as u64;
+      if position + (CHUNK_LENGTH_SIZE + extra) as u64 == index {
+        return Ok((position, extra));
       }
     }
+  }
+  return Err(ClipError::UnknownFileStruct);
+}
 
-    for i in 0..SQL_HEADER_LEN {
-      if data[i + SQL_CHANK_LEN + 8] != SQL_HEADER[i] {
-        continue 'outer;
-      }
+/// Brief.
+///
+/// `find_sqlite` for any reader. The reader is read from its current position,
+/// and `position` is relative to it.
+///
+/// The data must start with the clip file header. Other file types (PSD, PNG, zip, ...)
+/// are rejected with `ClipError::UnexpectedFileType` before scanning.
+pub fn find_sqlite_in<R: Read>(reader: R) -> Result<Option<(u64, usize)>, ClipError> {
+  return scan_sqlite(reader, &CancelToken::new());
+}
+
+/// `find_sqlite_in` checking the cancel token for each window.
+fn scan_sqlite<R: Read>(
+  mut reader: R,
+  cancel: &CancelToken,
+) -> Result<Option<(u64, usize)>, ClipError> {
+  let head = sniff::read_head(&mut reader)?;
+  sniff::check_file_magic(&head)?;
+  let mut buf = Buffer::new(Cursor::new(head).chain(reader));
+  let finder = memmem::Finder::new(SQL_CHANK);
+  while let Some((pos, window)) = buf.next()? {
+    cancel.check()?;
+    for i in finder.find_iter(window) {
+      let data = match window.get(i..i + READ_BLOCK_SIZE) {
+        Some(x) => x,
+        // continued in the next window
+        None => break,
+      };
+      let (length, extra) = match parse_sql_chunk_header(data) {
+        Some(x) => x,
+        None => continue,
+      };
+      let start = SQL_CHANK_LEN + CHUNK_LENGTH_SIZE + extra;
+      return Ok(Some((length - extra as u64, pos + i + start)));
     }
+  }
+  return Ok(None);
+}
 
-    let sqlsize_buf: [u8; 8] = data[SQL_CHANK_LEN..SQL_CHANK_LEN + 8].try_into().unwrap();
-    let sqlsize = u64::from_be_bytes(sqlsize_buf);
-    return Ok(Some((sqlsize, pos + SQL_CHANK_LEN + 8)));
+/// Brief.
+///
+/// Copy the sqlite3 data in a clip file to any writer.
+///
+/// The reader is read from the beginning. No temporary file is used.
+///
+/// * `reader`: clip file data
+/// * `writer`: output of the sqlite3 data
+///
+/// Return.
+///
+/// size of the sqlite3 data
+pub fn copy_sqlite_chunk<R: Read + Seek, W: Write>(
+  mut reader: R,
+  mut writer: W,
+) -> Result<u64, ClipError> {
+  if let Err(_) = reader.seek(SeekFrom::Start(0)) {
+    return Err(ClipError::IOError);
+  }
+  let (size, index) = match find_sqlite_in(&mut reader)? {
+    Some(x) => x,
+    None => return Err(ClipError::NotClipFile),
+  };
+  if let Err(_) = reader.seek(SeekFrom::Start(index as u64)) {
+    return Err(ClipError::IOError);
   }
+  let copied = match std::io::copy(&mut reader.take(size), &mut writer) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if copied != size {
+    return Err(ClipError::FileReadError);
+  }
+  return Ok(size);
 }
 
 /// Brief.
@@ -217,13 +664,15 @@ fn find_sqlite<P: AsRef<Path>>(path: P) -> Result<Option<(u64, usize)>, ClipErro
 /// * `splout`: output sqlite3 file path
 /// * `size`: sqlite3 data size.
 /// * `index`: sqlite3 data position in the clip file.
+/// * `cancel`: checked for each block
 fn save_sql_only<P1: AsRef<Path>, P2: AsRef<Path>>(
   clip: P1,
   sqlout: P2,
   size: u64,
   index: usize,
+  cancel: &CancelToken,
 ) -> Result<(), ClipError> {
-  let mut inf = BufReader::new(match File::open(&clip) {
+  let mut inf = BufReader::new(match open_shared(&clip) {
     Ok(x) => x,
     Err(_) => return Err(ClipError::FileOpenError),
   });
@@ -236,20 +685,23 @@ fn save_sql_only<P1: AsRef<Path>, P2: AsRef<Path>>(
     Err(_) => return Err(ClipError::FileSaveError),
   });
 
-  let mut buf: [u8; 1024] = unsafe { mem::MaybeUninit::zeroed().assume_init() };
+  let mut buf = [0u8; COPY_BUFFER_SIZE];
   let mut writesize = size as usize;
   while writesize != 0 {
-    let length = if writesize as usize > buf.len() {
+    cancel.check()?;
+    let length = if writesize > buf.len() {
       buf.len()
     } else {
       writesize
     };
 
     let read = match inf.read(&mut buf[0..length]) {
+      // the file ended before the sqlite3 data
+      Ok(0) => return Err(ClipError::FileReadError),
       Ok(x) => x,
       Err(_) => return Err(ClipError::FileReadError),
     };
-    if let Err(_) = outf.write_all(&mut buf[0..read]) {
+    if let Err(_) = outf.write_all(&buf[0..read]) {
       return Err(ClipError::FileSaveError);
     }
     writesize -= read;
@@ -266,25 +718,49 @@ fn save_sql_only<P1: AsRef<Path>, P2: AsRef<Path>>(
 /// * `srcsql` : the sqlite3 file path
 /// * `dstclip` : the output clip file pth
 /// * `index` : the sqlite3 data position in the srclip file
+/// * `tail` : position of the data after the sqlite3 data in the srcclip file (the footer chunk and
+///   anything appended after it), which is copied as it is. A footer chunk is written if there is no data.
+/// * `cancel` : checked for each block
 fn concat_sql<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
   srcclip: P1,
   srcsql: P2,
   dstclip: P3,
   index: usize,
+  tail: u64,
+  cancel: &CancelToken,
 ) -> Result<(), ClipError> {
-  let mut outf = BufWriter::new(match File::create(dstclip) {
+  let outf = BufWriter::new(match File::create(dstclip) {
     Ok(x) => x,
     Err(_) => return Err(ClipError::FileSaveError),
   });
-  let mut buf: [u8; 1024] = unsafe { mem::MaybeUninit::zeroed().assume_init() };
+  return concat_sql_to(srcclip, srcsql, outf, index, tail, cancel);
+}
+
+/// Brief
+///
+/// `concat_sql` writing to any writer.
+fn concat_sql_to<P1: AsRef<Path>, P2: AsRef<Path>, W: Write + Seek>(
+  srcclip: P1,
+  srcsql: P2,
+  mut outf: W,
+  index: usize,
+  tail: u64,
+  cancel: &CancelToken,
+) -> Result<(), ClipError> {
+  let mut buf = [0u8; COPY_BUFFER_SIZE];
+  let mut inf = BufReader::new(match open_shared(&srcclip) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  });
+  let (length_position, extra) = find_sql_chunk_length(&mut inf, index as u64)?;
+  if let Err(_) = inf.seek(SeekFrom::Start(0)) {
+    return Err(ClipError::IOError);
+  }
   {
-    // Original meta data
-    let mut inf = BufReader::new(match File::open(&srcclip) {
-      Ok(x) => x,
-      Err(_) => return Err(ClipError::FileOpenError),
-    });
-    let mut write_size: usize = index - 8;
+    // Original meta data and the chunk header
+    let mut write_size: usize = index;
     while write_size != 0 {
+      cancel.check()?;
       let read_length = std::cmp::min(write_size, buf.len());
       let slice = &mut buf[0..read_length];
 
@@ -299,11 +775,6 @@ fn concat_sql<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
     }
   }
   let mut sqlsize: u64 = 0;
-  // write dummy size
-  let bytes = sqlsize.to_be_bytes();
-  if let Err(_) = outf.write_all(&bytes) {
-    return Err(ClipError::FileSaveError);
-  }
 
   {
     // SQLite
@@ -313,6 +784,7 @@ fn concat_sql<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
     });
 
     loop {
+      cancel.check()?;
       let read_length = match inf.read(&mut buf) {
         Ok(x) => x,
         Err(_) => return Err(ClipError::FileReadError),
@@ -328,18 +800,31 @@ fn concat_sql<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
     }
   }
 
-  if let Err(_) = outf.write_all(&FOOT_CHANK_DATA) {
-    return Err(ClipError::FileSaveError);
+  // Footer and trailing data
+  if let Err(_) = inf.seek(SeekFrom::Start(tail)) {
+    return Err(ClipError::FileReadError);
+  }
+  let tail_size = match std::io::copy(&mut inf, &mut outf) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if tail_size == 0 {
+    if let Err(_) = outf.write_all(&FOOT_CHANK_DATA) {
+      return Err(ClipError::FileSaveError);
+    }
   }
 
-  if let Err(_) = outf.seek(SeekFrom::Start((index as u64) - 8)) {
+  if let Err(_) = outf.seek(SeekFrom::Start(length_position)) {
     return Err(ClipError::FileSaveError);
   }
 
-  let bytes = sqlsize.to_be_bytes();
+  let bytes = (extra as u64 + sqlsize).to_be_bytes();
   if let Err(_) = outf.write_all(&bytes) {
     return Err(ClipError::FileSaveError);
   }
+  if let Err(_) = outf.flush() {
+    return Err(ClipError::FileSaveError);
+  }
 
   return Ok(());
 }
@@ -349,35 +834,212 @@ fn concat_sql<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
 /// Rename layers
 ///
 /// * `sqlfile`: sqlite3 file path
-/// * `root_layer_base_name` : top level layer base name
-/// * `need_rename`: A function that takes a layer name as an argument and decides whether to change the layer name.
-pub fn rename_layers_in_sqlite<P: AsRef<Path>, F>(
+/// * `options`: options of renaming
+///
+/// The rule for empty layers needs the pixel data in the clip file and is ignored here.
+/// Use `ClipDocument::rename_layers` for it.
+pub fn rename_layers_in_sqlite<P: AsRef<Path>>(
   sqlfile: P,
-  root_layer_base_name: &str,
-  need_rename: F,
-) -> Result<(), ClipError>
-where
-  F: Fn(&str) -> bool + Copy,
-{
+  options: &RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  return rename_layers_with_empty(sqlfile.as_ref(), options, HashSet::new());
+}
+
+/// Brief
+///
+/// `rename_layers_in_sqlite` with the layers which have no visible pixels.
+///
+/// * `empty_layers`: main_id of empty layers (`pixels::find_empty_layers`)
+fn rename_layers_with_empty(
+  sqlfile: &Path,
+  options: &RenameOptions,
+  empty_layers: HashSet<u64>,
+) -> Result<RenameReport, ClipError> {
   let conn = match rusqlite::Connection::open(sqlfile) {
     Ok(x) => x,
     Err(_) => return Err(ClipError::SQLError),
   };
+  let plan = plan_renames_in(&conn, options, empty_layers)?;
+  return plan::apply_plan_in_transaction(
+    &conn,
+    &plan,
+    DEFAULT_UPDATE_BATCH_SIZE,
+    &CancelToken::new(),
+  );
+}
+
+/// Brief
+///
+/// Decide new layer names without changing the data base.
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `options`: options of renaming
+///
+/// The rule for empty layers needs the pixel data in the clip file and is ignored here.
+/// Use `ClipDocument::plan_renames` for it.
+pub fn plan_renames_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  options: &RenameOptions,
+) -> Result<RenamePlan, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return plan_renames_in(&conn, options, HashSet::new());
+}
+
+/// Brief
+///
+/// Write the new names of a plan (`plan_renames_in_sqlite`).
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `plan`: new names
+pub fn apply_plan_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  plan: &RenamePlan,
+) -> Result<RenameReport, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return plan::apply_plan_in_transaction(
+    &conn,
+    plan,
+    DEFAULT_UPDATE_BATCH_SIZE,
+    &CancelToken::new(),
+  );
+}
+
+/// Brief
+///
+/// Decide new layer names with the rules. The data base is only read.
+///
+/// * `conn`: sqlite3
+/// * `options`: options of renaming
+/// * `empty_layers`: main_id of empty layers (`pixels::find_empty_layers`)
+fn plan_renames_in(
+  conn: &rusqlite::Connection,
+  options: &RenameOptions,
+  empty_layers: HashSet<u64>,
+) -> Result<RenamePlan, ClipError> {
   let mut v: Vec<Box<ClipLayer>> = Vec::new();
-  let root_main_id = get_layers(&conn, &mut v)?;
-  let root_index = match find_layer_index(&v, root_main_id) {
-    Some(x) => x,
-    None => panic!("FATAL: root layer not found"),
+  let mut roots = get_layer_roots(conn, &mut v)?;
+  if let Some(n) = options.canvas {
+    if n == 0 || n > roots.len() {
+      return Err(ClipError::CanvasNotFound(n, roots.len()));
+    }
+    roots = vec![roots[n - 1]];
+  }
+  let options = options.for_layer_names(v.iter().map(|x| x.layer_name.as_str()));
+  let options: &RenameOptions = &options;
+  let mut report = RenameReport::default();
+  // each canvas is renamed as a file of its own
+  for root_main_id in roots {
+    let root_index = match find_layer_index(&v, root_main_id) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    let r = if options.renumber_folders {
+      renumber::renumber_top_folders(conn, &v, root_index, options)?
+    } else if options.frame_ranges {
+      timeline::rename_cels(conn, &v, root_index, options)?
+    } else {
+      rename_tree(conn, &v, root_index, options, empty_layers.clone())?
+    };
+    // the number of layers is counted in the whole file
+    report.total_layers = r.total_layers;
+    report.renamed.extend(r.renamed);
+    report.warnings.extend(r.warnings);
+  }
+  if let Some(selection) = &options.selection {
+    if let Some(id) = selection
+      .iter()
+      .find(|x| find_layer_index(&v, **x).is_none())
+    {
+      return Err(ClipError::LayerNotFound(*id));
+    }
+    report.renamed.retain(|r| selection.contains(&r.main_id));
+  }
+  collision::resolve_collisions(&v, &mut report, options.on_collision)?;
+  if let Some(style) = options.old_name_style.filter(|_| !options.frame_ranges) {
+    for r in report.renamed.iter_mut() {
+      r.new_name = style.format(&r.new_name, &r.old_name);
+    }
+  }
+  return Ok(RenamePlan::from_report(
+    report,
+    options.keep_old_names,
+    options.vector_names,
+  ));
+}
+
+/// Brief
+///
+/// Rename layers in the whole tree with the rules.
+///
+/// * `conn`: sqlite3
+/// * `v`: all layer information
+/// * `root_index`: root folder index of `v`
+/// * `options`: options of renaming
+/// * `empty_layers`: main_id of empty layers
+fn rename_tree(
+  conn: &rusqlite::Connection,
+  v: &[Box<ClipLayer>],
+  root_index: usize,
+  options: &RenameOptions,
+  empty_layers: HashSet<u64>,
+) -> Result<RenameReport, ClipError> {
+  let root_main_id = v[root_index].main_id;
+  let color_labels = if options.rules.use_color_labels {
+    color::get_color_labels(conn)?
+  } else {
+    HashMap::new()
+  };
+  let material_names = if options.rules.use_material_names {
+    material::get_material_names(conn)?
+  } else {
+    HashMap::new()
+  };
+  let creation_times = if options.rules.order == NumberingOrder::Created {
+    creation::get_creation_times(conn)?
+  } else {
+    HashMap::new()
+  };
+  let clipped_layers = if options.clipping_names {
+    clipping::get_clipped_layers(conn)?
+  } else {
+    HashSet::new()
+  };
+  let settings = RenameSettings {
+    options,
+    material_names,
+    color_labels,
+    empty_layers,
+    creation_times,
+    clipped_layers,
+  };
+  let mut report = RenameReport {
+    total_layers: v
+      .iter()
+      .filter(|x| {
+        x.layer_folder == 0
+          || (x.main_id != root_main_id
+            && (options.include_folders || options.rules.dictionary.contains_key(&x.layer_name)))
+      })
+      .count(),
+    renamed: Vec::new(),
+    warnings: Vec::new(),
   };
   rename_layers_in_folder(
-    &conn,
-    &v,
+    v,
     root_index,
     true,
-    root_layer_base_name,
-    need_rename,
+    &options.root_layer_base_name,
+    &settings,
+    &mut 1,
+    &mut report,
   )?;
-  return Ok(());
+  return Ok(report);
 }
 
 /// Brief
@@ -388,39 +1050,120 @@ where
 ///
 /// Return.
 ///
-/// root folder main_id
+/// root folder main_id. `ClipError::AmbiguousRoot` if the file has several canvases.
 fn get_layers(conn: &rusqlite::Connection, v: &mut Vec<Box<ClipLayer>>) -> Result<u64, ClipError> {
-  let mut stmt = match conn.prepare("SELECT _PW_ID, MainId, LayerName, LayerType, LayerFolder, LayerNextIndex, LayerFIrstChildIndex FROM Layer") {
+  let roots = get_layer_roots(conn, v)?;
+  return match roots.len() {
+    1 => Ok(roots[0]),
+    n => Err(ClipError::AmbiguousRoot(n)),
+  };
+}
+
+/// Brief
+///
+/// Get layer information from sqlite3 data base, with the root folders of all canvases.
+///
+/// `v`: output. ClipLayer vector
+///
+/// Return.
+///
+/// root folder main_ids in the order of the Canvas table (at least one)
+fn get_layer_roots(
+  conn: &rusqlite::Connection,
+  v: &mut Vec<Box<ClipLayer>>,
+) -> Result<Vec<u64>, ClipError> {
+  let mut layers = Layers::new(conn)?;
+  let mut candidates: Vec<u64> = Vec::new();
+  for layer in layers.iter()? {
+    let layer = layer?;
+    if layer.layer_type == 256 && layer.folder {
+      candidates.push(layer.main_id);
+    }
+    v.push(Box::new(ClipLayer {
+      pw_id: layer.pw_id,
+      main_id: layer.main_id,
+      layer_name: layer.name,
+      layer_type: layer.layer_type,
+      layer_folder: if layer.folder { 1 } else { 0 },
+      layer_next_index: layer.next_index,
+      layer_first_child_index: layer.first_child_index,
+      kind: layer.kind,
+      special: None,
+    }));
+  }
+  v.sort_by_key(|a| a.main_id);
+  special::detect_special_layers(conn, v)?;
+
+  let roots = get_canvas_root_folders(conn)?;
+  if !roots.is_empty() {
+    for root in roots.iter() {
+      match find_layer_index(v, *root) {
+        Some(i) if v[i].layer_folder != 0 => {}
+        _ => return Err(ClipError::UnknownFileStruct),
+      }
+    }
+    return Ok(roots);
+  }
+
+  return match candidates.len() {
+    0 => Err(ClipError::UnknownFileStruct),
+    1 => Ok(candidates),
+    n => Err(ClipError::AmbiguousRoot(n)),
+  };
+}
+
+/// Brief
+///
+/// Get column names of the Layer table.
+fn get_layer_columns(conn: &rusqlite::Connection) -> Result<Vec<String>, ClipError> {
+  let mut stmt = match conn.prepare("PRAGMA table_info(Layer)") {
     Ok(x) => x,
     Err(_) => return Err(ClipError::SQLError),
   };
-  let layer_itr = match stmt.query_map([], |row| {
-    Ok(ClipLayer {
-      pw_id: row.get(0)?,
-      main_id: row.get(1)?,
-      layer_name: row.get(2)?,
-      layer_type: row.get(3)?,
-      layer_folder: row.get(4)?,
-      layer_next_index: row.get(5)?,
-      layer_first_child_index: row.get(6)?,
-    })
-  }) {
+  let rows = match stmt.query_map([], |row| row.get::<_, String>(1)) {
     Ok(x) => x,
     Err(_) => return Err(ClipError::SQLError),
   };
-  let mut root_main_id: Option<u64> = None;
-  for layer in layer_itr {
-    let b = Box::new(layer.unwrap());
-    if b.layer_type == 256 && b.layer_folder == 1 {
-      root_main_id = Some(b.main_id);
+  let mut columns: Vec<String> = Vec::new();
+  for r in rows {
+    match r {
+      Ok(x) => columns.push(x),
+      Err(_) => return Err(ClipError::SQLError),
     }
-    v.push(b);
   }
-  v.sort_by(|a, b| a.main_id.cmp(&b.main_id));
-  match root_main_id {
-    Some(x) => return Ok(x),
-    None => return Err(ClipError::UnknownFileStruct),
+  return Ok(columns);
+}
+
+/// Brief
+///
+/// Read the root folder references of the canvases (`Canvas.CanvasRootFolder`).
+///
+/// Return.
+///
+/// main_ids of the root folders in the order of the Canvas table,
+/// or an empty vector when the file has no usable reference.
+fn get_canvas_root_folders(conn: &rusqlite::Connection) -> Result<Vec<u64>, ClipError> {
+  let mut stmt = match conn.prepare("SELECT CanvasRootFolder FROM Canvas ORDER BY MainId") {
+    Ok(x) => x,
+    Err(_) => return Ok(Vec::new()),
+  };
+  let rows = match stmt.query_map([], |row| row.get::<_, Option<u64>>(0)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut roots: Vec<u64> = Vec::new();
+  for r in rows {
+    match r {
+      Ok(Some(x)) if x != 0 => {
+        if !roots.contains(&x) {
+          roots.push(x);
+        }
+      }
+      Ok(_) => continue,
+      Err(_) => return Err(ClipError::SQLError),
+    }
   }
+  return Ok(roots);
 }
 
 /// Brief
@@ -433,86 +1176,487 @@ fn get_layers(conn: &rusqlite::Connection, v: &mut Vec<Box<ClipLayer>>) -> Resul
 /// Return.
 ///
 /// index of `v`
-fn find_layer_index(v: &Vec<Box<ClipLayer>>, main_id: u64) -> Option<usize> {
-  return match v.binary_search_by_key(&main_id, |x| x.main_id) {
-    Ok(x) => Some(x),
-    Err(_) => None,
-  };
+fn find_layer_index(v: &[Box<ClipLayer>], main_id: u64) -> Option<usize> {
+  return v.binary_search_by_key(&main_id, |x| x.main_id).ok();
+}
+
+/// names of layers with a color label (`RenameRules::use_color_labels`)
+const COLOR_LABEL_TEMPLATE: &str = "{parent}{n}";
+/// names of clipped layers. The base layer name is used as `{parent}`.
+const CLIPPING_TEMPLATE: &str = "{parent} clip {n}";
+
+/// Settings of renaming shared by all folders.
+struct RenameSettings<'a> {
+  options: &'a RenameOptions,
+  material_names: HashMap<u64, String>,
+  color_labels: HashMap<u64, &'static str>,
+  empty_layers: HashSet<u64>,
+  /// creation times for `NumberingOrder::Created`
+  creation_times: HashMap<u64, i64>,
+  /// layers clipped to the layer below (`RenameOptions::clipping_names`)
+  clipped_layers: HashSet<u64>,
 }
 
 /// Brief
 ///
-/// Recursively rename layers in the folders.
+/// Recursively plan the new names of layers in the folders.
 ///
-/// * `conn`: sqlite3
 /// * `v`: all layer information
 /// * `index`: target folder index of `v`
 /// * `root`: whether is the folder a top level folder?
-/// * `root_layer_base_name` : top level layer base name
-/// * `need_rename`: A function that takes a layer name as an argument and decides whether to change the layer name.
-fn rename_layers_in_folder<F>(
-  conn: &rusqlite::Connection,
-  v: &Vec<Box<ClipLayer>>,
+/// * `parent_name`: name used as `{parent}` for the layers in the folder
+/// * `settings`: rename settings
+/// * `counter`: the next number when the counter is shared by the whole tree (`global_counter`)
+/// * `report`: output. layers to be renamed are added.
+fn rename_layers_in_folder(
+  v: &[Box<ClipLayer>],
   index: usize,
   root: bool,
-  root_layer_base_name: &str,
-  need_rename: F,
-) -> Result<(), ClipError>
-where
-  F: Fn(&str) -> bool + Copy,
-{
+  parent_name: &str,
+  settings: &RenameSettings,
+  counter: &mut u64,
+  report: &mut RenameReport,
+) -> Result<(), ClipError> {
   let f = &v[index];
+  let options = settings.options;
 
   if f.layer_folder == 0 {
     return Err(ClipError::UnknownFileStruct);
   }
 
-  let mut next = f.layer_first_child_index;
-  let mut layer_number = 1 + get_max_layer_number(v, index, root, root_layer_base_name)?;
+  let mut local_number = 1;
+  let layer_number = if options.global_counter {
+    &mut *counter
+  } else {
+    &mut local_number
+  };
+  let mut empty_number = 1;
+  if options.continue_numbering {
+    for template in options.rules.templates() {
+      *layer_number = std::cmp::max(
+        *layer_number,
+        1 + get_max_layer_number(
+          v,
+          index,
+          parent_name,
+          template,
+          options.include_folders,
+          options,
+        )?,
+      );
+    }
+    if let Some(RuleAction::Template(template)) = &options.rules.empty_rule {
+      empty_number = 1 + get_max_layer_number(v, index, parent_name, template, false, options)?;
+    }
+  }
+  // color label → next number. Names are `{color}{n}`, i.e. the color is used as `{parent}`.
+  let color_template = NameTemplate::parse(COLOR_LABEL_TEMPLATE).unwrap();
+  let mut color_numbers: HashMap<&str, u64> = HashMap::new();
+  // layer kind → next `{type_index}`
+  let mut kind_numbers: HashMap<&str, u64> = HashMap::new();
+  let can_rename = !root || !parent_name.is_empty();
+
+  let mut children = get_children(v, index)?;
+  let clipping_bases = get_clipping_bases(v, &children, &settings.clipped_layers);
+  // clipped layers are renamed after their base layers
+  let mut clipped: Vec<usize> = Vec::new();
+  order_children(
+    v,
+    &mut children,
+    options.rules.order,
+    &settings.creation_times,
+  );
+  // the smallest number of the next child when every child is counted
+  let mut position = *layer_number;
+  for ci in children {
+    if options.count_all_layers {
+      *layer_number = std::cmp::max(*layer_number, position);
+      position = *layer_number + 1;
+    }
+    let c = &v[ci];
+    let translation = match options.rules.dictionary.get(&c.layer_name) {
+      Some(x) if !is_kept(c, options) => Some(x),
+      _ => None,
+    };
+    if c.layer_folder != 0 {
+      let mut folder_name = c.layer_name.clone();
+      if let Some(name) = translation {
+        folder_name = name.clone();
+        plan_rename(c, &folder_name, report);
+      } else if options.include_folders && can_rename && options.need_rename(&c.layer_name) {
+        let template = &options.rules.template;
+        let kind_n = next_kind_number(
+          v,
+          index,
+          parent_name,
+          template,
+          FOLDER_KIND_NAME,
+          options,
+          &mut kind_numbers,
+        )?;
+        folder_name = template.format_kind_layer(
+          parent_name,
+          *layer_number,
+          c.main_id,
+          &c.layer_name,
+          FOLDER_KIND_NAME,
+          kind_n,
+        );
+        *layer_number += 1;
+        plan_rename(c, &folder_name, report);
+      }
+      rename_layers_in_folder(v, ci, false, &folder_name, settings, layer_number, report)?;
+      continue;
+    }
+    if let Some(name) = translation {
+      plan_rename(c, name, report);
+      continue;
+    }
+    if clipping_bases.contains_key(&ci) {
+      clipped.push(ci);
+      continue;
+    }
+    if let Some(special) = c.special.filter(|x| !options.allow_special.contains(x)) {
+      // special layers keep their names
+      if options.matches_pattern(&c.layer_name) {
+        report.warnings.push(Warning::SkippedSpecial {
+          main_id: c.main_id,
+          name: c.layer_name.clone(),
+          special,
+        });
+      }
+      continue;
+    }
+    if !options.need_rename(&c.layer_name) {
+      if options.matches_pattern(&c.layer_name) {
+        report.warnings.push(Warning::SkippedProtected {
+          main_id: c.main_id,
+          name: c.layer_name.clone(),
+        });
+      }
+      continue;
+    }
+    if let Some(material) = settings.material_names.get(&c.main_id) {
+      plan_rename(c, material, report);
+      continue;
+    }
+    if let Some(color) = settings.color_labels.get(&c.main_id) {
+      if !can_rename {
+        continue;
+      }
+      let n = match color_numbers.get_mut(color) {
+        Some(x) => x,
+        None => {
+          let start = if options.continue_numbering {
+            1 + get_max_layer_number(v, index, color, &color_template, false, options)?
+          } else {
+            1
+          };
+          color_numbers.entry(color).or_insert(start)
+        }
+      };
+      let name = color_template.format_layer(color, *n, c.main_id, &c.layer_name);
+      *n += 1;
+      plan_rename(c, &name, report);
+      continue;
+    }
+    if settings.empty_layers.contains(&c.main_id) {
+      // empty layers have their own numbers
+      if let Some(RuleAction::Template(template)) = &options.rules.empty_rule {
+        if can_rename {
+          let name = template.format_layer(parent_name, empty_number, c.main_id, &c.layer_name);
+          empty_number += 1;
+          plan_rename(c, &name, report);
+        }
+      }
+      continue;
+    }
+    let template = match options.rules.template_for(c.kind) {
+      Some(x) => x,
+      None => continue,
+    };
+    if can_rename {
+      let kind = c.kind.name();
+      let kind_n = next_kind_number(
+        v,
+        index,
+        parent_name,
+        template,
+        kind,
+        options,
+        &mut kind_numbers,
+      )?;
+      let name = template.format_kind_layer(
+        parent_name,
+        *layer_number,
+        c.main_id,
+        &c.layer_name,
+        kind,
+        kind_n,
+      );
+      *layer_number += 1;
+      plan_rename(c, &name, report);
+    }
+  }
+  if can_rename && !clipped.is_empty() {
+    rename_clipped_layers(v, &clipped, &clipping_bases, settings, report);
+  }
+
+  return Ok(());
+}
+
+/// Brief
+///
+/// Find the base layer of each clipped layer in a folder.
+/// A clipped layer is clipped to the nearest layer below it which is not clipped.
+///
+/// * `v`: all layer information
+/// * `children`: indexes of the layers in the folder from the bottom layer
+/// * `clipped_layers`: main_id of clipped layers
+///
+/// Return.
+///
+/// index of the base layer for each index of clipped layers
+fn get_clipping_bases(
+  v: &[Box<ClipLayer>],
+  children: &[usize],
+  clipped_layers: &HashSet<u64>,
+) -> HashMap<usize, usize> {
+  let mut bases: HashMap<usize, usize> = HashMap::new();
+  let mut base: Option<usize> = None;
+  for ci in children.iter() {
+    if !clipped_layers.contains(&v[*ci].main_id) {
+      base = Some(*ci);
+    } else if let Some(b) = base {
+      bases.insert(*ci, b);
+    }
+  }
+  return bases;
+}
+
+/// Brief
+///
+/// Rename clipped layers matching the pattern to `{base} clip {n}` (`CLIPPING_TEMPLATE`).
+/// Must be called after the new names of the base layers are planned.
+///
+/// * `v`: all layer information
+/// * `clipped`: indexes of clipped layers in the numbering order
+/// * `bases`: index of the base layer for each clipped layer (`get_clipping_bases`)
+/// * `settings`: rename settings
+fn rename_clipped_layers(
+  v: &[Box<ClipLayer>],
+  clipped: &[usize],
+  bases: &HashMap<usize, usize>,
+  settings: &RenameSettings,
+  report: &mut RenameReport,
+) {
+  let options = settings.options;
+  let template = NameTemplate::parse(CLIPPING_TEMPLATE).unwrap();
+  let mut numbers: HashMap<usize, u64> = HashMap::new();
+  for ci in clipped.iter() {
+    let c = &v[*ci];
+    if c
+      .special
+      .is_some_and(|x| !options.allow_special.contains(&x))
+      || !options.need_rename(&c.layer_name)
+    {
+      continue;
+    }
+    let base = bases[ci];
+    let base_name = match report
+      .renamed
+      .iter()
+      .rev()
+      .find(|x| x.main_id == v[base].main_id)
+    {
+      Some(x) => x.new_name.clone(),
+      None => v[base].layer_name.clone(),
+    };
+    let n = numbers.entry(base).or_insert_with(|| {
+      if !options.continue_numbering {
+        return 1;
+      }
+      // numbers already used in the clipping group
+      let used = template.number_regex(&base_name).map(|re| {
+        bases
+          .iter()
+          .filter(|(_, b)| **b == base)
+          .filter_map(|(x, _)| re.captures(&v[*x].layer_name))
+          .filter_map(|x| x[1].parse::<u64>().ok())
+          .max()
+          .unwrap_or(0)
+      });
+      return 1 + used.unwrap_or(0);
+    });
+    let name = template.format_layer(&base_name, *n, c.main_id, &c.layer_name);
+    *n += 1;
+    plan_rename(c, &name, report);
+  }
+}
+
+/// Whether the layer keeps its name regardless of the rules (protected or special).
+fn is_kept(layer: &ClipLayer, options: &RenameOptions) -> bool {
+  return options.is_kept(&layer.layer_name, layer.special);
+}
 
+/// Brief
+///
+/// Collect main_id of all layers in the folder recursively.
+///
+/// * `v`: all layer information
+/// * `index`: target folder index of `v`
+/// * `out`: output
+fn collect_descendants(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  out: &mut Vec<u64>,
+) -> Result<(), ClipError> {
+  for ci in get_children(v, index)? {
+    let c = &v[ci];
+    out.push(c.main_id);
+    if c.layer_folder != 0 {
+      collect_descendants(v, ci, out)?;
+    }
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Get children of the folder from the bottom layer.
+///
+/// Return.
+///
+/// indices of `v`
+fn get_children(v: &[Box<ClipLayer>], index: usize) -> Result<Vec<usize>, ClipError> {
+  let mut children: Vec<usize> = Vec::new();
+  let mut next = v[index].layer_first_child_index;
   while next != 0 {
     let ci = match find_layer_index(v, next) {
       Some(x) => x,
       None => return Err(ClipError::UnknownFileStruct),
     };
-    let c = &v[ci];
-    next = c.layer_next_index;
-    if c.layer_folder != 0 {
-      rename_layers_in_folder(conn, v, ci, false, root_layer_base_name, need_rename)?;
-    } else if (!root || root_layer_base_name.len() != 0) && need_rename(&c.layer_name) {
-      let name = if root {
-        format!("{} {}", root_layer_base_name, layer_number)
-      } else {
-        format!("{} {}", f.layer_name, layer_number)
-      };
-      layer_number += 1;
-      rename_layer(conn, c.main_id, &name)?;
+    children.push(ci);
+    next = v[ci].layer_next_index;
+  }
+  return Ok(children);
+}
+
+/// Brief
+///
+/// Sort children given by `get_children` (from the bottom layer) in the numbering order.
+///
+/// * `v`: all layer information
+/// * `children`: indexes of `v`
+/// * `order`: numbering order
+/// * `creation_times`: creation times for `NumberingOrder::Created` (`creation::get_creation_times`)
+fn order_children(
+  v: &[Box<ClipLayer>],
+  children: &mut [usize],
+  order: NumberingOrder,
+  creation_times: &HashMap<u64, i64>,
+) {
+  match order {
+    NumberingOrder::BottomUp => {}
+    NumberingOrder::TopDown => children.reverse(),
+    NumberingOrder::Id => children.sort_by_key(|x| v[*x].main_id),
+    NumberingOrder::Created => {
+      children.sort_by_key(|x| (creation_times.get(&v[*x].main_id).copied(), v[*x].main_id))
     }
   }
+}
 
-  return Ok(());
+/// `{type}` of folders
+const FOLDER_KIND_NAME: &str = "folder";
+
+/// Brief
+///
+/// Take the next `{type_index}` of the layer kind in the folder.
+///
+/// The numbers start from 1, or continue the existing names (`get_max_layer_number`)
+/// with `RenameOptions::continue_numbering`.
+///
+/// * `kind_numbers`: layer kind → next number in the folder
+fn next_kind_number<'a>(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  parent_name: &str,
+  template: &NameTemplate,
+  kind: &'a str,
+  options: &RenameOptions,
+  kind_numbers: &mut HashMap<&'a str, u64>,
+) -> Result<u64, ClipError> {
+  if !template.uses_kind_number() {
+    return Ok(0);
+  }
+  let n = match kind_numbers.get_mut(kind) {
+    Some(x) => x,
+    None => {
+      let start = if options.continue_numbering {
+        let folders = kind == FOLDER_KIND_NAME;
+        let re = template.kind_number_regex(parent_name, kind);
+        1 + get_max_number(v, index, parent_name, template, re, folders, options)?
+      } else {
+        1
+      };
+      kind_numbers.entry(kind).or_insert(start)
+    }
+  };
+  let current = *n;
+  *n += 1;
+  return Ok(current);
 }
 
+/// Brief
+///
 /// Finds the maximum value of the number of layers in the folder.
+///
+/// Layers whose names are in the form of `template` are counted,
+/// so that new numbers continue the existing scheme (e.g. "背景 1".."背景 4" → "背景 5").
+/// Layers which will be renamed (matching the pattern of `options`) are not counted,
+/// so that running again on a renamed file gives the same numbers.
 fn get_max_layer_number(
-  v: &Vec<Box<ClipLayer>>,
+  v: &[Box<ClipLayer>],
   index: usize,
-  root: bool,
-  root_layer_base_name: &str,
+  parent_name: &str,
+  template: &NameTemplate,
+  include_folders: bool,
+  options: &RenameOptions,
+) -> Result<u64, ClipError> {
+  let re = template.number_regex(parent_name);
+  return get_max_number(
+    v,
+    index,
+    parent_name,
+    template,
+    re,
+    include_folders,
+    options,
+  );
+}
+
+/// `get_max_layer_number` with the regex capturing the number (`None`: the template has no number).
+fn get_max_number(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  parent_name: &str,
+  template: &NameTemplate,
+  re: Option<regex::Regex>,
+  include_folders: bool,
+  options: &RenameOptions,
 ) -> Result<u64, ClipError> {
   let f = &v[index];
 
   let mut next = f.layer_first_child_index;
   let mut layer_number: u64 = 0;
 
-  let base_name = if root {
-    root_layer_base_name
-  } else {
-    &f.layer_name
-  };
-  if base_name.len() == 0 {
+  if template.uses_parent() && parent_name.is_empty() {
     return Ok(0);
   }
+  let re = match re {
+    Some(x) => x,
+    None => return Ok(0),
+  };
   while next != 0 {
     let ci = match find_layer_index(v, next) {
       Some(x) => x,
@@ -520,30 +1664,245 @@ fn get_max_layer_number(
     };
     let c = &v[ci];
     next = c.layer_next_index;
-    if c.layer_folder == 0 && c.layer_name.starts_with(base_name) {
-      let substr = c.layer_name[base_name.len()..].trim();
-      layer_number = match substr.parse::<u64>() {
-        Ok(x) => std::cmp::max(x, layer_number),
-        Err(_) => continue,
-      };
+    if c.layer_folder != 0 && !include_folders {
+      continue;
+    }
+    if options.need_rename(&c.layer_name) && !is_kept(c, options) {
+      continue;
+    }
+    if let Some(cap) = re.captures(options.without_old_name(&c.layer_name)) {
+      if let Ok(x) = cap[1].parse::<u64>() {
+        layer_number = std::cmp::max(x, layer_number);
+      }
     }
   }
   return Ok(layer_number);
 }
 
+/// Brief
+///
+/// Record a new layer name in the plan, unless the name is already `rename`.
+/// The data base is not changed (see `plan::apply_plan_in`).
+fn plan_rename(layer: &ClipLayer, rename: &str, plan: &mut RenameReport) {
+  if layer.layer_name == rename {
+    return;
+  }
+  plan.renamed.push(RenamedLayer {
+    main_id: layer.main_id,
+    old_name: layer.layer_name.clone(),
+    new_name: rename.to_string(),
+  });
+}
+
+/// layers updated by one statement by default (`ClipDocument::set_update_batch_size`)
+pub const DEFAULT_UPDATE_BATCH_SIZE: usize = 256;
+/// two parameters for each layer within the parameter limit of the bundled SQLite (32766)
+const MAX_UPDATE_BATCH_SIZE: usize = 16383;
+
+/// Brief
+///
+/// Update the names of many layers with one statement for each batch,
+/// instead of one statement for each layer (`rename_layer`).
+///
+/// * `conn` : sqlite3. The caller should start a transaction.
+/// * `renames` : target layers and new names. Layers whose names are already the new names are skipped.
+/// * `batch_size` : layers updated by one statement. 1 updates layers one by one with a prepared statement.
+/// * `vector_name_columns` : auxiliary name fields of vector layers
+/// * `report` : output. the layers are added in the order of `renames`.
+/// * `cancel` : checked for each batch
+fn rename_layers_batched(
+  conn: &rusqlite::Connection,
+  renames: &[(&ClipLayer, &str)],
+  batch_size: usize,
+  vector_name_columns: &[(String, String)],
+  report: &mut RenameReport,
+  cancel: &CancelToken,
+) -> Result<(), ClipError> {
+  let renames: Vec<&(&ClipLayer, &str)> = renames
+    .iter()
+    .filter(|(layer, rename)| layer.layer_name != *rename)
+    .collect();
+  let batch_size = batch_size.clamp(1, MAX_UPDATE_BATCH_SIZE);
+  let mut start = 0;
+  while start < renames.len() {
+    cancel.check()?;
+    // a layer renamed twice is updated in the next batch, so that the last name wins
+    let mut ids: HashSet<u64> = HashSet::new();
+    let mut end = start;
+    while end < renames.len() && end - start < batch_size && ids.insert(renames[end].0.main_id) {
+      end += 1;
+    }
+    let batch = &renames[start..end];
+    let sql = if batch.len() == 1 {
+      "UPDATE Layer SET LayerName = ?2 WHERE MainId = ?1".to_string()
+    } else {
+      format!(
+        "WITH m(id, name) AS (VALUES {}) \
+         UPDATE Layer SET LayerName = (SELECT name FROM m WHERE m.id = Layer.MainId) \
+         WHERE MainId IN (SELECT id FROM m)",
+        vec!["(?, ?)"; batch.len()].join(", ")
+      )
+    };
+    let mut stmt = match conn.prepare_cached(&sql) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let mut values: Vec<rusqlite::types::Value> = Vec::with_capacity(batch.len() * 2);
+    for (layer, rename) in batch.iter() {
+      values.push(rusqlite::types::Value::Integer(layer.main_id as i64));
+      values.push(rusqlite::types::Value::Text(rename.to_string()));
+    }
+    if let Err(_) = stmt.execute(rusqlite::params_from_iter(values.iter())) {
+      return Err(ClipError::SQLError);
+    }
+    for (layer, rename) in batch.iter() {
+      if layer.kind == LayerKind::Vector {
+        vector::rename_vector_names(
+          conn,
+          vector_name_columns,
+          layer.main_id,
+          &layer.layer_name,
+          rename,
+        )?;
+      }
+      report.renamed.push(RenamedLayer {
+        main_id: layer.main_id,
+        old_name: layer.layer_name.clone(),
+        new_name: rename.to_string(),
+      });
+    }
+    start = end;
+  }
+  return Ok(());
+}
+
 /// Brief
 ///
 /// update layer name
 ///
 /// * `conn` : sqlite3
-/// * `main_id` : layer main_id
+/// * `layer` : target layer
 /// * `rename` : new layer name
-fn rename_layer(conn: &rusqlite::Connection, main_id: u64, rename: &str) -> Result<(), ClipError> {
+/// * `vector_name_columns` : auxiliary name fields of vector layers
+/// * `report` : output. the layer is added unless the name is already `rename`.
+fn rename_layer(
+  conn: &rusqlite::Connection,
+  layer: &ClipLayer,
+  rename: &str,
+  vector_name_columns: &[(String, String)],
+  report: &mut RenameReport,
+) -> Result<(), ClipError> {
+  if layer.layer_name == rename {
+    // already conforming: no write, so that a clean file is saved byte-identical
+    return Ok(());
+  }
   if let Err(_) = conn.execute(
     "UPDATE Layer SET LayerName = $1 WHERE MainId = $2",
-    rusqlite::params![rename, main_id],
+    rusqlite::params![rename, layer.main_id],
   ) {
     return Err(ClipError::SQLError);
   }
+  if layer.kind == LayerKind::Vector {
+    vector::rename_vector_names(
+      conn,
+      vector_name_columns,
+      layer.main_id,
+      &layer.layer_name,
+      rename,
+    )?;
+  }
+  report.renamed.push(RenamedLayer {
+    main_id: layer.main_id,
+    old_name: layer.layer_name.clone(),
+    new_name: rename.to_string(),
+  });
   return Ok(());
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  /// `CHNKSQLi`, the chunk length, `extra` zero bytes and the sqlite3 header
+  fn sql_chunk(length: u64, extra: usize) -> Vec<u8> {
+    let mut data = SQL_CHANK.to_vec();
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend(std::iter::repeat(0u8).take(extra));
+    data.extend_from_slice(SQL_HEADER);
+    return data;
+  }
+
+  #[test]
+  fn sql_chunk_header() {
+    assert_eq!(parse_sql_chunk_header(&sql_chunk(100, 0)), Some((100, 0)));
+    assert_eq!(parse_sql_chunk_header(&sql_chunk(100, 8)), Some((100, 8)));
+    assert_eq!(
+      parse_sql_chunk_header(&sql_chunk(100, SQL_CHUNK_MAX_EXTRA)),
+      Some((100, SQL_CHUNK_MAX_EXTRA))
+    );
+  }
+
+  #[test]
+  fn sql_chunk_header_rejected() {
+    // too many extra bytes
+    assert_eq!(
+      parse_sql_chunk_header(&sql_chunk(100, SQL_CHUNK_MAX_EXTRA + 1)),
+      None
+    );
+    // the length is shorter than the extra fields
+    assert_eq!(parse_sql_chunk_header(&sql_chunk(4, 8)), None);
+    // not a sqlite3 chunk
+    let mut data = sql_chunk(100, 0);
+    data[4..8].copy_from_slice(b"Exta");
+    assert_eq!(parse_sql_chunk_header(&data), None);
+    // cut in the length field
+    assert_eq!(parse_sql_chunk_header(&sql_chunk(100, 0)[..12]), None);
+  }
+
+  #[test]
+  fn sql_chunk_length_position() {
+    let mut data = vec![0xffu8; 40];
+    data.extend(sql_chunk(1000, 4));
+    let index = data.len() - SQL_HEADER_LEN;
+    let mut cursor = Cursor::new(data);
+    assert_eq!(
+      find_sql_chunk_length(&mut cursor, index as u64).unwrap(),
+      (40 + SQL_CHANK_LEN as u64, 4)
+    );
+    // the sqlite3 data is not right after the header
+    assert!(matches!(
+      find_sql_chunk_length(&mut cursor, index as u64 - 1),
+      Err(ClipError::UnknownFileStruct)
+    ));
+  }
+
+  #[test]
+  fn sql_chunk_length_near_start() {
+    let data = sql_chunk(1000, 0);
+    let index = (SQL_CHANK_LEN + CHUNK_LENGTH_SIZE) as u64;
+    assert_eq!(
+      find_sql_chunk_length(&mut Cursor::new(data), index).unwrap(),
+      (SQL_CHANK_LEN as u64, 0)
+    );
+  }
+
+  #[test]
+  fn save_sql_only_short_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let clip = dir.path().join("short.clip");
+    std::fs::write(&clip, [1u8; 10]).unwrap();
+    let result = save_sql_only(
+      &clip,
+      dir.path().join("out.sqlite"),
+      100,
+      4,
+      &CancelToken::new(),
+    );
+    assert!(
+      matches!(result, Err(ClipError::FileReadError)),
+      "{:?}",
+      result
+    );
+  }
+}
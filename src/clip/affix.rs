@@ -0,0 +1,65 @@
+use super::{
+  get_layer_roots, rename_layer, vector, ClipError, ClipLayer, RenameOptions, RenameReport,
+};
+use std::path::Path;
+
+/// Brief
+///
+/// Add a prefix and/or a suffix to the names of matching layers.
+///
+/// Unlike `rename_layers_in_sqlite`, layers are not numbered and the folder structure is not used.
+/// Only the pattern, the protect prefix, `include_folders` and `allow_special` of `options` are used.
+/// Names which already start with `prefix` (end with `suffix`) do not get it again,
+/// so that running the same command twice does not make "WIP_WIP_レイヤー 1".
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `options`: options of renaming
+/// * `prefix`: text prepended to the names
+/// * `suffix`: text appended to the names
+pub fn add_affix_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  options: &RenameOptions,
+  prefix: &str,
+  suffix: &str,
+) -> Result<RenameReport, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let roots = get_layer_roots(&conn, &mut v)?;
+  let vector_name_columns = if options.vector_names {
+    vector::get_vector_name_columns(&conn)?
+  } else {
+    Vec::new()
+  };
+  let options = options.for_layer_names(v.iter().map(|x| x.layer_name.as_str()));
+
+  let mut report = RenameReport::default();
+  for l in v.iter() {
+    let folder = l.layer_folder != 0;
+    if roots.contains(&l.main_id) || (folder && !options.include_folders) {
+      continue;
+    }
+    report.total_layers += 1;
+    if l
+      .special
+      .is_some_and(|x| !options.allow_special.contains(&x))
+      || !options.need_rename(&l.layer_name)
+    {
+      continue;
+    }
+    let mut name = l.layer_name.clone();
+    if !name.starts_with(prefix) {
+      name.insert_str(0, prefix);
+    }
+    if !name.ends_with(suffix) {
+      name.push_str(suffix);
+    }
+    if name == l.layer_name {
+      continue;
+    }
+    rename_layer(&conn, l, &name, &vector_name_columns, &mut report)?;
+  }
+  return Ok(report);
+}
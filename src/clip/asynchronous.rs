@@ -0,0 +1,96 @@
+use super::{ClipDocument, ClipError, RenameOptions, RenameReport};
+use std::path::PathBuf;
+
+/// Brief.
+///
+/// Run `f` on the blocking thread pool.
+///
+/// A panic in `f` is resumed. `ClipError::Cancelled` if the runtime is shutting down.
+async fn run_blocking<T, F>(f: F) -> Result<T, ClipError>
+where
+  T: Send + 'static,
+  F: FnOnce() -> Result<T, ClipError> + Send + 'static,
+{
+  return match tokio::task::spawn_blocking(f).await {
+    Ok(x) => x,
+    Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+    Err(_) => Err(ClipError::Cancelled),
+  };
+}
+
+/// Open the clip file (`ClipDocument::open`).
+pub async fn open(src: PathBuf) -> Result<ClipDocument, ClipError> {
+  return run_blocking(move || ClipDocument::open(src)).await;
+}
+
+/// Write the clip file (`ClipDocument::save`).
+pub async fn save(doc: ClipDocument, dst: PathBuf) -> Result<(), ClipError> {
+  return run_blocking(move || doc.save(dst)).await;
+}
+
+/// Rename layers of the document (`ClipDocument::rename_layers`).
+///
+/// Return.
+///
+/// the document and the result
+pub async fn rename_layers(
+  mut doc: ClipDocument,
+  options: RenameOptions,
+) -> Result<(ClipDocument, RenameReport), ClipError> {
+  return run_blocking(move || {
+    let report = doc.rename_layers(&options)?;
+    Ok((doc, report))
+  })
+  .await;
+}
+
+/// Read the clip file, rename layers and write it (`create_layer_renamed_clip_file`).
+pub async fn create_layer_renamed_clip_file(
+  src: PathBuf,
+  dst: PathBuf,
+  options: RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  return run_blocking(move || super::create_layer_renamed_clip_file(src, dst, &options)).await;
+}
+
+/// Brief.
+///
+/// Rename layers of many files concurrently with the same options.
+///
+/// ```no_run
+/// # async fn f() {
+/// use renamelayer::clip::{asynchronous, RenameOptions};
+/// let files = vec![("a.clip".into(), "out/a.clip".into()), ("b.clip".into(), "out/b.clip".into())];
+/// for result in asynchronous::rename_files(files, &RenameOptions::new()).await {
+///   println!("{:?}", result.map(|x| x.summary()));
+/// }
+/// # }
+/// ```
+///
+/// * `files`: `(input, output)`
+/// * `options`: options of renaming
+///
+/// Return.
+///
+/// the result of each file in the order of `files`
+pub async fn rename_files(
+  files: Vec<(PathBuf, PathBuf)>,
+  options: &RenameOptions,
+) -> Vec<Result<RenameReport, ClipError>> {
+  let jobs: Vec<_> = files
+    .into_iter()
+    .map(|(src, dst)| {
+      let options = options.clone();
+      tokio::spawn(create_layer_renamed_clip_file(src, dst, options))
+    })
+    .collect();
+  let mut results = Vec::with_capacity(jobs.len());
+  for job in jobs {
+    results.push(match job.await {
+      Ok(x) => x,
+      Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+      Err(_) => Err(ClipError::Cancelled),
+    });
+  }
+  return results;
+}
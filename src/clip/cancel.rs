@@ -0,0 +1,49 @@
+use super::ClipError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Brief.
+///
+/// A flag to abort a long operation of `ClipDocument` from another thread (e.g. the cancel button of a GUI).
+///
+/// Clones share the flag. The operation fails with `ClipError::Cancelled` soon after `cancel` is called,
+/// and its temporary files are removed. A file being saved is not written.
+///
+/// ```no_run
+/// use renamelayer::clip::{CancelToken, ClipDocument, RenameOptions};
+/// let token = CancelToken::new();
+/// let cancel = token.clone(); // give it to the UI thread
+/// let mut doc = ClipDocument::open_with_cancel("in.clip", token)?;
+/// doc.rename_layers(&RenameOptions::new())?;
+/// doc.save("out.clip")?;
+/// # Ok::<(), renamelayer::clip::ClipError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+  /// Not cancelled token.
+  pub fn new() -> CancelToken {
+    return CancelToken::default();
+  }
+
+  /// Request cancellation of the operations using this token.
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  /// Whether `cancel` was called.
+  pub fn is_cancelled(&self) -> bool {
+    return self.cancelled.load(Ordering::Relaxed);
+  }
+
+  /// `ClipError::Cancelled` if cancelled.
+  pub(super) fn check(&self) -> Result<(), ClipError> {
+    if self.is_cancelled() {
+      return Err(ClipError::Cancelled);
+    }
+    return Ok(());
+  }
+}
@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// temporary files and directories which are in use
+static TEMPORARY_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Brief.
+///
+/// A registered temporary path. The path is unregistered when dropped.
+///
+/// The owner is still responsible for removing the path in normal cases;
+/// the registration is only used by `remove_temporary_files`.
+pub(super) struct Registered {
+  path: PathBuf,
+}
+
+impl Registered {
+  pub(super) fn new(path: &Path) -> Registered {
+    if let Ok(mut paths) = TEMPORARY_PATHS.lock() {
+      paths.push(path.to_path_buf());
+    }
+    return Registered {
+      path: path.to_path_buf(),
+    };
+  }
+}
+
+impl Drop for Registered {
+  fn drop(&mut self) {
+    if let Ok(mut paths) = TEMPORARY_PATHS.lock() {
+      if let Some(i) = paths.iter().rposition(|x| *x == self.path) {
+        paths.remove(i);
+      }
+    }
+  }
+}
+
+/// Brief.
+///
+/// Remove the temporary directories and partially written files in use by this library.
+///
+/// Destructors are not run when the process is terminated (e.g. by Ctrl-C),
+/// so call this from the signal handler before exiting.
+/// The documents and files in use must not be used after this.
+pub fn remove_temporary_files() {
+  let paths = match TEMPORARY_PATHS.lock() {
+    Ok(mut x) => std::mem::take(&mut *x),
+    Err(_) => return,
+  };
+  for p in paths.iter() {
+    if p.is_dir() {
+      let _ = std::fs::remove_dir_all(p);
+    } else {
+      let _ = std::fs::remove_file(p);
+    }
+  }
+}
@@ -0,0 +1,38 @@
+use super::material::get_columns;
+use super::ClipError;
+use std::collections::HashSet;
+
+/// column of the Layer table: non-zero if the layer is clipped to the layer below
+const CLIP_COLUMN: &str = "LayerClip";
+
+/// Brief
+///
+/// Collect layers clipped to the layer below (clipping groups).
+///
+/// Return.
+///
+/// main_id of clipped layers. Empty if the file has no clipping column.
+pub(super) fn get_clipped_layers(conn: &rusqlite::Connection) -> Result<HashSet<u64>, ClipError> {
+  let mut clipped: HashSet<u64> = HashSet::new();
+  if !get_columns(conn, "Layer")?.iter().any(|x| x == CLIP_COLUMN) {
+    return Ok(clipped);
+  }
+  let mut stmt = match conn.prepare(&format!(
+    "SELECT MainId FROM Layer WHERE {} != 0",
+    CLIP_COLUMN
+  )) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| row.get::<_, u64>(0)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  for row in rows {
+    match row {
+      Ok(x) => clipped.insert(x),
+      Err(_) => return Err(ClipError::SQLError),
+    };
+  }
+  return Ok(clipped);
+}
@@ -0,0 +1,161 @@
+use super::normalize;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// How layer names are compared when sorting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+  /// byte order of UTF-8 ("10" before "2", katakana after hiragana)
+  Byte,
+  /// numbers in names are compared by value ("2" before "10")
+  Natural,
+  /// `Natural` and Japanese: hiragana and katakana are the same kana ("あ" = "ア"),
+  /// full width and half width characters are the same ("Ａ" = "A", "ｱ" = "ア")
+  Japanese,
+}
+
+impl Collation {
+  /// all collations
+  pub const ALL: [Collation; 3] = [Collation::Byte, Collation::Natural, Collation::Japanese];
+
+  /// name used on the command line
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Collation::Byte => "byte",
+      Collation::Natural => "natural",
+      Collation::Japanese => "japanese",
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Compare two names. Names equal in this collation are compared in byte order.
+  pub fn compare(&self, a: &str, b: &str) -> Ordering {
+    let order = match self {
+      Collation::Byte => Ordering::Equal,
+      Collation::Natural => compare_natural(a, b),
+      Collation::Japanese => compare_natural(&japanese_key(a), &japanese_key(b)),
+    };
+    return order.then_with(|| a.cmp(b));
+  }
+}
+
+impl FromStr for Collation {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    return match Collation::ALL.iter().find(|x| x.name() == s) {
+      Some(x) => Ok(*x),
+      None => Err(format!("unknown collation: {}", s)),
+    };
+  }
+}
+
+/// Brief
+///
+/// Compare names splitting them into numbers and the others.
+/// Numbers are compared by value, and by the number of digits if the values are the same ("01" after "1").
+fn compare_natural(a: &str, b: &str) -> Ordering {
+  let mut a = a.chars().peekable();
+  let mut b = b.chars().peekable();
+  loop {
+    let (x, y) = match (a.peek(), b.peek()) {
+      (None, None) => return Ordering::Equal,
+      (None, Some(_)) => return Ordering::Less,
+      (Some(_), None) => return Ordering::Greater,
+      (Some(x), Some(y)) => (*x, *y),
+    };
+    if x.is_ascii_digit() && y.is_ascii_digit() {
+      let x = take_digits(&mut a);
+      let y = take_digits(&mut b);
+      let order = x
+        .trim_start_matches('0')
+        .len()
+        .cmp(&y.trim_start_matches('0').len())
+        .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+        .then_with(|| x.len().cmp(&y.len()));
+      if order != Ordering::Equal {
+        return order;
+      }
+      continue;
+    }
+    if x != y {
+      return x.cmp(&y);
+    }
+    a.next();
+    b.next();
+  }
+}
+
+fn take_digits<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> String {
+  let mut digits = String::new();
+  while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+    digits.push(c);
+  }
+  return digits;
+}
+
+/// Brief
+///
+/// Sort key of a Japanese name: normalized width, katakana as hiragana, ASCII letters in lower case.
+fn japanese_key(name: &str) -> String {
+  return normalize::normalize_width(name)
+    .chars()
+    .map(|c| match c {
+      // ァ..ヶ → ぁ..ゖ
+      '\u{30a1}'..='\u{30f6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+      _ => c.to_ascii_lowercase(),
+    })
+    .collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sorted(collation: Collation, names: &[&str]) -> Vec<String> {
+    let mut v: Vec<String> = names.iter().map(|x| x.to_string()).collect();
+    v.sort_by(|a, b| collation.compare(a, b));
+    return v;
+  }
+
+  #[test]
+  fn byte() {
+    assert_eq!(
+      sorted(Collation::Byte, &["ア 2", "あ 10", "あ 2"]),
+      vec!["あ 10", "あ 2", "ア 2"]
+    );
+  }
+
+  #[test]
+  fn natural() {
+    assert_eq!(
+      sorted(Collation::Natural, &["a10", "a2", "a02", "a", "b1"]),
+      vec!["a", "a2", "a02", "a10", "b1"]
+    );
+  }
+
+  #[test]
+  fn japanese() {
+    assert_eq!(
+      sorted(
+        Collation::Japanese,
+        &["い", "ア 10", "ｱ 2", "あ 3", "B", "ａ"]
+      ),
+      vec!["ａ", "B", "ｱ 2", "あ 3", "ア 10", "い"]
+    );
+  }
+
+  #[test]
+  fn equal_names_in_byte_order() {
+    assert_eq!(Collation::Japanese.compare("ア", "あ"), "ア".cmp("あ"));
+    assert_eq!(Collation::Natural.compare("a1", "a1"), Ordering::Equal);
+  }
+
+  #[test]
+  fn names() {
+    for c in Collation::ALL.iter() {
+      assert_eq!(c.name().parse::<Collation>(), Ok(*c));
+    }
+    assert!("unicode".parse::<Collation>().is_err());
+  }
+}
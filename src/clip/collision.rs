@@ -0,0 +1,86 @@
+use super::{get_children, ClipError, ClipLayer, RenameReport};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// What to do when a new layer name equals the name of a sibling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+  /// rename anyway, leaving layers of the same name in the folder
+  Overwrite,
+  /// keep the old name
+  Skip,
+  /// add a number to the new name, e.g. "線画 (2)"
+  Suffix,
+  /// fail with `ClipError::NameCollision`
+  Error,
+}
+
+/// Brief
+///
+/// Apply the collision policy to planned renames.
+/// A new name collides if it equals the name of a sibling after renaming.
+///
+/// * `v`: all layer information
+/// * `plan`: planned renames. Renames are changed or removed.
+/// * `policy`: collision policy
+pub(super) fn resolve_collisions(
+  v: &[Box<ClipLayer>],
+  plan: &mut RenameReport,
+  policy: CollisionPolicy,
+) -> Result<(), ClipError> {
+  if policy == CollisionPolicy::Overwrite || plan.renamed.is_empty() {
+    return Ok(());
+  }
+  // siblings of each layer, including itself
+  let mut siblings: HashMap<u64, Rc<Vec<u64>>> = HashMap::new();
+  for i in 0..v.len() {
+    if v[i].layer_folder == 0 {
+      continue;
+    }
+    let children: Vec<u64> = get_children(v, i)?.iter().map(|x| v[*x].main_id).collect();
+    let children = Rc::new(children);
+    for id in children.iter() {
+      siblings.insert(*id, children.clone());
+    }
+  }
+  let mut names: HashMap<u64, String> = v
+    .iter()
+    .map(|x| (x.main_id, x.layer_name.clone()))
+    .collect();
+  for r in plan.renamed.iter() {
+    names.insert(r.main_id, r.new_name.clone());
+  }
+
+  for r in plan.renamed.iter_mut() {
+    let taken: HashSet<&str> = match siblings.get(&r.main_id) {
+      Some(x) => x
+        .iter()
+        .filter(|id| **id != r.main_id)
+        .filter_map(|id| names.get(id))
+        .map(|x| x.as_str())
+        .collect(),
+      None => continue,
+    };
+    if !taken.contains(r.new_name.as_str()) {
+      continue;
+    }
+    let new_name = match policy {
+      CollisionPolicy::Overwrite => continue,
+      CollisionPolicy::Error => {
+        return Err(ClipError::NameCollision(r.main_id, r.new_name.clone()))
+      }
+      CollisionPolicy::Skip => r.old_name.clone(),
+      CollisionPolicy::Suffix => {
+        let mut n = 2;
+        while taken.contains(format!("{} ({})", r.new_name, n).as_str()) {
+          n += 1;
+        }
+        format!("{} ({})", r.new_name, n)
+      }
+    };
+    r.new_name = new_name.clone();
+    names.insert(r.main_id, new_name);
+  }
+  plan.renamed.retain(|r| r.old_name != r.new_name);
+  return Ok(());
+}
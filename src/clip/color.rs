@@ -0,0 +1,85 @@
+use super::{get_layer_columns, ClipError};
+use std::collections::HashMap;
+
+/// names of the color labels and their colors
+const COLOR_NAMES: &[(&str, (i32, i32, i32))] = &[
+  ("red", (255, 0, 0)),
+  ("orange", (255, 128, 0)),
+  ("yellow", (255, 255, 0)),
+  ("green", (0, 255, 0)),
+  ("cyan", (0, 255, 255)),
+  ("blue", (0, 0, 255)),
+  ("purple", (128, 0, 255)),
+  ("pink", (255, 0, 255)),
+  ("white", (255, 255, 255)),
+  ("gray", (128, 128, 128)),
+  ("black", (0, 0, 0)),
+];
+
+/// Brief
+///
+/// Get the color label names of layers.
+///
+/// The color label is `LayerPaletteRed/Green/Blue` when `LayerUsePaletteColor` is set.
+/// The name is the nearest color of `COLOR_NAMES`.
+///
+/// Return.
+///
+/// main_id → color name. Layers without a color label are not included.
+pub(super) fn get_color_labels(
+  conn: &rusqlite::Connection,
+) -> Result<HashMap<u64, &'static str>, ClipError> {
+  let mut labels: HashMap<u64, &'static str> = HashMap::new();
+  let columns = get_layer_columns(conn)?;
+  if !columns.iter().any(|x| x == "LayerUsePaletteColor") {
+    return Ok(labels);
+  }
+  let mut stmt = match conn.prepare(
+    "SELECT MainId, LayerPaletteRed, LayerPaletteGreen, LayerPaletteBlue FROM Layer WHERE LayerUsePaletteColor != 0",
+  ) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((
+      row.get::<_, u64>(0)?,
+      row.get::<_, Option<u64>>(1)?.unwrap_or(0),
+      row.get::<_, Option<u64>>(2)?.unwrap_or(0),
+      row.get::<_, Option<u64>>(3)?.unwrap_or(0),
+    ))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  for r in rows {
+    let (main_id, red, green, blue) = match r {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    labels.insert(main_id, color_name(to_u8(red), to_u8(green), to_u8(blue)));
+  }
+  return Ok(labels);
+}
+
+/// Scale a color component to 0-255. Components may be stored in 8, 16 or 32 bits.
+fn to_u8(x: u64) -> i32 {
+  return if x > 0xffff {
+    (x >> 24) as i32 & 0xff
+  } else if x > 0xff {
+    (x >> 8) as i32
+  } else {
+    x as i32
+  };
+}
+
+/// Name of the nearest color.
+fn color_name(red: i32, green: i32, blue: i32) -> &'static str {
+  let distance = |c: &(i32, i32, i32)| {
+    return (c.0 - red).pow(2) + (c.1 - green).pow(2) + (c.2 - blue).pow(2);
+  };
+  return COLOR_NAMES
+    .iter()
+    .min_by_key(|x| distance(&x.1))
+    .map(|x| x.0)
+    .unwrap();
+}
@@ -0,0 +1,158 @@
+use super::material::get_columns;
+use super::{ClipError, RenameReport};
+
+/// Brief
+///
+/// Find the text column of the Layer table for comments or search keywords.
+///
+/// The first column whose name contains `Comment`, `Keyword` or `Memo` is used.
+/// Older files have no such column.
+pub(super) fn get_comment_column(conn: &rusqlite::Connection) -> Result<Option<String>, ClipError> {
+  let columns = get_columns(conn, "Layer")?;
+  return Ok(
+    columns
+      .into_iter()
+      .find(|x| x.contains("Comment") || x.contains("Keyword") || x.contains("Memo")),
+  );
+}
+
+/// Brief
+///
+/// Add the old names of renamed layers to the comment column (`get_comment_column`),
+/// so that the layers can still be found by the old names in Clip Studio Paint.
+///
+/// An old name already in the comment is not added again. Nothing is done if the file has no comment column.
+///
+/// * `conn`: sqlite3
+/// * `report`: renamed layers
+pub(super) fn record_old_names(
+  conn: &rusqlite::Connection,
+  report: &RenameReport,
+) -> Result<(), ClipError> {
+  let column = match get_comment_column(conn)? {
+    Some(x) => x,
+    None => return Ok(()),
+  };
+  for r in report.renamed.iter() {
+    let comment: Option<String> = match conn.query_row(
+      &format!("SELECT \"{}\" FROM Layer WHERE MainId = $1", column),
+      [r.main_id],
+      |row| row.get(0),
+    ) {
+      Ok(x) => x,
+      // not a text column
+      Err(_) => return Ok(()),
+    };
+    let comment = comment.unwrap_or_default();
+    if comment.lines().any(|x| x == r.old_name) {
+      continue;
+    }
+    let comment = if comment.is_empty() {
+      r.old_name.clone()
+    } else {
+      format!("{}\n{}", comment, r.old_name)
+    };
+    if let Err(_) = conn.execute(
+      &format!("UPDATE Layer SET \"{}\" = $1 WHERE MainId = $2", column),
+      rusqlite::params![comment, r.main_id],
+    ) {
+      return Err(ClipError::SQLError);
+    }
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Read the comment of a layer.
+///
+/// * `conn`: sqlite3
+/// * `main_id`: main_id of the layer
+///
+/// Return.
+///
+/// `None` if the comment is not set. `ClipError::NoCommentField` if the file has no comment column.
+pub(super) fn get_comment(
+  conn: &rusqlite::Connection,
+  main_id: u64,
+) -> Result<Option<String>, ClipError> {
+  let column = match get_comment_column(conn)? {
+    Some(x) => x,
+    None => return Err(ClipError::NoCommentField),
+  };
+  return match conn.query_row(
+    &format!("SELECT \"{}\" FROM Layer WHERE MainId = $1", column),
+    [main_id],
+    |row| row.get::<_, Option<String>>(0),
+  ) {
+    Ok(x) => Ok(x.filter(|x| !x.is_empty())),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Err(ClipError::LayerNotFound(main_id)),
+    Err(_) => Err(ClipError::SQLError),
+  };
+}
+
+/// Brief
+///
+/// Read the comments of all layers which have one.
+///
+/// Return.
+///
+/// `(main_id, comment)` in the order of main_id
+pub(super) fn get_comments(conn: &rusqlite::Connection) -> Result<Vec<(u64, String)>, ClipError> {
+  let column = match get_comment_column(conn)? {
+    Some(x) => x,
+    None => return Err(ClipError::NoCommentField),
+  };
+  let mut stmt = match conn.prepare(&format!(
+    "SELECT MainId, \"{0}\" FROM Layer WHERE \"{0}\" IS NOT NULL AND \"{0}\" != '' ORDER BY MainId",
+    column
+  )) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut comments: Vec<(u64, String)> = Vec::new();
+  for r in rows {
+    match r {
+      Ok(x) => comments.push(x),
+      Err(_) => return Err(ClipError::SQLError),
+    }
+  }
+  return Ok(comments);
+}
+
+/// Brief
+///
+/// Set the comment of a layer. An empty comment clears it.
+///
+/// * `conn`: sqlite3
+/// * `main_id`: main_id of the layer
+/// * `comment`: new comment
+pub(super) fn set_comment(
+  conn: &rusqlite::Connection,
+  main_id: u64,
+  comment: &str,
+) -> Result<(), ClipError> {
+  let column = match get_comment_column(conn)? {
+    Some(x) => x,
+    None => return Err(ClipError::NoCommentField),
+  };
+  let value = if comment.is_empty() {
+    None
+  } else {
+    Some(comment)
+  };
+  return match conn.execute(
+    &format!("UPDATE Layer SET \"{}\" = $1 WHERE MainId = $2", column),
+    rusqlite::params![value, main_id],
+  ) {
+    Ok(0) => Err(ClipError::LayerNotFound(main_id)),
+    Ok(_) => Ok(()),
+    Err(_) => Err(ClipError::SQLError),
+  };
+}
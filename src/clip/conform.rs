@@ -0,0 +1,106 @@
+use super::{
+  find_layer_index, get_children, get_layers, ClipError, ClipLayer, RenamePlan, RenamedLayer,
+  Warning,
+};
+use std::path::Path;
+
+/// Brief
+///
+/// Decide new names of the target layers from a reference file whose layers are named correctly,
+/// e.g. a template page.
+///
+/// Layers are matched by their positions from the top in the folders of the same positions.
+/// A target layer takes the name of the reference layer at the same position if both are folders,
+/// or both are layers of the same kind. Otherwise the layer (and the contents of a folder) is left
+/// as it is with `Warning::NoReferenceLayer`.
+///
+/// * `reference`: sqlite3 of the reference file
+/// * `target`: sqlite3 of the file to rename. It is only read.
+pub(super) fn plan_conform_in(
+  reference: &rusqlite::Connection,
+  target: &rusqlite::Connection,
+) -> Result<RenamePlan, ClipError> {
+  let mut rv: Vec<Box<ClipLayer>> = Vec::new();
+  let reference_root = get_layers(reference, &mut rv)?;
+  let mut tv: Vec<Box<ClipLayer>> = Vec::new();
+  let target_root = get_layers(target, &mut tv)?;
+  let (ri, ti) = match (
+    find_layer_index(&rv, reference_root),
+    find_layer_index(&tv, target_root),
+  ) {
+    (Some(x), Some(y)) => (x, y),
+    _ => return Err(ClipError::UnknownFileStruct),
+  };
+  let mut plan = RenamePlan {
+    total_layers: tv.iter().filter(|l| l.layer_folder == 0).count(),
+    ..RenamePlan::default()
+  };
+  conform_folder(&rv, ri, &tv, ti, &mut plan)?;
+  return Ok(plan);
+}
+
+/// Brief
+///
+/// `plan_conform_in` with sqlite3 files.
+///
+/// * `reference`: sqlite3 file path of the reference file
+/// * `target`: sqlite3 file path of the file to rename. It is only read.
+pub fn plan_conform_in_sqlite<P1: AsRef<Path>, P2: AsRef<Path>>(
+  reference: P1,
+  target: P2,
+) -> Result<RenamePlan, ClipError> {
+  let (reference, target) = match (
+    rusqlite::Connection::open(reference),
+    rusqlite::Connection::open(target),
+  ) {
+    (Ok(x), Ok(y)) => (x, y),
+    _ => return Err(ClipError::SQLError),
+  };
+  return plan_conform_in(&reference, &target);
+}
+
+fn conform_folder(
+  rv: &[Box<ClipLayer>],
+  ri: usize,
+  tv: &[Box<ClipLayer>],
+  ti: usize,
+  plan: &mut RenamePlan,
+) -> Result<(), ClipError> {
+  // children from the top, as shown in the layer palette
+  let reference_children: Vec<usize> = get_children(rv, ri)?.into_iter().rev().collect();
+  let target_children: Vec<usize> = get_children(tv, ti)?.into_iter().rev().collect();
+  for (position, tci) in target_children.into_iter().enumerate() {
+    let t = &tv[tci];
+    let rci = match reference_children.get(position) {
+      Some(x) if same_structure(&rv[*x], t) => *x,
+      _ => {
+        plan.warnings.push(Warning::NoReferenceLayer {
+          main_id: t.main_id,
+          name: t.layer_name.clone(),
+        });
+        continue;
+      }
+    };
+    let r = &rv[rci];
+    if r.layer_name != t.layer_name {
+      plan.renames.push(RenamedLayer {
+        main_id: t.main_id,
+        old_name: t.layer_name.clone(),
+        new_name: r.layer_name.clone(),
+      });
+    }
+    if t.layer_folder != 0 {
+      conform_folder(rv, rci, tv, tci, plan)?;
+    }
+  }
+  return Ok(());
+}
+
+/// Both are folders, or both are layers of the same kind.
+fn same_structure(reference: &ClipLayer, target: &ClipLayer) -> bool {
+  return match (reference.layer_folder != 0, target.layer_folder != 0) {
+    (true, true) => true,
+    (false, false) => reference.kind == target.kind,
+    _ => false,
+  };
+}
@@ -0,0 +1,66 @@
+use super::material::get_columns;
+use super::ClipError;
+use std::collections::HashMap;
+
+/// Brief
+///
+/// Find the column of the Layer table recording when layers were made.
+///
+/// A column whose name contains `Create` is used, otherwise one containing `Modif` or `Update`.
+/// Files saved by the current Clip Studio Paint have no such column.
+fn get_time_column(conn: &rusqlite::Connection) -> Result<Option<String>, ClipError> {
+  let columns = get_columns(conn, "Layer")?;
+  if let Some(x) = columns.iter().find(|x| x.contains("Create")) {
+    return Ok(Some(x.clone()));
+  }
+  return Ok(
+    columns
+      .into_iter()
+      .find(|x| x.contains("Modif") || x.contains("Update")),
+  );
+}
+
+/// Brief
+///
+/// Collect the creation (or modification) times of layers for `NumberingOrder::Created`.
+///
+/// Only numeric values are used. The table is empty if the file records no times.
+///
+/// Return.
+///
+/// time for each layer main_id
+pub(super) fn get_creation_times(
+  conn: &rusqlite::Connection,
+) -> Result<HashMap<u64, i64>, ClipError> {
+  let mut times: HashMap<u64, i64> = HashMap::new();
+  let column = match get_time_column(conn)? {
+    Some(x) => x,
+    None => return Ok(times),
+  };
+  let mut stmt = match conn.prepare(&format!("SELECT MainId, \"{}\" FROM Layer", column)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((
+      row.get::<_, u64>(0)?,
+      row.get::<_, rusqlite::types::Value>(1)?,
+    ))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  for row in rows {
+    let (main_id, value) = match row {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let time = match value {
+      rusqlite::types::Value::Integer(x) => x,
+      rusqlite::types::Value::Real(x) => x as i64,
+      _ => continue,
+    };
+    times.insert(main_id, time);
+  }
+  return Ok(times);
+}
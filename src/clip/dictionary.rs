@@ -0,0 +1,125 @@
+use super::ClipError;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Brief.
+///
+/// Read a translation dictionary from a CSV file (`parse_dictionary`).
+///
+/// * `path`: CSV file path
+pub fn load_dictionary<P: AsRef<Path>>(path: P) -> Result<HashMap<String, String>, ClipError> {
+  let text = match std::fs::read_to_string(path) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileReadError),
+  };
+  return parse_dictionary(&text);
+}
+
+/// Brief.
+///
+/// Parse a translation dictionary: one `name,replacement` pair per line (e.g. `線画,Lineart`).
+///
+/// Fields may be quoted with `"` (`""` in a quoted field is `"`).
+/// Empty lines and lines starting with `#` are ignored. A later line of the same name replaces the former.
+///
+/// * `text`: CSV text
+///
+/// Return.
+///
+/// replacement for each layer name
+pub fn parse_dictionary(text: &str) -> Result<HashMap<String, String>, ClipError> {
+  let mut dictionary: HashMap<String, String> = HashMap::new();
+  let text = text.trim_start_matches('\u{feff}');
+  for (i, line) in text.lines().enumerate() {
+    if line.trim().is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let invalid = || ClipError::InvalidDictionary(format!("line {}: {}", i + 1, line));
+    let fields = match split_csv_line(line) {
+      Some(x) => x,
+      None => return Err(invalid()),
+    };
+    if fields.len() != 2 || fields[0].is_empty() || fields[1].is_empty() {
+      return Err(invalid());
+    }
+    let mut fields = fields.into_iter();
+    let name = fields.next().unwrap();
+    let replacement = fields.next().unwrap();
+    dictionary.insert(name, replacement);
+  }
+  return Ok(dictionary);
+}
+
+/// Split a CSV line into fields. `None` if a quote is not closed.
+pub(super) fn split_csv_line(line: &str) -> Option<Vec<String>> {
+  let mut fields: Vec<String> = Vec::new();
+  let mut field = String::new();
+  let mut chars = line.chars().peekable();
+  let mut quoted = false;
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if quoted => {
+        if chars.peek() == Some(&'"') {
+          chars.next();
+          field.push('"');
+        } else {
+          quoted = false;
+        }
+      }
+      '"' if field.trim().is_empty() => {
+        field.clear();
+        quoted = true;
+      }
+      ',' if !quoted => fields.push(std::mem::take(&mut field)),
+      _ => field.push(c),
+    }
+  }
+  if quoted {
+    return None;
+  }
+  fields.push(field);
+  return Some(fields.into_iter().map(|x| x.trim().to_string()).collect());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn csv_fields() {
+    assert_eq!(split_csv_line("a, b ,c").unwrap(), vec!["a", "b", "c"]);
+    assert_eq!(
+      split_csv_line(r#""a,b", "say ""hi""""#).unwrap(),
+      vec!["a,b", r#"say "hi""#]
+    );
+    assert_eq!(split_csv_line("a,").unwrap(), vec!["a", ""]);
+    assert_eq!(split_csv_line(r#""a,b"#), None);
+  }
+
+  #[test]
+  fn dictionary() {
+    let d = parse_dictionary("\u{feff}# comment\n線画,Lineart\n\n\"塗り, 影\",Shade\n線画,Line\n")
+      .unwrap();
+    assert_eq!(d.len(), 2);
+    assert_eq!(d["線画"], "Line");
+    assert_eq!(d["塗り, 影"], "Shade");
+  }
+
+  #[test]
+  fn invalid_dictionary() {
+    for text in [
+      "線画",
+      "線画,Lineart,x",
+      ",Lineart",
+      "線画,",
+      "\"線画,Lineart",
+    ]
+    .iter()
+    {
+      match parse_dictionary(text) {
+        Err(ClipError::InvalidDictionary(x)) => assert!(x.starts_with("line 1: "), "{}", x),
+        x => panic!("{:?}: {:?}", text, x),
+      }
+    }
+  }
+}
@@ -0,0 +1,1066 @@
+use super::{
+  cleanup, collect_descendants, comment, concat_sql, concat_sql_to, conform, find_layer_index,
+  find_sqlite_in, find_sqlite_with, get_children, get_layer_roots, info, journal,
+  long_path::extended_path, move_file, naming, panel, pixels, plan, plan_renames_in, project,
+  rename_layers_batched, save_sql_only, schema, space, temp::create_temp_dir, CancelToken,
+  ClipError, ClipLayer, LayerTree, Layers, Panel, RenameOptions, RenamePlan, RenameReport, Warning,
+  DEFAULT_UPDATE_BATCH_SIZE,
+};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// size of the footer chunk (`CHNKFoot` and its data size)
+const FOOT_CHUNK_SIZE: u64 = 16;
+/// Layer columns of a new empty folder (`ClipDocument::create_folder`) as Clip Studio Paint saves them:
+/// shown, opaque, normal blending, not locked, with no render caches (mipmap, thumbnail),
+/// which Clip Studio Paint creates again. Other columns are NULL.
+const FOLDER_DEFAULTS: &[(&str, i64)] = &[
+  ("LayerType", 0),
+  ("LayerLock", 0),
+  ("LayerClip", 0),
+  ("LayerMasking", 32),
+  ("LayerOffsetX", 0),
+  ("LayerOffsetY", 0),
+  ("LayerRenderOffscrOffsetX", 0),
+  ("LayerRenderOffscrOffsetY", 0),
+  ("LayerMaskOffsetX", 0),
+  ("LayerMaskOffsetY", 0),
+  ("LayerMaskOffscrOffsetX", 0),
+  ("LayerMaskOffscrOffsetY", 0),
+  ("LayerOpacity", 256),
+  ("LayerComposite", 0),
+  ("LayerUsePaletteColor", 0),
+  ("LayerNoticeablePaletteColor", 0),
+  ("LayerPaletteRed", 0),
+  ("LayerPaletteGreen", 0),
+  ("LayerPaletteBlue", 0),
+  ("LayerFolder", 1),
+  ("LayerVisibility", 1),
+  ("LayerSelect", 0),
+  ("LayerNextIndex", 0),
+  ("LayerFirstChildIndex", 0),
+  ("LayerRenderMipmap", 0),
+  ("LayerLayerMaskMipmap", 0),
+  ("LayerRenderThumbnail", 0),
+  ("LayerLayerMaskThumbnail", 0),
+];
+
+/// Brief.
+///
+/// Time and size of opening a clip file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadStatistics {
+  /// time to find the sqlite3 data
+  pub scan: Duration,
+  /// time to copy the sqlite3 data (and the metadata before it) to the temporary directory
+  pub extract: Duration,
+  /// offset of the sqlite3 data, i.e. bytes scanned
+  pub scanned_bytes: u64,
+  /// size of the sqlite3 data
+  pub sqlite_bytes: u64,
+  /// bytes appended after the footer chunk by other writers. They are kept as they are when saved.
+  pub trailing_bytes: u64,
+}
+
+/// Brief.
+///
+/// A clip file opened for editing.
+///
+/// The sqlite3 data of the clip file is extracted to a temporary directory.
+/// Changes are written to a clip file by `save`.
+pub struct ClipDocument {
+  src: PathBuf,
+  index: usize,
+  /// position of the data after the sqlite3 data in `src`
+  tail: u64,
+  dir: TempDir,
+  sql_path: PathBuf,
+  conn: rusqlite::Connection,
+  state: journal::DatabaseState,
+  statistics: LoadStatistics,
+  /// rebuild the data base and fix the header when saved (`set_deterministic`)
+  deterministic: bool,
+  /// rebuild the data base when saved (`set_compact`)
+  compact: bool,
+  /// layers updated by one statement (`set_update_batch_size`)
+  update_batch_size: usize,
+  /// checked in long operations (`set_cancel_token`)
+  cancel: CancelToken,
+  registered: cleanup::Registered,
+}
+
+impl ClipDocument {
+  /// Brief.
+  ///
+  /// Open the clip file.
+  ///
+  /// * `src`: clip file path
+  pub fn open<P: AsRef<Path>>(src: P) -> Result<ClipDocument, ClipError> {
+    return ClipDocument::open_with_cancel(src, CancelToken::new());
+  }
+
+  /// Brief.
+  ///
+  /// `open` which can be cancelled with the token.
+  /// The token is also used by the operations of the document (`set_cancel_token`).
+  ///
+  /// * `src`: clip file path
+  /// * `cancel`: cancel token
+  pub fn open_with_cancel<P: AsRef<Path>>(
+    src: P,
+    cancel: CancelToken,
+  ) -> Result<ClipDocument, ClipError> {
+    let src = extended_path(src.as_ref());
+    let dir = create_temp_dir()?;
+    let sql_path = dir.path().join("sql.sql");
+
+    let start = Instant::now();
+    let (sqlsize, index) = match find_sqlite_with(&src, &cancel)? {
+      Some(x) => x,
+      None => return Err(ClipError::NotClipFile),
+    };
+    let scanned = Instant::now();
+    // the data base now, and the rebuilt file when saving
+    let src_size = match std::fs::metadata(&src) {
+      Ok(x) => x.len(),
+      Err(_) => return Err(ClipError::FileReadError),
+    };
+    space::check_space(
+      dir.path().parent().unwrap_or(dir.path()),
+      src_size.saturating_add(sqlsize),
+    )?;
+    save_sql_only(&src, &sql_path, sqlsize, index, &cancel)?;
+    let statistics = LoadStatistics {
+      scan: scanned - start,
+      extract: scanned.elapsed(),
+      scanned_bytes: index as u64,
+      sqlite_bytes: sqlsize,
+      trailing_bytes: 0,
+    };
+    let tail = index as u64 + sqlsize;
+    let mut doc = ClipDocument::init(
+      src.as_ref().to_path_buf(),
+      index,
+      tail,
+      dir,
+      sql_path,
+      statistics,
+    )?;
+    doc.cancel = cancel;
+    return Ok(doc);
+  }
+
+  /// Brief.
+  ///
+  /// Open clip data from any reader.
+  ///
+  /// The metadata before the sqlite3 data and the sqlite3 data are copied to a temporary directory,
+  /// so the reader is not needed after this.
+  ///
+  /// * `reader`: clip data
+  pub fn from_reader<R: Read + Seek>(mut reader: R) -> Result<ClipDocument, ClipError> {
+    let dir = create_temp_dir()?;
+    let head_path = dir.path().join("head.clip");
+    let sql_path = dir.path().join("sql.sql");
+
+    let start = Instant::now();
+    let size = match reader.seek(SeekFrom::End(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::IOError),
+    };
+    info::check_truncated(&mut reader, size)?;
+    if let Err(_) = reader.seek(SeekFrom::Start(0)) {
+      return Err(ClipError::IOError);
+    }
+    let (sqlsize, index) = match find_sqlite_in(&mut reader)? {
+      Some(x) => x,
+      None => return Err(ClipError::NotClipFile),
+    };
+    let scanned = Instant::now();
+    if let Err(_) = reader.seek(SeekFrom::Start(0)) {
+      return Err(ClipError::IOError);
+    }
+    copy_exact(&mut reader, &head_path, index as u64)?;
+    copy_exact(&mut reader, &sql_path, sqlsize)?;
+    // the footer and the data after it follow the metadata in the same file
+    let mut head = match std::fs::OpenOptions::new().append(true).open(&head_path) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::FileSaveError),
+    };
+    if let Err(_) = std::io::copy(&mut reader, &mut head) {
+      return Err(ClipError::FileReadError);
+    }
+    drop(head);
+    let statistics = LoadStatistics {
+      scan: scanned - start,
+      extract: scanned.elapsed(),
+      scanned_bytes: index as u64,
+      sqlite_bytes: sqlsize,
+      trailing_bytes: 0,
+    };
+    return ClipDocument::init(head_path, index, index as u64, dir, sql_path, statistics);
+  }
+
+  fn init(
+    src: PathBuf,
+    index: usize,
+    tail: u64,
+    dir: TempDir,
+    sql_path: PathBuf,
+    mut statistics: LoadStatistics,
+  ) -> Result<ClipDocument, ClipError> {
+    let state = journal::inspect_header(&sql_path)?;
+    statistics.trailing_bytes = match std::fs::metadata(&src) {
+      Ok(x) => x.len().saturating_sub(tail + FOOT_CHUNK_SIZE),
+      Err(_) => return Err(ClipError::FileReadError),
+    };
+    let conn = match rusqlite::Connection::open(&sql_path) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    if state.inconsistent_size {
+      journal::check_integrity(&conn)?;
+    }
+    return Ok(ClipDocument {
+      src,
+      index,
+      tail,
+      registered: cleanup::Registered::new(dir.path()),
+      dir,
+      sql_path,
+      conn,
+      state,
+      statistics,
+      deterministic: false,
+      compact: false,
+      update_batch_size: DEFAULT_UPDATE_BATCH_SIZE,
+      cancel: CancelToken::new(),
+    });
+  }
+
+  /// Brief.
+  ///
+  /// Copy the document, e.g. to write several variants from one scan of the clip file.
+  ///
+  /// The extracted sqlite3 data (with the changes made so far) is copied to a new temporary directory.
+  /// The clip file is not scanned again.
+  pub fn try_clone(&self) -> Result<ClipDocument, ClipError> {
+    if self.state.wal {
+      journal::checkpoint(&self.conn)?;
+    }
+    let dir = create_temp_dir()?;
+    let sql_path = dir.path().join("sql.sql");
+    if let Err(_) = std::fs::copy(&self.sql_path, &sql_path) {
+      return Err(ClipError::IOError);
+    }
+    // the metadata copied by `from_reader` is in the temporary directory
+    let src = if self.src.starts_with(self.dir.path()) {
+      let head_path = dir.path().join("head.clip");
+      if let Err(_) = std::fs::copy(&self.src, &head_path) {
+        return Err(ClipError::IOError);
+      }
+      head_path
+    } else {
+      self.src.clone()
+    };
+    let mut doc = ClipDocument::init(src, self.index, self.tail, dir, sql_path, self.statistics)?;
+    doc.deterministic = self.deterministic;
+    doc.compact = self.compact;
+    doc.update_batch_size = self.update_batch_size;
+    doc.cancel = self.cancel.clone();
+    return Ok(doc);
+  }
+
+  /// Brief.
+  ///
+  /// Check the token in long operations: renaming (for each layer) and saving (for each block copied).
+  /// A cancelled operation fails with `ClipError::Cancelled`, and the document should be dropped.
+  pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+    self.cancel = cancel;
+  }
+
+  /// Brief.
+  ///
+  /// Non fatal conditions found while loading the file (WAL mode, data after the footer, ...).
+  /// They are also added to the reports of `rename_layers` and `set_layer_names`.
+  pub fn warnings(&self) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = Vec::new();
+    if self.state.wal {
+      warnings.push(Warning::WalCheckpointed);
+    }
+    if self.statistics.trailing_bytes != 0 {
+      warnings.push(Warning::TrailingBytesKept(self.statistics.trailing_bytes));
+    }
+    if self.state.inconsistent_size {
+      warnings.push(Warning::InconsistentSize);
+    }
+    return warnings;
+  }
+
+  /// State of the embedded data base when it was extracted.
+  pub fn database_state(&self) -> journal::DatabaseState {
+    return self.state;
+  }
+
+  /// Brief.
+  ///
+  /// Make the saved file depend only on the input file and the changes (default: off),
+  /// e.g. for build pipelines which compare or cache the outputs.
+  ///
+  /// The data base is rebuilt by `VACUUM` before saving, so that the pages do not depend on the history of changes,
+  /// and the header fields which count writes are set to fixed values.
+  /// The `LayerUuid` of created folders is made from the main_id and the name instead of a random value.
+  ///
+  /// * `enable`: whether to enable
+  pub fn set_deterministic(&mut self, enable: bool) {
+    self.deterministic = enable;
+  }
+
+  /// Brief.
+  ///
+  /// Rebuild the data base by `VACUUM` before saving (default: off).
+  ///
+  /// The free pages left by deleted layers and history are removed, which often makes the clip file smaller.
+  ///
+  /// * `enable`: whether to enable
+  pub fn set_compact(&mut self, enable: bool) {
+    self.compact = enable;
+  }
+
+  /// Brief.
+  ///
+  /// Number of layers whose names are updated by one SQL statement
+  /// (default: `DEFAULT_UPDATE_BATCH_SIZE`).
+  ///
+  /// Larger batches write files with thousands of layers faster, and 1 updates the layers one by one.
+  /// Batches are limited to 16383 layers by the number of parameters of a statement.
+  ///
+  /// * `size`: layers in a batch. 0 is the same as 1.
+  pub fn set_update_batch_size(&mut self, size: usize) {
+    self.update_batch_size = size;
+  }
+
+  /// Time and size of opening the clip file.
+  pub fn load_statistics(&self) -> LoadStatistics {
+    return self.statistics;
+  }
+
+  /// The path of the extracted sqlite3 file.
+  pub fn sql_path(&self) -> &Path {
+    return &self.sql_path;
+  }
+
+  /// Brief.
+  ///
+  /// Layers read from the database one by one (`Layers::iter`).
+  pub fn layers(&self) -> Result<Layers<'_>, ClipError> {
+    return Layers::new(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// All layers for checking the tree structure (`LayerTree::validate`).
+  /// This succeeds even if the tree is broken.
+  pub fn layer_tree(&self) -> Result<LayerTree, ClipError> {
+    return LayerTree::read(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// Check that the data base exactly matches a known schema of Clip Studio Paint,
+  /// for callers which prefer refusing files to editing unknown versions by guess.
+  ///
+  /// Return.
+  ///
+  /// `ClipError::UnknownSchema` with the differences
+  pub fn check_schema(&self) -> Result<(), ClipError> {
+    return schema::check_schema(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// The column of the Layer table used for layer comments (search keywords),
+  /// which `RenameOptions::keep_old_names` writes to. `None` if the file has no such column.
+  pub fn layer_comment_column(&self) -> Result<Option<String>, ClipError> {
+    return comment::get_comment_column(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// The comment of the layer (`layer_comment_column`). `None` if it is not set.
+  ///
+  /// * `main_id`: main_id of the layer
+  pub fn layer_comment(&self, main_id: u64) -> Result<Option<String>, ClipError> {
+    return comment::get_comment(&self.conn, main_id);
+  }
+
+  /// Brief.
+  ///
+  /// Comments of all layers which have one, as `(main_id, comment)` in the order of main_id.
+  pub fn layer_comments(&self) -> Result<Vec<(u64, String)>, ClipError> {
+    return comment::get_comments(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// Set the comment of the layer, e.g. a review note. An empty comment clears it.
+  ///
+  /// * `main_id`: main_id of the layer
+  /// * `comment`: new comment
+  pub fn set_layer_comment(&mut self, main_id: u64, comment: &str) -> Result<(), ClipError> {
+    return comment::set_comment(&self.conn, main_id, comment);
+  }
+
+  /// Brief.
+  ///
+  /// The project name (title) of the file, e.g. "EP03_cut12". `None` if the file has no project record.
+  pub fn project_name(&self) -> Result<Option<String>, ClipError> {
+    return project::get_project_name(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// Set the project name (title) of the file.
+  ///
+  /// * `name`: new project name
+  pub fn set_project_name(&mut self, name: &str) -> Result<(), ClipError> {
+    return project::set_project_name(&self.conn, name);
+  }
+
+  /// Brief.
+  ///
+  /// The names of the canvases in the order of `RenameOptions::canvas`.
+  /// The name of a canvas is the story name (作品名) of its story information (`Canvas.ComicStoryStoryName`);
+  /// unnamed canvases are empty. `ClipError::UnknownSchema` if the file has no such column.
+  pub fn canvas_names(&self) -> Result<Vec<String>, ClipError> {
+    return project::get_canvas_names(&self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// Set the name of a canvas (`canvas_names`).
+  ///
+  /// * `canvas`: canvas number from 1. `ClipError::CanvasNotFound` if the file has no such canvas.
+  /// * `name`: new canvas name
+  pub fn set_canvas_name(&mut self, canvas: usize, name: &str) -> Result<(), ClipError> {
+    return project::set_canvas_name(&self.conn, canvas, name);
+  }
+
+  /// Brief.
+  ///
+  /// Advanced: run custom queries on the extracted sqlite3 database.
+  ///
+  /// The connection is the one used by the other methods and the changes are saved by `save`.
+  /// Nothing checks what `f` does: the layer tree (`LayerNextIndex`, `LayerFirstChildIndex`)
+  /// and the other tables must be kept consistent, or Clip Studio Paint may not open the saved file.
+  /// Do not close the connection or change the journal mode.
+  ///
+  /// ```no_run
+  /// let mut doc = renamelayer::clip::ClipDocument::open("in.clip")?;
+  /// let count: i64 = doc
+  ///   .with_connection(|conn| conn.query_row("SELECT count(*) FROM Layer", [], |r| r.get(0)))
+  ///   .unwrap();
+  /// # Ok::<(), renamelayer::clip::ClipError>(())
+  /// ```
+  ///
+  /// * `f`: function given the connection (re-exported as `clip::rusqlite`)
+  ///
+  /// Return.
+  ///
+  /// the value returned by `f`
+  pub fn with_connection<T, F>(&mut self, f: F) -> T
+  where
+    F: FnOnce(&mut rusqlite::Connection) -> T,
+  {
+    return f(&mut self.conn);
+  }
+
+  /// Brief.
+  ///
+  /// Rename layers (`rename_layers_in_sqlite`).
+  /// Unlike `rename_layers_in_sqlite`, the rule for empty layers is applied.
+  /// Same as `apply_plan` of `plan_renames`.
+  ///
+  /// The layers being read (`layers`) cannot be kept while renaming:
+  ///
+  /// ```compile_fail
+  /// use renamelayer::clip::{ClipDocument, RenameOptions};
+  /// let mut doc = ClipDocument::open("in.clip")?;
+  /// let layers = doc.layers()?;
+  /// doc.rename_layers(&RenameOptions::new())?;
+  /// drop(layers);
+  /// # Ok::<(), renamelayer::clip::ClipError>(())
+  /// ```
+  ///
+  /// * `options`: options of renaming
+  pub fn rename_layers(&mut self, options: &RenameOptions) -> Result<RenameReport, ClipError> {
+    let plan = self.plan_renames(options)?;
+    return self.apply_plan(&plan);
+  }
+
+  /// Brief.
+  ///
+  /// Decide new layer names without changing the file.
+  /// The rule for empty layers is applied.
+  ///
+  /// ```no_run
+  /// use renamelayer::clip::{ClipDocument, RenameOptions};
+  /// let mut doc = ClipDocument::open("in.clip")?;
+  /// let mut plan = doc.plan_renames(&RenameOptions::new())?;
+  /// plan.renames.retain(|r| !r.old_name.starts_with("下書き"));
+  /// doc.apply_plan(&plan)?;
+  /// doc.save("out.clip")?;
+  /// # Ok::<(), renamelayer::clip::ClipError>(())
+  /// ```
+  ///
+  /// * `options`: options of renaming
+  pub fn plan_renames(&self, options: &RenameOptions) -> Result<RenamePlan, ClipError> {
+    let empty_layers = if options.rules.empty_rule.is_some() {
+      pixels::find_empty_layers(&self.conn, &self.src)?
+    } else {
+      HashSet::new()
+    };
+    let mut plan = plan_renames_in(&self.conn, options, empty_layers)?;
+    plan.warnings.extend(self.warnings());
+    return Ok(plan);
+  }
+
+  /// Brief.
+  ///
+  /// Decide new layer names from a reference document with the same layer structure
+  /// (see `plan_conform_in_sqlite`). Apply the plan with `apply_plan`.
+  ///
+  /// * `reference`: document whose layers are named correctly
+  pub fn plan_conform(&self, reference: &ClipDocument) -> Result<RenamePlan, ClipError> {
+    let mut plan = conform::plan_conform_in(&reference.conn, &self.conn)?;
+    plan.warnings.extend(self.warnings());
+    return Ok(plan);
+  }
+
+  /// Brief.
+  ///
+  /// Name each layer after the panel containing its offset (see `plan_panels_in_sqlite`).
+  /// Apply the plan with `apply_plan`.
+  ///
+  /// * `panels`: panel definitions (`load_panels`)
+  pub fn plan_panels(&self, panels: &[Panel]) -> Result<RenamePlan, ClipError> {
+    let mut plan = panel::plan_panels_in(&self.conn, panels)?;
+    plan.warnings.extend(self.warnings());
+    return Ok(plan);
+  }
+
+  /// Brief.
+  ///
+  /// Decide new layer names with a callback instead of the rename rules (see `plan_with_in_sqlite`).
+  /// `Some(name)` renames the layer, and `None` keeps the name. Apply the plan with `apply_plan`.
+  ///
+  /// ```no_run
+  /// use renamelayer::clip::ClipDocument;
+  /// let mut doc = ClipDocument::open("in.clip")?;
+  /// let plan = doc.plan_with(|layer, context| match layer.folder {
+  ///   false if layer.name.starts_with("レイヤー") => Some(format!(
+  ///     "{}_{:02}",
+  ///     context.folders.join("_"),
+  ///     context.position
+  ///   )),
+  ///   _ => None,
+  /// })?;
+  /// doc.apply_plan(&plan)?;
+  /// doc.save("out.clip")?;
+  /// # Ok::<(), renamelayer::clip::ClipError>(())
+  /// ```
+  ///
+  /// * `f`: callback returning the new name
+  pub fn plan_with<F>(&self, f: F) -> Result<RenamePlan, ClipError>
+  where
+    F: FnMut(&naming::LayerView, &naming::RenameContext) -> Option<String>,
+  {
+    let mut plan = naming::plan_with_in(&self.conn, f)?;
+    plan.warnings.extend(self.warnings());
+    return Ok(plan);
+  }
+
+  /// Brief.
+  ///
+  /// Write the new names of a plan (`plan_renames`). All names are changed in one transaction.
+  ///
+  /// * `plan`: new names. It may be edited or deserialized.
+  ///
+  /// Return.
+  ///
+  /// renamed layers. `ClipError::StalePlan` if a layer name differs from `old_name` of the plan.
+  pub fn apply_plan(&mut self, plan: &RenamePlan) -> Result<RenameReport, ClipError> {
+    let tx = match self.conn.transaction() {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let report = plan::apply_plan_in(&tx, plan, self.update_batch_size, &self.cancel)?;
+    if let Err(_) = tx.commit() {
+      return Err(ClipError::SQLError);
+    }
+    return Ok(report);
+  }
+
+  /// Brief.
+  ///
+  /// Set names of layers. All names are changed in one transaction.
+  ///
+  /// * `names`: `(main_id, new name)`. Layers whose names are not changed are ignored.
+  ///
+  /// Return.
+  ///
+  /// renamed layers (`total_layers` is the number of `names`)
+  pub fn set_layer_names(&mut self, names: &[(u64, String)]) -> Result<RenameReport, ClipError> {
+    let tx = match self.conn.transaction() {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let mut v: Vec<Box<ClipLayer>> = Vec::new();
+    get_layer_roots(&tx, &mut v)?;
+    let mut report = RenameReport {
+      total_layers: names.len(),
+      renamed: Vec::new(),
+      warnings: Vec::new(),
+    };
+    let mut renames: Vec<(&ClipLayer, &str)> = Vec::with_capacity(names.len());
+    for (id, name) in names.iter() {
+      let index = match find_layer_index(&v, *id) {
+        Some(x) => x,
+        None => return Err(ClipError::LayerNotFound(*id)),
+      };
+      renames.push((&v[index], name));
+    }
+    rename_layers_batched(
+      &tx,
+      &renames,
+      self.update_batch_size,
+      &[],
+      &mut report,
+      &self.cancel,
+    )?;
+    if let Err(_) = tx.commit() {
+      return Err(ClipError::SQLError);
+    }
+    report.warnings.extend(self.warnings());
+    return Ok(report);
+  }
+
+  /// Brief.
+  ///
+  /// Write the clip file.
+  ///
+  /// * `dst`: output file. It may be the same as the opened file.
+  pub fn save<P: AsRef<Path>>(self, dst: P) -> Result<(), ClipError> {
+    let ClipDocument {
+      src,
+      index,
+      tail,
+      dir,
+      sql_path,
+      conn,
+      state,
+      deterministic,
+      compact,
+      cancel,
+      registered,
+      ..
+    } = self;
+    close_connection(conn, state, deterministic, compact, &sql_path)?;
+
+    let dst_path = extended_path(dst.as_ref());
+    let dst_path: &Path = &dst_path;
+    let dst_dir = match dst_path.parent() {
+      Some(x) if !x.as_os_str().is_empty() => x,
+      _ => Path::new("."),
+    };
+    if !dst_dir.exists() {
+      if let Err(_) = std::fs::create_dir_all(dst_dir) {
+        return Err(ClipError::CreateDirectoryError);
+      }
+    }
+
+    // the rebuilt file is written to the temporary directory, and copied if the output is on another disk
+    let required = output_size_estimate(&src, index, tail, &sql_path)?;
+    space::check_space(dir.path().parent().unwrap_or(dir.path()), required)?;
+    if !space::same_file_system(dir.path(), dst_dir) {
+      space::check_space(dst_dir, required)?;
+    }
+
+    let out_path = dir.path().join("out.clip");
+    concat_sql(&src, &sql_path, &out_path, index, tail, &cancel)?;
+    cancel.check()?;
+
+    move_file(&out_path, dst_path)?;
+
+    drop(registered);
+    if let Err(_) = dir.close() {
+      return Err(ClipError::IOError);
+    }
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// Write the clip data to any writer.
+  ///
+  /// * `writer`: output
+  pub fn save_to<W: Write + Seek>(self, writer: W) -> Result<(), ClipError> {
+    let ClipDocument {
+      src,
+      index,
+      tail,
+      dir,
+      sql_path,
+      conn,
+      state,
+      deterministic,
+      compact,
+      cancel,
+      registered,
+      ..
+    } = self;
+    close_connection(conn, state, deterministic, compact, &sql_path)?;
+    concat_sql_to(
+      &src,
+      &sql_path,
+      BufWriter::new(writer),
+      index,
+      tail,
+      &cancel,
+    )?;
+    drop(registered);
+    if let Err(_) = dir.close() {
+      return Err(ClipError::IOError);
+    }
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// Create a new empty folder at the top of `parent`.
+  ///
+  /// The columns of the folder are `FOLDER_DEFAULTS`, and the canvas is the one of `parent`.
+  ///
+  /// * `parent`: main_id of the parent folder
+  /// * `name`: folder name
+  ///
+  /// Return.
+  ///
+  /// main_id of the new folder
+  pub fn create_folder(&mut self, parent: u64, name: &str) -> Result<u64, ClipError> {
+    let tx = match self.conn.transaction() {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let mut v: Vec<Box<ClipLayer>> = Vec::new();
+    get_layer_roots(&tx, &mut v)?;
+    let parent_index = match find_layer_index(&v, parent) {
+      Some(x) => x,
+      None => return Err(ClipError::LayerNotFound(parent)),
+    };
+    if v[parent_index].layer_folder == 0 {
+      return Err(ClipError::InvalidLayerMove(parent));
+    }
+
+    let main_id = next_layer_main_id(&tx, &v)?;
+    let columns = super::get_layer_columns(&tx)?;
+    let has = |name: &str| columns.iter().any(|x| x == name);
+    let mut names: Vec<String> = vec!["MainId".to_string(), "LayerName".to_string()];
+    let mut values: Vec<String> = vec!["?".to_string(), "?".to_string()];
+    let mut params: Vec<rusqlite::types::Value> = vec![
+      rusqlite::types::Value::Integer(main_id as i64),
+      rusqlite::types::Value::Text(name.to_string()),
+    ];
+    if has("CanvasId") {
+      names.push("CanvasId".to_string());
+      values.push("CanvasId".to_string());
+    }
+    if has("LayerUuid") {
+      names.push("LayerUuid".to_string());
+      values.push("?".to_string());
+      params.push(rusqlite::types::Value::Text(new_layer_uuid(
+        main_id,
+        name,
+        self.deterministic,
+      )));
+    }
+    for (column, value) in FOLDER_DEFAULTS.iter().filter(|(x, _)| has(x)) {
+      names.push(column.to_string());
+      values.push(value.to_string());
+    }
+    params.push(rusqlite::types::Value::Integer(parent as i64));
+    // the canvas is read from the parent folder
+    let sql = format!(
+      "INSERT INTO Layer ({}) SELECT {} FROM Layer WHERE MainId = ?",
+      names.join(", "),
+      values.join(", ")
+    );
+    if let Err(_) = tx.execute(&sql, rusqlite::params_from_iter(params.iter())) {
+      return Err(ClipError::SQLError);
+    }
+    if let Err(_) = tx.execute(
+      "UPDATE ElemScheme SET MaxIndex = $1 WHERE TableName = 'Layer' AND MaxIndex < $1",
+      rusqlite::params![main_id],
+    ) {
+      return Err(ClipError::SQLError);
+    }
+
+    let mut v: Vec<Box<ClipLayer>> = Vec::new();
+    get_layer_roots(&tx, &mut v)?;
+    insert_into_folder(&tx, &v, main_id, parent, usize::MAX)?;
+    if let Err(_) = tx.commit() {
+      return Err(ClipError::SQLError);
+    }
+    return Ok(main_id);
+  }
+
+  /// Brief.
+  ///
+  /// Move a layer (or folder with its contents) into `new_parent`.
+  ///
+  /// * `id`: main_id of the layer
+  /// * `new_parent`: main_id of the destination folder
+  /// * `position`: position in the destination folder counted from the bottom layer (0).
+  ///   A position larger than the number of layers puts the layer at the top.
+  pub fn move_layer(&mut self, id: u64, new_parent: u64, position: usize) -> Result<(), ClipError> {
+    let tx = match self.conn.transaction() {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let mut v: Vec<Box<ClipLayer>> = Vec::new();
+    let roots = get_layer_roots(&tx, &mut v)?;
+    let index = match find_layer_index(&v, id) {
+      Some(x) => x,
+      None => return Err(ClipError::LayerNotFound(id)),
+    };
+    let parent_index = match find_layer_index(&v, new_parent) {
+      Some(x) => x,
+      None => return Err(ClipError::LayerNotFound(new_parent)),
+    };
+    if roots.contains(&id) || v[parent_index].layer_folder == 0 {
+      return Err(ClipError::InvalidLayerMove(id));
+    }
+    if v[index].layer_folder != 0 {
+      // a folder cannot be moved into itself
+      let mut descendants = vec![id];
+      collect_descendants(&v, index, &mut descendants)?;
+      if descendants.contains(&new_parent) {
+        return Err(ClipError::InvalidLayerMove(id));
+      }
+    }
+
+    // unlink
+    let old_parent = match find_parent(&v, id)? {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    let next = v[index].layer_next_index;
+    let siblings = get_children(&v, old_parent)?;
+    let pos = match siblings.iter().position(|x| *x == index) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    if pos == 0 {
+      set_first_child_index(&tx, v[old_parent].main_id, next)?;
+    } else {
+      set_next_index(&tx, v[siblings[pos - 1]].main_id, next)?;
+    }
+    set_next_index(&tx, id, 0)?;
+
+    let mut v: Vec<Box<ClipLayer>> = Vec::new();
+    get_layer_roots(&tx, &mut v)?;
+    insert_into_folder(&tx, &v, id, new_parent, position)?;
+    if let Err(_) = tx.commit() {
+      return Err(ClipError::SQLError);
+    }
+    return Ok(());
+  }
+}
+
+/// Brief
+///
+/// Find the folder which has the layer as a child.
+///
+/// Return.
+///
+/// index of `v`
+fn find_parent(v: &[Box<ClipLayer>], main_id: u64) -> Result<Option<usize>, ClipError> {
+  for (i, l) in v.iter().enumerate() {
+    if l.layer_folder == 0 {
+      continue;
+    }
+    for c in get_children(v, i)? {
+      if v[c].main_id == main_id {
+        return Ok(Some(i));
+      }
+    }
+  }
+  return Ok(None);
+}
+
+/// Brief
+///
+/// Link an unlinked layer into the chain of the folder.
+///
+/// * `v`: all layer information
+/// * `id`: main_id of the layer
+/// * `parent`: main_id of the folder
+/// * `position`: position counted from the bottom layer
+fn insert_into_folder(
+  conn: &rusqlite::Connection,
+  v: &[Box<ClipLayer>],
+  id: u64,
+  parent: u64,
+  position: usize,
+) -> Result<(), ClipError> {
+  let parent_index = match find_layer_index(v, parent) {
+    Some(x) => x,
+    None => return Err(ClipError::LayerNotFound(parent)),
+  };
+  let children = get_children(v, parent_index)?;
+  let position = std::cmp::min(position, children.len());
+  if position == 0 {
+    set_next_index(conn, id, v[parent_index].layer_first_child_index)?;
+    set_first_child_index(conn, parent, id)?;
+  } else {
+    let prev = &v[children[position - 1]];
+    set_next_index(conn, id, prev.layer_next_index)?;
+    set_next_index(conn, prev.main_id, id)?;
+  }
+  return Ok(());
+}
+
+fn set_next_index(conn: &rusqlite::Connection, main_id: u64, next: u64) -> Result<(), ClipError> {
+  if let Err(_) = conn.execute(
+    "UPDATE Layer SET LayerNextIndex = $1 WHERE MainId = $2",
+    rusqlite::params![next, main_id],
+  ) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+fn set_first_child_index(
+  conn: &rusqlite::Connection,
+  main_id: u64,
+  first: u64,
+) -> Result<(), ClipError> {
+  if let Err(_) = conn.execute(
+    "UPDATE Layer SET LayerFirstChildIndex = $1 WHERE MainId = $2",
+    rusqlite::params![first, main_id],
+  ) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Unused main_id for a new layer.
+/// `ElemScheme.MaxIndex` of the Layer table is taken into account.
+fn next_layer_main_id(conn: &rusqlite::Connection, v: &[Box<ClipLayer>]) -> Result<u64, ClipError> {
+  let max_id = v.iter().map(|x| x.main_id).max().unwrap_or(0);
+  let max_index: Option<u64> = match conn.query_row(
+    "SELECT MaxIndex FROM ElemScheme WHERE TableName = 'Layer'",
+    [],
+    |row| row.get(0),
+  ) {
+    Ok(x) => x,
+    Err(rusqlite::Error::QueryReturnedNoRows) => None,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return Ok(std::cmp::max(max_id, max_index.unwrap_or(0)) + 1);
+}
+
+/// Brief.
+///
+/// Size of the clip file rebuilt from `src` with the sqlite3 data in `sql_path`.
+///
+/// * `index`, `tail`: position of the old sqlite3 data in `src`
+fn output_size_estimate(
+  src: &Path,
+  index: usize,
+  tail: u64,
+  sql_path: &Path,
+) -> Result<u64, ClipError> {
+  let (src_size, sql_size) = match (std::fs::metadata(src), std::fs::metadata(sql_path)) {
+    (Ok(x), Ok(y)) => (x.len(), y.len()),
+    _ => return Err(ClipError::FileReadError),
+  };
+  return Ok(src_size.saturating_sub(tail - index as u64) + sql_size);
+}
+
+/// Close the data base before writing it back.
+fn close_connection(
+  conn: rusqlite::Connection,
+  state: journal::DatabaseState,
+  deterministic: bool,
+  compact: bool,
+  sql_path: &Path,
+) -> Result<(), ClipError> {
+  if state.wal {
+    journal::checkpoint(&conn)?;
+  }
+  if deterministic || compact {
+    journal::vacuum(&conn)?;
+  }
+  if let Err(_) = conn.close() {
+    return Err(ClipError::SQLError);
+  }
+  if deterministic {
+    journal::fix_header_counters(sql_path)?;
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Create a `LayerUuid` value in the same form as Clip Studio Paint (`xxxxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxx`).
+///
+/// * `main_id`: main_id of the new layer
+/// * `name`: name of the new layer
+/// * `deterministic`: make the same value from the same `main_id` and `name` instead of a random value.
+///   The value is made from the SHA-256 as `{uuid}` of templates, so it does not change with the Rust version.
+fn new_layer_uuid(main_id: u64, name: &str, deterministic: bool) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(format!("renamelayer layer {} {}", main_id, name).as_bytes());
+  if !deterministic {
+    let mut random = RandomState::new().build_hasher();
+    random.write_u64(main_id);
+    hasher.update(random.finish().to_be_bytes());
+  }
+  let hex: String = hasher.finalize()[..16]
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect();
+  return format!(
+    "{}-{}-{}-{}-{}",
+    &hex[0..10],
+    &hex[10..14],
+    &hex[14..18],
+    &hex[18..22],
+    &hex[22..32]
+  );
+}
+
+/// Copy `size` bytes of the reader to a new file.
+fn copy_exact<R: Read>(reader: &mut R, path: &Path, size: u64) -> Result<(), ClipError> {
+  let mut file = match std::fs::File::create(path) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  let copied = match std::io::copy(&mut reader.take(size), &mut file) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if copied != size {
+    return Err(ClipError::FileReadError);
+  }
+  return Ok(());
+}
@@ -0,0 +1,256 @@
+use super::share::open_shared;
+use super::sniff::{check_file_magic, FILE_MAGIC, SNIFF_SIZE};
+use super::ClipError;
+use std::convert::TryInto;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const CHUNK_MAGIC: &[u8; 4] = b"CHNK";
+/// `CSFCHUNK`, file size, offset of the first chunk
+const FILE_HEADER_SIZE: usize = 24;
+/// `CHNK`, chunk type, data size
+const CHUNK_HEADER_SIZE: u64 = 16;
+
+/// Brief.
+///
+/// A chunk of a clip file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+  /// chunk type (`Head`, `Exta`, `SQLi`, `Foot`)
+  pub kind: String,
+  /// position of the chunk header (`CHNK`) in the file
+  pub offset: u64,
+  /// data size of the chunk (the chunk header is not included)
+  pub length: u64,
+}
+
+impl ChunkInfo {
+  /// Position of the chunk data in the file.
+  pub fn data_offset(&self) -> u64 {
+    return self.offset + CHUNK_HEADER_SIZE;
+  }
+}
+
+/// Brief.
+///
+/// Structure of a clip file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipInfo {
+  /// file size written in the file header
+  pub file_size: u64,
+  /// chunks in the order of the file
+  pub chunks: Vec<ChunkInfo>,
+}
+
+impl ClipInfo {
+  /// The first chunk of the type.
+  pub fn chunk(&self, kind: &str) -> Option<&ChunkInfo> {
+    return self.chunks.iter().find(|c| c.kind == kind);
+  }
+}
+
+/// Brief.
+///
+/// Read the chunk structure of a clip file.
+///
+/// * `path`: clip file path
+pub fn info<P: AsRef<Path>>(path: P) -> Result<ClipInfo, ClipError> {
+  let file = match open_shared(path) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  let actual_size = match file.metadata() {
+    Ok(x) => x.len(),
+    Err(_) => return Err(ClipError::FileReadError),
+  };
+  let mut inf = BufReader::new(file);
+  check_truncated(&mut inf, actual_size)?;
+  if let Err(_) = inf.seek(SeekFrom::Start(0)) {
+    return Err(ClipError::IOError);
+  }
+
+  let mut header = [0u8; FILE_HEADER_SIZE];
+  if let Err(_) = inf.read_exact(&mut header) {
+    return Err(ClipError::NotClipFile);
+  }
+  check_file_magic(&header[..SNIFF_SIZE])?;
+  let file_size = u64::from_be_bytes(header[8..16].try_into().unwrap());
+  let mut offset = u64::from_be_bytes(header[16..24].try_into().unwrap());
+
+  let mut chunks: Vec<ChunkInfo> = Vec::new();
+  loop {
+    let data_offset = match offset.checked_add(CHUNK_HEADER_SIZE) {
+      Some(x) if x <= actual_size => x,
+      Some(_) => break,
+      None => return Err(ClipError::Truncated(actual_size, offset.max(file_size))),
+    };
+    if let Err(_) = inf.seek(SeekFrom::Start(offset)) {
+      return Err(ClipError::IOError);
+    }
+    let mut chunk = [0u8; CHUNK_HEADER_SIZE as usize];
+    if let Err(_) = inf.read_exact(&mut chunk) {
+      return Err(ClipError::FileReadError);
+    }
+    if &chunk[0..4] != CHUNK_MAGIC {
+      return Err(ClipError::UnknownFileStruct);
+    }
+    let kind = String::from_utf8_lossy(&chunk[4..8]).to_string();
+    let length = u64::from_be_bytes(chunk[8..16].try_into().unwrap());
+    let next = match data_offset.checked_add(length) {
+      Some(x) if x <= actual_size => x,
+      Some(x) => return Err(ClipError::Truncated(actual_size, x.max(file_size))),
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    let foot = kind == "Foot";
+    chunks.push(ChunkInfo {
+      kind,
+      offset,
+      length,
+    });
+    if foot {
+      break;
+    }
+    offset = next;
+  }
+
+  return Ok(ClipInfo { file_size, chunks });
+}
+
+/// Brief.
+///
+/// Detect a clip file cut short (e.g. by a sync tool) from the sizes declared in the file,
+/// without scanning the whole file.
+///
+/// * `inf`: file data
+/// * `actual_size`: size of the data
+///
+/// Return.
+///
+/// `ClipError::EmptyFile` if the data is empty, `ClipError::Truncated` if the file header or a chunk
+/// extends past the end of the data. `Ok` if it is not truncated, or is not a clip file.
+pub(super) fn check_truncated<R: Read + Seek>(
+  inf: &mut R,
+  actual_size: u64,
+) -> Result<(), ClipError> {
+  if actual_size == 0 {
+    return Err(ClipError::EmptyFile);
+  }
+  if let Err(_) = inf.seek(SeekFrom::Start(0)) {
+    return Err(ClipError::IOError);
+  }
+  let mut header: Vec<u8> = Vec::with_capacity(FILE_HEADER_SIZE);
+  if let Err(_) = inf
+    .by_ref()
+    .take(FILE_HEADER_SIZE as u64)
+    .read_to_end(&mut header)
+  {
+    return Err(ClipError::FileReadError);
+  }
+  if !header.starts_with(FILE_MAGIC) && !FILE_MAGIC.starts_with(&header) {
+    // not a clip file
+    return Ok(());
+  }
+  if header.len() < FILE_HEADER_SIZE {
+    return Err(ClipError::Truncated(actual_size, FILE_HEADER_SIZE as u64));
+  }
+  let file_size = u64::from_be_bytes(header[8..16].try_into().unwrap());
+  let mut offset = u64::from_be_bytes(header[16..24].try_into().unwrap());
+  loop {
+    let data_offset = match offset.checked_add(CHUNK_HEADER_SIZE) {
+      Some(x) if x <= actual_size => x,
+      Some(_) => break,
+      None => return Err(ClipError::Truncated(actual_size, offset.max(file_size))),
+    };
+    if let Err(_) = inf.seek(SeekFrom::Start(offset)) {
+      return Err(ClipError::IOError);
+    }
+    let mut chunk = [0u8; CHUNK_HEADER_SIZE as usize];
+    if let Err(_) = inf.read_exact(&mut chunk) {
+      return Err(ClipError::FileReadError);
+    }
+    if &chunk[0..4] != CHUNK_MAGIC || &chunk[4..8] == b"Foot" {
+      break;
+    }
+    let length = u64::from_be_bytes(chunk[8..16].try_into().unwrap());
+    offset = match data_offset.checked_add(length) {
+      Some(x) if x <= actual_size => x,
+      Some(x) => return Err(ClipError::Truncated(actual_size, x.max(file_size))),
+      None => break,
+    };
+  }
+  if file_size > actual_size {
+    return Err(ClipError::Truncated(actual_size, file_size));
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  /// clip file data with the declared file size and chunks of `(type, declared length, actual length)`
+  fn clip_data(file_size: u64, chunks: &[(&[u8; 4], u64, usize)]) -> Vec<u8> {
+    let mut data = FILE_MAGIC.to_vec();
+    data.extend_from_slice(&file_size.to_be_bytes());
+    data.extend_from_slice(&(FILE_HEADER_SIZE as u64).to_be_bytes());
+    for (kind, length, actual) in chunks.iter() {
+      data.extend_from_slice(CHUNK_MAGIC);
+      data.extend_from_slice(*kind);
+      data.extend_from_slice(&length.to_be_bytes());
+      data.extend(std::iter::repeat(0u8).take(*actual));
+    }
+    return data;
+  }
+
+  fn check(data: &[u8]) -> Result<(), ClipError> {
+    return check_truncated(&mut Cursor::new(data), data.len() as u64);
+  }
+
+  #[test]
+  fn complete_file() {
+    let data = clip_data(24 + 16 + 10 + 16, &[(b"Head", 10, 10), (b"Foot", 0, 0)]);
+    assert!(check(&data).is_ok());
+  }
+
+  #[test]
+  fn empty_file() {
+    assert!(matches!(check(&[]), Err(ClipError::EmptyFile)));
+  }
+
+  #[test]
+  fn not_a_clip_file() {
+    assert!(check(b"\x89PNG\r\n\x1a\n").is_ok());
+  }
+
+  #[test]
+  fn cut_in_the_header() {
+    let data = clip_data(1000, &[]);
+    assert!(matches!(
+      check(&data[..12]),
+      Err(ClipError::Truncated(12, 24))
+    ));
+  }
+
+  #[test]
+  fn cut_in_a_chunk() {
+    let data = clip_data(24 + 16 + 100, &[(b"Head", 100, 50)]);
+    assert!(matches!(check(&data), Err(ClipError::Truncated(90, 140))));
+  }
+
+  #[test]
+  fn cut_after_the_chunks() {
+    let data = clip_data(1000, &[(b"Head", 10, 10)]);
+    assert!(matches!(check(&data), Err(ClipError::Truncated(50, 1000))));
+  }
+
+  #[test]
+  fn overflowing_offset() {
+    let mut data = clip_data(100, &[]);
+    data[16..24].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(matches!(
+      check(&data),
+      Err(ClipError::Truncated(24, u64::MAX))
+    ));
+  }
+}
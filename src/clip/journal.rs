@@ -0,0 +1,118 @@
+use super::ClipError;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Brief.
+///
+/// State of the embedded sqlite3 data base found when it was extracted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DatabaseState {
+  /// The data base is in WAL mode. Changes are checkpointed into the data base before it is written back.
+  pub wal: bool,
+  /// The page count in the header does not match the data size,
+  /// i.e. the data base was saved in the middle of a transaction.
+  /// The data base passed an integrity check after it was opened.
+  pub inconsistent_size: bool,
+}
+
+/// Brief
+///
+/// Read the sqlite3 header of the extracted data base.
+///
+/// * `sqlfile`: sqlite3 file path
+pub(super) fn inspect_header<P: AsRef<Path>>(sqlfile: P) -> Result<DatabaseState, ClipError> {
+  let mut f = match File::open(&sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  let file_size = match f.metadata() {
+    Ok(x) => x.len(),
+    Err(_) => return Err(ClipError::FileReadError),
+  };
+  let mut header = [0u8; 100];
+  if let Err(_) = f.read_exact(&mut header) {
+    return Err(ClipError::UnknownFileStruct);
+  }
+
+  let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+    1 => 65536,
+    x => x as u64,
+  };
+  let write_version = header[18];
+  let read_version = header[19];
+  let change_counter = u32::from_be_bytes([header[24], header[25], header[26], header[27]]);
+  let page_count = u32::from_be_bytes([header[28], header[29], header[30], header[31]]) as u64;
+  let version_valid_for = u32::from_be_bytes([header[92], header[93], header[94], header[95]]);
+
+  // The page count is only valid when the change counter matches "version-valid-for".
+  let inconsistent_size =
+    page_size != 0 && (change_counter != version_valid_for || page_count * page_size != file_size);
+  return Ok(DatabaseState {
+    wal: write_version == 2 || read_version == 2,
+    inconsistent_size,
+  });
+}
+
+/// Brief
+///
+/// Check the integrity of the data base.
+pub(super) fn check_integrity(conn: &rusqlite::Connection) -> Result<(), ClipError> {
+  let result: String = match conn.query_row("PRAGMA quick_check", [], |row| row.get(0)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::CorruptDatabase),
+  };
+  if result != "ok" {
+    return Err(ClipError::CorruptDatabase);
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Write all changes in the WAL file into the data base.
+pub(super) fn checkpoint(conn: &rusqlite::Connection) -> Result<(), ClipError> {
+  if let Err(_) = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Rebuild the data base with `VACUUM`, so that the pages depend only on the contents
+/// and not on the history of changes.
+pub(super) fn vacuum(conn: &rusqlite::Connection) -> Result<(), ClipError> {
+  if let Err(_) = conn.execute_batch("VACUUM") {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Set the header fields which change on every write to fixed values:
+/// the file change counter, "version-valid-for" and the version of SQLite which wrote the file.
+/// Call this after the connection is closed.
+///
+/// * `sqlfile`: sqlite3 file path
+pub(super) fn fix_header_counters<P: AsRef<Path>>(sqlfile: P) -> Result<(), ClipError> {
+  let mut f = match OpenOptions::new().read(true).write(true).open(&sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  // file change counter (24) and version-valid-for (92) must be equal
+  // for the page count in the header to be valid
+  for (offset, value) in [(24, 1u32), (92, 1u32), (96, FIXED_SQLITE_VERSION)] {
+    if let Err(_) = f.seek(SeekFrom::Start(offset)) {
+      return Err(ClipError::FileSaveError);
+    }
+    if let Err(_) = f.write_all(&value.to_be_bytes()) {
+      return Err(ClipError::FileSaveError);
+    }
+  }
+  return Ok(());
+}
+
+/// `SQLITE_VERSION_NUMBER` written by `fix_header_counters` (3.35.0)
+const FIXED_SQLITE_VERSION: u32 = 3_035_000;
@@ -0,0 +1,238 @@
+use super::{get_canvas_root_folders, ClipError, LayerRecord, Layers};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// `LayerType` of the root folder
+const ROOT_LAYER_TYPE: u64 = 256;
+
+/// Brief.
+///
+/// A problem of the layer tree found by `LayerTree::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TreeIssue {
+  /// No root folder was found.
+  NoRoot,
+  /// More than one root folder candidate (main_ids) was found.
+  AmbiguousRoot(Vec<u64>),
+  /// Several rows of the Layer table have the same main_id.
+  DuplicateMainId(u64),
+  /// The layer `from` refers to a layer `to` which does not exist
+  /// (`LayerNextIndex` or `LayerFirstChildIndex`).
+  DanglingReference { from: u64, to: u64 },
+  /// The layer is reached again while walking the tree from the root.
+  Cycle(u64),
+  /// The layer has children (`LayerFirstChildIndex`) but its folder flag is not set.
+  FolderFlagUnset(u64),
+  /// The layer is not reachable from the root folder.
+  OrphanLayer(u64),
+}
+
+impl fmt::Display for TreeIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return match self {
+      TreeIssue::NoRoot => write!(f, "root folder not found"),
+      TreeIssue::AmbiguousRoot(ids) => write!(f, "root folder is ambiguous: {:?}", ids),
+      TreeIssue::DuplicateMainId(id) => write!(f, "main_id {} is duplicated", id),
+      TreeIssue::DanglingReference { from, to } => {
+        write!(f, "layer {} refers to missing layer {}", from, to)
+      }
+      TreeIssue::Cycle(id) => write!(f, "layer {} is in a cycle", id),
+      TreeIssue::FolderFlagUnset(id) => write!(f, "layer {} has children but is not a folder", id),
+      TreeIssue::OrphanLayer(id) => write!(f, "layer {} is not reachable from the root", id),
+    };
+  }
+}
+
+/// Brief.
+///
+/// All rows of the Layer table, for checking the tree structure.
+///
+/// Unlike the rename functions, a `LayerTree` can be built from broken files:
+/// the problems are reported by `validate` instead of errors.
+///
+/// ```no_run
+/// use renamelayer::clip::ClipDocument;
+/// let doc = ClipDocument::open("in.clip")?;
+/// for issue in doc.layer_tree()?.validate() {
+///   println!("{}", issue);
+/// }
+/// # Ok::<(), renamelayer::clip::ClipError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LayerTree {
+  layers: Vec<LayerRecord>,
+  /// `Canvas.CanvasRootFolder` of all canvases
+  canvas_roots: Vec<u64>,
+  /// main_id → index of `layers` (the first one if duplicated)
+  by_id: HashMap<u64, usize>,
+  /// main_id → main_id of the folder containing it
+  parents: HashMap<u64, u64>,
+}
+
+impl LayerTree {
+  /// Read all layers of the database.
+  pub(super) fn read(conn: &rusqlite::Connection) -> Result<LayerTree, ClipError> {
+    let mut layers: Vec<LayerRecord> = Vec::new();
+    let mut reader = Layers::new(conn)?;
+    for layer in reader.iter()? {
+      layers.push(layer?);
+    }
+    let canvas_roots = get_canvas_root_folders(conn)?;
+    let mut by_id: HashMap<u64, usize> = HashMap::new();
+    for (i, l) in layers.iter().enumerate() {
+      by_id.entry(l.main_id).or_insert(i);
+    }
+    let mut parents: HashMap<u64, u64> = HashMap::new();
+    for l in layers.iter() {
+      let mut next = l.first_child_index;
+      while let Some(c) = by_id.get(&next).map(|i| &layers[*i]) {
+        // a layer in a cycle or in several folders keeps the first parent
+        if parents.contains_key(&next) {
+          break;
+        }
+        parents.insert(next, l.main_id);
+        next = c.next_index;
+      }
+    }
+    return Ok(LayerTree {
+      layers,
+      canvas_roots,
+      by_id,
+      parents,
+    });
+  }
+
+  /// `Canvas.CanvasRootFolder` of all canvases, in the order of the Canvas table.
+  pub fn canvas_roots(&self) -> &[u64] {
+    return &self.canvas_roots;
+  }
+
+  /// main_id of the folder containing the layer. `None` for root folders and unknown or orphan layers.
+  pub fn parent(&self, main_id: u64) -> Option<u64> {
+    return self.parents.get(&main_id).copied();
+  }
+
+  /// Brief.
+  ///
+  /// Names of the folders containing the layer from the top level, followed by the name of the layer
+  /// (the root folder is not included).
+  ///
+  /// Return.
+  ///
+  /// `None` if the layer is not in the file, not reachable from a root folder, or is a root folder.
+  pub fn path(&self, main_id: u64) -> Option<Vec<String>> {
+    let mut path: Vec<String> = Vec::new();
+    let mut current = main_id;
+    while let Some(parent) = self.parent(current) {
+      path.push(self.layers[*self.by_id.get(&current)?].name.clone());
+      if path.len() > self.layers.len() {
+        return None;
+      }
+      current = parent;
+    }
+    if path.is_empty() || !self.canvas_roots.contains(&current) {
+      return None;
+    }
+    path.reverse();
+    return Some(path);
+  }
+
+  /// Depth of the layer in the tree. Top level layers are 0. `None` in the same cases as `path`.
+  pub fn depth(&self, main_id: u64) -> Option<usize> {
+    return self.path(main_id).map(|x| x.len() - 1);
+  }
+
+  /// Rows of the Layer table in the order of the table.
+  pub fn layers(&self) -> &[LayerRecord] {
+    return &self.layers;
+  }
+
+  /// Brief.
+  ///
+  /// Check the tree structure.
+  ///
+  /// Return.
+  ///
+  /// found problems. Empty if the tree is valid.
+  pub fn validate(&self) -> Vec<TreeIssue> {
+    let mut issues: Vec<TreeIssue> = Vec::new();
+    let mut by_id: HashMap<u64, &LayerRecord> = HashMap::new();
+    for l in self.layers.iter() {
+      if by_id.insert(l.main_id, l).is_some() {
+        issues.push(TreeIssue::DuplicateMainId(l.main_id));
+      }
+    }
+    for l in self.layers.iter() {
+      for to in [l.next_index, l.first_child_index] {
+        if to != 0 && !by_id.contains_key(&to) {
+          issues.push(TreeIssue::DanglingReference {
+            from: l.main_id,
+            to,
+          });
+        }
+      }
+      if l.first_child_index != 0 && !l.folder {
+        issues.push(TreeIssue::FolderFlagUnset(l.main_id));
+      }
+    }
+
+    let canvas_roots: Vec<u64> = self
+      .canvas_roots
+      .iter()
+      .filter(|x| by_id.contains_key(x))
+      .cloned()
+      .collect();
+    let roots = if !canvas_roots.is_empty() {
+      canvas_roots
+    } else {
+      let mut candidates: Vec<u64> = self
+        .layers
+        .iter()
+        .filter(|x| x.layer_type == ROOT_LAYER_TYPE && x.folder)
+        .map(|x| x.main_id)
+        .collect();
+      candidates.dedup();
+      match candidates.len() {
+        0 => {
+          issues.push(TreeIssue::NoRoot);
+          return issues;
+        }
+        1 => candidates,
+        _ => {
+          issues.push(TreeIssue::AmbiguousRoot(candidates));
+          return issues;
+        }
+      }
+    };
+
+    // walk the trees from the roots
+    let mut visited: HashSet<u64> = roots.iter().cloned().collect();
+    let mut folders: Vec<u64> = roots;
+    while let Some(folder) = folders.pop() {
+      let mut next = by_id[&folder].first_child_index;
+      while let Some(l) = by_id.get(&next) {
+        if !visited.insert(next) {
+          issues.push(TreeIssue::Cycle(next));
+          break;
+        }
+        if l.first_child_index != 0 {
+          folders.push(next);
+        }
+        next = l.next_index;
+      }
+    }
+    let mut reported: HashSet<u64> = HashSet::new();
+    for l in self.layers.iter() {
+      if !visited.contains(&l.main_id) && reported.insert(l.main_id) {
+        issues.push(TreeIssue::OrphanLayer(l.main_id));
+      }
+    }
+    return issues;
+  }
+
+  /// Whether `validate` finds no problem.
+  pub fn is_valid(&self) -> bool {
+    return self.validate().is_empty();
+  }
+}
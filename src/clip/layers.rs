@@ -0,0 +1,127 @@
+use super::{get_layer_columns, ClipError, LayerKind};
+
+/// Brief.
+///
+/// A row of the Layer table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerRecord {
+  pub(super) pw_id: u64,
+  /// main_id of the layer
+  pub main_id: u64,
+  /// layer name
+  pub name: String,
+  /// `LayerType`
+  pub layer_type: u64,
+  /// whether the layer is a folder
+  pub folder: bool,
+  /// kind of the layer
+  pub kind: LayerKind,
+  /// main_id of the layer above this layer in the same folder. 0 if this is the top.
+  pub next_index: u64,
+  /// main_id of the bottom layer in the folder. 0 if this is not a folder or the folder is empty.
+  pub first_child_index: u64,
+}
+
+/// Brief.
+///
+/// Layers of a clip file read from the database one by one.
+///
+/// The rows are not sorted and the tree is not checked,
+/// so that files with many layers can be scanned without loading all layers into memory.
+///
+/// ```no_run
+/// use renamelayer::clip::{self, ClipDocument};
+/// let doc = ClipDocument::open("in.clip")?;
+/// let mut layers = doc.layers()?;
+/// for layer in layers.iter()? {
+///   let layer = layer?;
+///   println!("{} {}", layer.main_id, layer.name);
+/// }
+/// # Ok::<(), clip::ClipError>(())
+/// ```
+pub struct Layers<'c> {
+  stmt: rusqlite::Statement<'c>,
+}
+
+/// Iterator of `Layers::iter`.
+pub struct LayerIter<'s> {
+  rows: rusqlite::Rows<'s>,
+  done: bool,
+}
+
+impl<'c> Layers<'c> {
+  /// Prepare the query of the Layer table.
+  pub(super) fn new(conn: &'c rusqlite::Connection) -> Result<Layers<'c>, ClipError> {
+    let columns = get_layer_columns(conn)?;
+    let vector_type = if columns.iter().any(|x| x == "VectorNormalType") {
+      "VectorNormalType"
+    } else {
+      "NULL"
+    };
+    let sql = format!("SELECT _PW_ID, MainId, LayerName, LayerType, LayerFolder, LayerNextIndex, LayerFIrstChildIndex, {} FROM Layer", vector_type);
+    return match conn.prepare(&sql) {
+      Ok(stmt) => Ok(Layers { stmt }),
+      Err(_) => Err(ClipError::SQLError),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Iterate all layers (including the root folder) in the order of the database.
+  pub fn iter(&mut self) -> Result<LayerIter<'_>, ClipError> {
+    return match self.stmt.query([]) {
+      Ok(rows) => Ok(LayerIter { rows, done: false }),
+      Err(_) => Err(ClipError::SQLError),
+    };
+  }
+}
+
+impl<'s> Iterator for LayerIter<'s> {
+  type Item = Result<LayerRecord, ClipError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let row = match self.rows.next() {
+      Ok(Some(x)) => x,
+      Ok(None) => {
+        self.done = true;
+        return None;
+      }
+      Err(_) => {
+        self.done = true;
+        return Some(Err(ClipError::SQLError));
+      }
+    };
+    return match read_row(row) {
+      Ok(x) => Some(Ok(x)),
+      Err(_) => {
+        self.done = true;
+        Some(Err(ClipError::SQLError))
+      }
+    };
+  }
+}
+
+fn read_row(row: &rusqlite::Row) -> rusqlite::Result<LayerRecord> {
+  let layer_type: u64 = row.get(3)?;
+  let vector_type: Option<u64> = row.get(7)?;
+  let folder: u64 = row.get(4)?;
+  return Ok(LayerRecord {
+    pw_id: row.get(0)?,
+    main_id: row.get(1)?,
+    name: row.get(2)?,
+    layer_type,
+    folder: folder != 0,
+    kind: if layer_type == 1 {
+      LayerKind::Raster
+    } else if layer_type == 0 && vector_type.is_some() {
+      LayerKind::Vector
+    } else {
+      LayerKind::Other
+    },
+    next_index: row.get(5)?,
+    first_child_index: row.get(6)?,
+  });
+}
@@ -0,0 +1,128 @@
+use super::{list_layers_in_sqlite, ClipError, LayerKind};
+use regex::Regex;
+use std::path::Path;
+
+/// Brief.
+///
+/// Naming convention of layers checked by `lint_layers_in_sqlite`.
+///
+/// A layer name must match the regex of its parent folder (`add_folder_rule`) if there is one,
+/// or else the regex of its kind (`add_kind_rule`). Layers without a rule are not checked.
+#[derive(Debug, Clone, Default)]
+pub struct NamingConvention {
+  /// `(kind, regex)`. `None` is for folders.
+  kind_rules: Vec<(Option<LayerKind>, Regex)>,
+  /// `(folder name, regex)` for the layers and folders directly in the folder
+  folder_rules: Vec<(String, Regex)>,
+}
+
+impl NamingConvention {
+  /// A convention without rules.
+  pub fn new() -> NamingConvention {
+    return NamingConvention::default();
+  }
+
+  /// Brief.
+  ///
+  /// Parse a per kind rule (`KIND=REGEX`, `KIND` is `raster`, `vector`, `other` or `folder`) and add it.
+  /// A later rule of the same kind replaces the former.
+  pub fn add_kind_rule(&mut self, rule: &str) -> Result<(), ClipError> {
+    let (kind, regex) = split_rule(rule)?;
+    let kind = match kind.trim() {
+      "folder" => None,
+      x => match x.parse() {
+        Ok(k) => Some(k),
+        Err(_) => return Err(ClipError::InvalidRule(rule.to_string())),
+      },
+    };
+    self.kind_rules.retain(|(k, _)| *k != kind);
+    self.kind_rules.push((kind, regex));
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// Parse a per folder rule (`FOLDER=REGEX`) for the layers and folders directly in the folders named `FOLDER`,
+  /// and add it. A later rule of the same folder replaces the former.
+  pub fn add_folder_rule(&mut self, rule: &str) -> Result<(), ClipError> {
+    let (folder, regex) = split_rule(rule)?;
+    self.folder_rules.retain(|(f, _)| f != folder);
+    self.folder_rules.push((folder.to_string(), regex));
+    return Ok(());
+  }
+
+  /// Whether no rule is given.
+  pub fn is_empty(&self) -> bool {
+    return self.kind_rules.is_empty() && self.folder_rules.is_empty();
+  }
+
+  /// The regex for a layer in the folder `parent` (`None` at the top level).
+  fn regex_for(&self, parent: Option<&str>, kind: Option<LayerKind>) -> Option<&Regex> {
+    if let Some(parent) = parent {
+      if let Some((_, re)) = self.folder_rules.iter().find(|(f, _)| f == parent) {
+        return Some(re);
+      }
+    }
+    return self
+      .kind_rules
+      .iter()
+      .find(|(k, _)| *k == kind)
+      .map(|(_, re)| re);
+  }
+}
+
+/// `NAME=REGEX` → `(NAME, Regex)`
+fn split_rule(rule: &str) -> Result<(&str, Regex), ClipError> {
+  let (name, regex) = match rule.find('=') {
+    Some(i) => (&rule[..i], &rule[i + 1..]),
+    None => return Err(ClipError::InvalidRule(rule.to_string())),
+  };
+  return match Regex::new(regex) {
+    Ok(x) => Ok((name, x)),
+    Err(_) => Err(ClipError::InvalidRule(rule.to_string())),
+  };
+}
+
+/// Brief.
+///
+/// A layer whose name violates the naming convention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+  /// main_id of the layer
+  pub main_id: u64,
+  /// names of the folders and the layer joined with `/`
+  pub path: String,
+  /// the regex the name does not match
+  pub expected: String,
+}
+
+/// Brief
+///
+/// Find the layers and folders whose names violate the naming convention.
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `convention`: naming convention
+///
+/// Return.
+///
+/// violations from the top of the layer palette
+pub fn lint_layers_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  convention: &NamingConvention,
+) -> Result<Vec<LintIssue>, ClipError> {
+  let layers = list_layers_in_sqlite(sqlfile)?;
+  let mut issues: Vec<LintIssue> = Vec::new();
+  for l in layers.iter() {
+    let folder = l.path.len().checked_sub(2).map(|i| l.path[i].as_str());
+    if let Some(re) = convention.regex_for(folder, l.kind) {
+      if !re.is_match(&l.name) {
+        issues.push(LintIssue {
+          main_id: l.main_id,
+          path: l.path_string(),
+          expected: re.as_str().to_string(),
+        });
+      }
+    }
+  }
+  return Ok(issues);
+}
@@ -0,0 +1,102 @@
+use regex::Regex;
+
+/// Brief.
+///
+/// Default layer name of a display language of Clip Studio Paint ("レイヤー 1", "Layer 1", ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerNameLocale {
+  /// language tag (e.g. `ja`, `zh-Hans`)
+  pub language: &'static str,
+  /// word of default layer names before the number
+  pub layer_word: &'static str,
+}
+
+/// known default layer names. The first one is used when the counts are the same.
+pub const LAYER_NAME_LOCALES: &[LayerNameLocale] = &[
+  LayerNameLocale {
+    language: "ja",
+    layer_word: "レイヤー",
+  },
+  LayerNameLocale {
+    language: "en",
+    layer_word: "Layer",
+  },
+  LayerNameLocale {
+    language: "zh-Hans",
+    layer_word: "图层",
+  },
+  LayerNameLocale {
+    language: "zh-Hant",
+    layer_word: "圖層",
+  },
+  LayerNameLocale {
+    language: "ko",
+    layer_word: "레이어",
+  },
+  LayerNameLocale {
+    language: "fr",
+    layer_word: "Calque",
+  },
+  LayerNameLocale {
+    language: "de",
+    layer_word: "Ebene",
+  },
+  LayerNameLocale {
+    language: "es",
+    layer_word: "Capa",
+  },
+];
+
+impl LayerNameLocale {
+  /// Find a locale by the language tag.
+  pub fn find(language: &str) -> Option<&'static LayerNameLocale> {
+    return LAYER_NAME_LOCALES.iter().find(|x| x.language == language);
+  }
+
+  /// Brief.
+  ///
+  /// Rename pattern of this language in the form of `DEFAULT_PATTERN` (e.g. `Layer \d+`).
+  pub fn pattern(&self) -> Regex {
+    return Regex::new(&format!(r"{} \d+", regex::escape(self.layer_word))).unwrap();
+  }
+
+  /// Whether the name is a default layer name of this language ("Layer 3", "Layer3").
+  fn is_default_name(&self, name: &str) -> bool {
+    let rest = match name.trim().strip_prefix(self.layer_word) {
+      Some(x) => x.trim_start(),
+      None => return false,
+    };
+    return !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit());
+  }
+}
+
+/// Brief.
+///
+/// Detect the display language of Clip Studio Paint from default layer names
+/// ("Layer 1", "图层 2", ...).
+///
+/// * `names`: layer names
+///
+/// Return.
+///
+/// the language with the most default names. `None` if no name is a default name.
+pub fn detect_layer_name_locale<'a, I: IntoIterator<Item = &'a str>>(
+  names: I,
+) -> Option<&'static LayerNameLocale> {
+  let mut counts = vec![0usize; LAYER_NAME_LOCALES.len()];
+  for name in names {
+    if let Some(i) = LAYER_NAME_LOCALES
+      .iter()
+      .position(|x| x.is_default_name(name))
+    {
+      counts[i] += 1;
+    }
+  }
+  let mut best: Option<usize> = None;
+  for (i, n) in counts.iter().enumerate() {
+    if *n != 0 && best.map_or(true, |b| counts[b] < *n) {
+      best = Some(i);
+    }
+  }
+  return best.map(|i| &LAYER_NAME_LOCALES[i]);
+}
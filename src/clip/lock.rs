@@ -0,0 +1,83 @@
+use super::{collect_descendants, get_layer_roots, ClipError, ClipLayer};
+use std::path::Path;
+
+/// `LayerLock` bit for "lock layer".
+pub(super) const LAYER_LOCK_BIT: u64 = 1;
+
+/// Brief
+///
+/// Lock or unlock layers.
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `folders`: names of the target folders. The folders themselves are (un)locked.
+/// * `all`: (un)lock every layer in the target folders too. If `folders` is empty, (un)lock every layer of the file.
+/// * `lock`: `true` to lock, `false` to unlock
+///
+/// Only the "lock layer" bit of `LayerLock` is changed, other lock states (e.g. transparent pixels) are kept.
+///
+/// Return.
+///
+/// number of target layers
+pub fn lock_layers_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  folders: &[&str],
+  all: bool,
+  lock: bool,
+) -> Result<usize, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let roots = get_layer_roots(&conn, &mut v)?;
+
+  let mut targets: Vec<u64> = Vec::new();
+  if folders.is_empty() {
+    if all {
+      for l in v.iter() {
+        if !roots.contains(&l.main_id) {
+          targets.push(l.main_id);
+        }
+      }
+    }
+  } else {
+    for (i, l) in v.iter().enumerate() {
+      if l.layer_folder == 0
+        || roots.contains(&l.main_id)
+        || !folders.contains(&l.layer_name.as_str())
+      {
+        continue;
+      }
+      targets.push(l.main_id);
+      if all {
+        collect_descendants(&v, i, &mut targets)?;
+      }
+    }
+  }
+  targets.sort_unstable();
+  targets.dedup();
+
+  for main_id in targets.iter() {
+    set_layer_lock(&conn, *main_id, lock)?;
+  }
+  return Ok(targets.len());
+}
+
+/// Brief
+///
+/// update the lock bit of the layer
+///
+/// * `conn` : sqlite3
+/// * `main_id` : layer main_id
+/// * `lock` : new lock state
+fn set_layer_lock(conn: &rusqlite::Connection, main_id: u64, lock: bool) -> Result<(), ClipError> {
+  let sql = if lock {
+    "UPDATE Layer SET LayerLock = (IFNULL(LayerLock, 0) | $1) WHERE MainId = $2"
+  } else {
+    "UPDATE Layer SET LayerLock = (IFNULL(LayerLock, 0) & ~$1) WHERE MainId = $2"
+  };
+  if let Err(_) = conn.execute(sql, rusqlite::params![LAYER_LOCK_BIT, main_id]) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
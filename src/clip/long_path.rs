@@ -0,0 +1,53 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// Paths of this length (in bytes) or longer get the extended-length prefix.
+/// Windows limits paths to `MAX_PATH` (260) characters, and directories to 248 characters.
+const LONG_PATH_THRESHOLD: usize = 248;
+
+/// Brief.
+///
+/// Path which can be opened and created beyond `MAX_PATH` on Windows.
+///
+/// Long paths are made absolute and given the extended-length prefix
+/// (`\\?\C:\...`, `\\?\UNC\server\share\...`), which Windows accepts regardless of the long path setting.
+/// Short paths, paths with a prefix already, and all paths on the other platforms are returned as they are.
+///
+/// * `path`: file path given by the user
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+  if !cfg!(windows) {
+    return Cow::Borrowed(path);
+  }
+  // `absolute` resolves `.` and `..`, which are not resolved after the prefix
+  let absolute = match std::path::absolute(path) {
+    Ok(x) => x,
+    Err(_) => return Cow::Borrowed(path),
+  };
+  if absolute.as_os_str().len() < LONG_PATH_THRESHOLD {
+    return Cow::Borrowed(path);
+  }
+  let mut components = absolute.components();
+  let mut extended = match components.next() {
+    Some(Component::Prefix(p)) => match p.kind() {
+      Prefix::Disk(_) => {
+        let mut x = OsString::from(r"\\?\");
+        x.push(absolute.as_os_str());
+        return Cow::Owned(PathBuf::from(x));
+      }
+      Prefix::UNC(server, share) => {
+        let mut x = OsString::from(r"\\?\UNC\");
+        x.push(server);
+        x.push(r"\");
+        x.push(share);
+        x
+      }
+      // `\\?\...`, `\\.\...`
+      _ => return Cow::Borrowed(path),
+    },
+    _ => return Cow::Borrowed(path),
+  };
+  // `\dir\file` after `\\server\share`
+  extended.push(components.as_path().as_os_str());
+  return Cow::Owned(PathBuf::from(extended));
+}
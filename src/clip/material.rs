@@ -0,0 +1,155 @@
+use super::ClipError;
+use std::collections::HashMap;
+
+/// Brief
+///
+/// Collect the names of the materials referenced by layers.
+///
+/// Material layers (image materials, 3D materials, ...) are assumed to keep their source in tables
+/// whose names contain "Material" with a `LayerId` column. This is not a known schema of Clip Studio Paint:
+/// the sample files have no such table. Only a column declared as `TEXT` whose name ends with
+/// `Name`, `File` or `Path` is used (the first one), so that other data is never taken as a name.
+/// Directories and extensions are removed from file paths.
+///
+/// Return.
+///
+/// material name for each layer main_id
+pub(super) fn get_material_names(
+  conn: &rusqlite::Connection,
+) -> Result<HashMap<u64, String>, ClipError> {
+  let mut names: HashMap<u64, String> = HashMap::new();
+  let tables: Vec<String> = {
+    let mut stmt = match conn
+      .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE '%Material%'")
+    {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| row.get::<_, String>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    rows.filter_map(|x| x.ok()).collect()
+  };
+
+  for table in tables.iter() {
+    let columns = get_columns(conn, table)?;
+    if !columns.iter().any(|x| x == "LayerId") {
+      continue;
+    }
+    let column = match get_text_columns(conn, table)?
+      .into_iter()
+      .find(|x| x.ends_with("Name") || x.ends_with("File") || x.ends_with("Path"))
+    {
+      Some(x) => x,
+      None => continue,
+    };
+    let mut stmt = match conn.prepare(&format!(
+      "SELECT LayerId, \"{}\" FROM \"{}\"",
+      column, table
+    )) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| {
+      Ok((
+        row.get::<_, Option<u64>>(0)?,
+        row.get::<_, Option<String>>(1)?,
+      ))
+    }) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    for r in rows {
+      if let Ok((Some(id), Some(name))) = r {
+        let name = material_base_name(&name);
+        if !name.is_empty() {
+          names.entry(id).or_insert_with(|| name.to_string());
+        }
+      }
+    }
+  }
+  return Ok(names);
+}
+
+/// Column names of the table.
+pub(super) fn get_columns(
+  conn: &rusqlite::Connection,
+  table: &str,
+) -> Result<Vec<String>, ClipError> {
+  let mut stmt = match conn.prepare(&format!("PRAGMA table_info(\"{}\")", table)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| row.get::<_, String>(1)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return Ok(rows.filter_map(|x| x.ok()).collect());
+}
+
+/// Names of the columns of the table declared as `TEXT`.
+pub(super) fn get_text_columns(
+  conn: &rusqlite::Connection,
+  table: &str,
+) -> Result<Vec<String>, ClipError> {
+  let mut stmt = match conn.prepare(&format!("PRAGMA table_info(\"{}\")", table)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return Ok(
+    rows
+      .filter_map(|x| x.ok())
+      .filter(|(_, t)| t.eq_ignore_ascii_case("TEXT"))
+      .map(|(name, _)| name)
+      .collect(),
+  );
+}
+
+/// "C:\materials\wood.png" → "wood"
+fn material_base_name(name: &str) -> &str {
+  let name = name.trim();
+  let file = match name.rfind(['/', '\\']) {
+    Some(i) => &name[i + 1..],
+    None => name,
+  };
+  return match file.rfind('.') {
+    Some(i) if i > 0 => &file[..i],
+    _ => file,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn base_name() {
+    assert_eq!(material_base_name("C:\\materials\\wood.png"), "wood");
+    assert_eq!(material_base_name(" /a/b/stone.tar.gz "), "stone.tar");
+    assert_eq!(material_base_name(".hidden"), ".hidden");
+    assert_eq!(material_base_name("雲"), "雲");
+  }
+
+  #[test]
+  fn material_names_from_text_columns() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "CREATE TABLE ImageMaterial (LayerId INTEGER, MaterialName BLOB, SourcePath TEXT);
+         INSERT INTO ImageMaterial VALUES (3, x'00', 'C:\\m\\wood.png'), (4, x'00', NULL);
+         CREATE TABLE MaterialCache (CacheName TEXT);
+         INSERT INTO MaterialCache VALUES ('cache');",
+      )
+      .unwrap();
+    let names = get_material_names(&conn).unwrap();
+    assert_eq!(names.len(), 1);
+    assert_eq!(names[&3], "wood");
+  }
+}
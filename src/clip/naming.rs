@@ -0,0 +1,162 @@
+use super::{
+  find_layer_index, get_children, get_layer_roots, ClipError, ClipLayer, LayerKind, RenamePlan,
+  RenamedLayer, SpecialLayer,
+};
+use std::path::Path;
+
+/// Brief.
+///
+/// A layer or folder given to the naming callback (`ClipDocument::plan_with`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerView {
+  /// main_id of the layer
+  pub main_id: u64,
+  /// current layer name
+  pub name: String,
+  /// whether the layer is a folder
+  pub folder: bool,
+  /// kind of the layer. `None` for folders.
+  pub kind: Option<LayerKind>,
+  /// special layer type. Special layers are given to the callback, too.
+  pub special: Option<SpecialLayer>,
+}
+
+/// Brief.
+///
+/// Where the layer is, given to the naming callback with `LayerView`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameContext {
+  /// main_id of the folder containing the layer (the root folder for top level layers)
+  pub parent_id: u64,
+  /// current name of the folder containing the layer. Empty for top level layers.
+  pub parent_name: String,
+  /// new name of the folder containing the layer if the callback renamed it
+  pub parent_new_name: Option<String>,
+  /// names of the folders containing the layer from the top level (the root folder is not included)
+  pub folders: Vec<String>,
+  /// position in the folder from the bottom, from 1
+  pub position: usize,
+  /// number of layers and folders in the folder
+  pub siblings: usize,
+  /// canvas of the layer, from 1 (see `RenameOptions::canvas`)
+  pub canvas: usize,
+}
+
+impl RenameContext {
+  /// Depth in the tree. Top level layers are 0.
+  pub fn depth(&self) -> usize {
+    return self.folders.len();
+  }
+}
+
+/// Brief
+///
+/// Decide new layer names with a callback instead of the pattern and the templates.
+///
+/// Layers and folders of all canvases are given to the callback from the top of the layer palette,
+/// a folder before its contents. `Some(name)` renames the layer, and `None` keeps the name.
+/// Protection (`RenameOptions::protect_prefix`) and special layers are left to the callback.
+///
+/// * `conn`: sqlite3. It is only read.
+/// * `f`: callback returning the new name
+pub(super) fn plan_with_in<F>(
+  conn: &rusqlite::Connection,
+  mut f: F,
+) -> Result<RenamePlan, ClipError>
+where
+  F: FnMut(&LayerView, &RenameContext) -> Option<String>,
+{
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let roots = get_layer_roots(conn, &mut v)?;
+  let mut plan = RenamePlan {
+    total_layers: v.iter().filter(|l| l.layer_folder == 0).count(),
+    ..RenamePlan::default()
+  };
+  for (i, root) in roots.into_iter().enumerate() {
+    let index = match find_layer_index(&v, root) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    let context = RenameContext {
+      parent_id: root,
+      parent_name: String::new(),
+      parent_new_name: None,
+      folders: Vec::new(),
+      position: 0,
+      siblings: 0,
+      canvas: i + 1,
+    };
+    name_folder(&v, index, context, &mut f, &mut plan)?;
+  }
+  return Ok(plan);
+}
+
+/// Brief
+///
+/// `plan_with_in` with a sqlite3 file.
+///
+/// * `sqlfile`: sqlite3 file path. It is only read.
+/// * `f`: callback returning the new name
+pub fn plan_with_in_sqlite<P, F>(sqlfile: P, f: F) -> Result<RenamePlan, ClipError>
+where
+  P: AsRef<Path>,
+  F: FnMut(&LayerView, &RenameContext) -> Option<String>,
+{
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return plan_with_in(&conn, f);
+}
+
+/// * `index`: folder index of `v`
+/// * `context`: context of the contents of the folder (`position` and `siblings` are set for each layer)
+fn name_folder<F>(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  mut context: RenameContext,
+  f: &mut F,
+  plan: &mut RenamePlan,
+) -> Result<(), ClipError>
+where
+  F: FnMut(&LayerView, &RenameContext) -> Option<String>,
+{
+  let children = get_children(v, index)?;
+  context.siblings = children.len();
+  // from the top, as shown in the layer palette
+  for (position, ci) in children.into_iter().enumerate().rev() {
+    let c = &v[ci];
+    let folder = c.layer_folder != 0;
+    let view = LayerView {
+      main_id: c.main_id,
+      name: c.layer_name.clone(),
+      folder,
+      kind: if folder { None } else { Some(c.kind) },
+      special: c.special,
+    };
+    context.position = position + 1;
+    let new_name = f(&view, &context).filter(|x| *x != c.layer_name);
+    if let Some(name) = &new_name {
+      plan.renames.push(RenamedLayer {
+        main_id: c.main_id,
+        old_name: c.layer_name.clone(),
+        new_name: name.clone(),
+      });
+    }
+    if folder {
+      let mut folders = context.folders.clone();
+      folders.push(c.layer_name.clone());
+      let inner = RenameContext {
+        parent_id: c.main_id,
+        parent_name: c.layer_name.clone(),
+        parent_new_name: new_name,
+        folders,
+        position: 0,
+        siblings: 0,
+        canvas: context.canvas,
+      };
+      name_folder(v, ci, inner, f, plan)?;
+    }
+  }
+  return Ok(());
+}
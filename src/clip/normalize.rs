@@ -0,0 +1,97 @@
+/// half width katakana from U+FF61 to U+FF9D
+const HALF_WIDTH_KATAKANA: &str =
+  "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+/// half width voiced sound mark (ﾞ)
+const HALF_WIDTH_VOICED: char = '\u{FF9E}';
+/// half width semi-voiced sound mark (ﾟ)
+const HALF_WIDTH_SEMI_VOICED: char = '\u{FF9F}';
+
+/// Brief
+///
+/// Normalize the character width of a layer name.
+///
+/// * full width ASCII (`Ａ`, `１`, ...) and the ideographic space → ASCII
+/// * half width katakana (`ﾚｲﾔｰ`, `ｶﾞ`, ...) → full width katakana
+pub(super) fn normalize_width(name: &str) -> String {
+  let mut s = String::with_capacity(name.len());
+  for c in name.chars() {
+    let code = c as u32;
+    if (0xFF01..=0xFF5E).contains(&code) {
+      s.push(char::from_u32(code - 0xFEE0).unwrap());
+    } else if c == '\u{3000}' {
+      s.push(' ');
+    } else if (0xFF61..=0xFF9D).contains(&code) {
+      s.push(
+        HALF_WIDTH_KATAKANA
+          .chars()
+          .nth((code - 0xFF61) as usize)
+          .unwrap(),
+      );
+    } else if c == HALF_WIDTH_VOICED || c == HALF_WIDTH_SEMI_VOICED {
+      let voiced = c == HALF_WIDTH_VOICED;
+      let prev = s.pop();
+      match prev.and_then(|x| combine_sound_mark(x, voiced)) {
+        Some(x) => s.push(x),
+        None => {
+          // not combinable: keep the mark as a full width sound mark
+          s.extend(prev);
+          s.push(if voiced { '゛' } else { '゜' });
+        }
+      }
+    } else {
+      s.push(c);
+    }
+  }
+  return s;
+}
+
+/// `カ` + `ﾞ` → `ガ`, `ハ` + `ﾟ` → `パ`
+fn combine_sound_mark(c: char, voiced: bool) -> Option<char> {
+  let code = c as u32;
+  if voiced {
+    if c == 'ウ' {
+      return Some('ヴ');
+    }
+    // カ..チ and ツ..ト are in pairs (カ ガ キ ギ ...)
+    if ((0x30AB..=0x30C1).contains(&code) && (code - 0x30AB) % 2 == 0)
+      || ((0x30C4..=0x30C8).contains(&code) && (code - 0x30C4) % 2 == 0)
+    {
+      return char::from_u32(code + 1);
+    }
+  }
+  // ハ..ホ are in triples (ハ バ パ ...)
+  if (0x30CF..=0x30DB).contains(&code) && (code - 0x30CF) % 3 == 0 {
+    return char::from_u32(code + if voiced { 1 } else { 2 });
+  }
+  return None;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn full_width_ascii() {
+    assert_eq!(normalize_width("レイヤー　１２"), "レイヤー 12");
+    assert_eq!(normalize_width("Ｌａｙｅｒ！"), "Layer!");
+  }
+
+  #[test]
+  fn half_width_katakana() {
+    assert_eq!(normalize_width("ﾚｲﾔｰ 1"), "レイヤー 1");
+    assert_eq!(normalize_width("ｶﾞｳﾞﾊﾟﾎﾞ"), "ガヴパボ");
+    assert_eq!(normalize_width("｢ｱ｣､｡"), "「ア」、。");
+  }
+
+  #[test]
+  fn sound_marks_not_combinable() {
+    assert_eq!(normalize_width("ｱﾞ"), "ア゛");
+    assert_eq!(normalize_width("ﾟ"), "゜");
+    assert_eq!(normalize_width("aﾞ"), "a゛");
+  }
+
+  #[test]
+  fn others_unchanged() {
+    assert_eq!(normalize_width("線画 ラフ abc"), "線画 ラフ abc");
+  }
+}
@@ -0,0 +1,63 @@
+/// marker of the old name in `OldNameStyle::Suffix` and `OldNameStyle::Prefix`
+const OLD_MARK: &str = "旧: ";
+/// marker of the old name in `OldNameStyle::Compact`
+const COMPACT_MARK: &str = "←";
+
+/// How the old name of a renamed layer is kept in the new name (`RenameOptions::keep_old_in_name`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OldNameStyle {
+  /// "背景 3 (旧: レイヤー 15)"
+  Suffix,
+  /// "(旧: レイヤー 15) 背景 3"
+  Prefix,
+  /// "背景 3 (←レイヤー 15)"
+  Compact,
+}
+
+impl OldNameStyle {
+  /// Parse `suffix`, `prefix` or `compact`.
+  pub fn parse(text: &str) -> Option<OldNameStyle> {
+    return match text {
+      "suffix" => Some(OldNameStyle::Suffix),
+      "prefix" => Some(OldNameStyle::Prefix),
+      "compact" => Some(OldNameStyle::Compact),
+      _ => None,
+    };
+  }
+
+  /// New name with the old name in this style.
+  pub fn format(&self, new_name: &str, old_name: &str) -> String {
+    return match self {
+      OldNameStyle::Suffix => format!("{} ({}{})", new_name, OLD_MARK, old_name),
+      OldNameStyle::Prefix => format!("({}{}) {}", OLD_MARK, old_name, new_name),
+      OldNameStyle::Compact => format!("{} ({}{})", new_name, COMPACT_MARK, old_name),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Split a name made by `format`.
+  ///
+  /// Return.
+  ///
+  /// `(new name, old name)`, or `None` if the name is not in this style.
+  pub fn split<'a>(&self, name: &'a str) -> Option<(&'a str, &'a str)> {
+    return match self {
+      OldNameStyle::Suffix | OldNameStyle::Compact => {
+        let mark = if *self == OldNameStyle::Suffix {
+          OLD_MARK
+        } else {
+          COMPACT_MARK
+        };
+        let body = name.strip_suffix(')')?;
+        let start = body.find(&format!(" ({}", mark))?;
+        Some((&body[..start], &body[start + 2 + mark.len()..]))
+      }
+      OldNameStyle::Prefix => {
+        let body = name.strip_prefix('(')?.strip_prefix(OLD_MARK)?;
+        let end = body.find(") ")?;
+        Some((&body[end + 2..], &body[..end]))
+      }
+    };
+  }
+}
@@ -0,0 +1,416 @@
+use super::{
+  detect_layer_name_locale, normalize, ClipError, CollisionPolicy, NameTemplate, NumberingOrder,
+  OldNameStyle, RenameRules, SpecialLayer,
+};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// default regex of layer names to be renamed
+pub const DEFAULT_PATTERN: &str = r"レイヤー \d+";
+/// default base name of top level layers
+pub const DEFAULT_ROOT_LAYER_NAME: &str = "ルートレイヤ ";
+
+/// How the pattern matches layer names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+  /// the pattern matches a part of the name (e.g. `レイヤー \d+` matches "背景レイヤー 3")
+  Search,
+  /// the pattern matches the whole name, as if it is written as `^(?:PATTERN)$`
+  Exact,
+}
+
+/// Brief.
+///
+/// Options of renaming.
+///
+/// ```no_run
+/// use renamelayer::clip::{self, RenameOptions};
+/// let options = RenameOptions::new()
+///   .pattern(regex::Regex::new(r"^Layer \d+$").unwrap())
+///   .template("{parent} {n}")?
+///   .include_folders(true);
+/// clip::create_layer_renamed_clip_file("in.clip", "out.clip", &options)?;
+/// # Ok::<(), clip::ClipError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RenameOptions {
+  /// regex given by `pattern`
+  pub(super) pattern_source: Regex,
+  /// regex used for matching: `pattern_source` with `match_mode` and `ignore_case`
+  pub(super) pattern: Regex,
+  pub(super) match_mode: MatchMode,
+  pub(super) root_layer_base_name: String,
+  pub(super) rules: RenameRules,
+  pub(super) allow_special: Vec<SpecialLayer>,
+  pub(super) continue_numbering: bool,
+  pub(super) include_folders: bool,
+  pub(super) global_counter: bool,
+  pub(super) count_all_layers: bool,
+  pub(super) renumber_folders: bool,
+  pub(super) frame_ranges: bool,
+  pub(super) keep_old_names: bool,
+  pub(super) vector_names: bool,
+  pub(super) old_name_style: Option<OldNameStyle>,
+  pub(super) ignore_case: bool,
+  pub(super) normalize_width: bool,
+  pub(super) detect_language: bool,
+  pub(super) on_collision: CollisionPolicy,
+  pub(super) clipping_names: bool,
+  pub(super) selection: Option<HashSet<u64>>,
+  pub(super) canvas: Option<usize>,
+}
+
+impl RenameOptions {
+  /// Default options: rename `レイヤー N` to `PARENT N` from the bottom layer.
+  pub fn new() -> RenameOptions {
+    return RenameOptions {
+      pattern_source: Regex::new(DEFAULT_PATTERN).unwrap(),
+      pattern: Regex::new(DEFAULT_PATTERN).unwrap(),
+      match_mode: MatchMode::Search,
+      root_layer_base_name: DEFAULT_ROOT_LAYER_NAME.to_string(),
+      rules: RenameRules::default(),
+      allow_special: Vec::new(),
+      continue_numbering: true,
+      include_folders: false,
+      global_counter: false,
+      count_all_layers: false,
+      renumber_folders: false,
+      frame_ranges: false,
+      keep_old_names: false,
+      vector_names: false,
+      old_name_style: None,
+      ignore_case: false,
+      normalize_width: false,
+      detect_language: false,
+      on_collision: CollisionPolicy::Overwrite,
+      clipping_names: false,
+      selection: None,
+      canvas: None,
+    };
+  }
+
+  /// Regex of layer names to be renamed. Disables `detect_language`.
+  pub fn pattern(mut self, pattern: Regex) -> RenameOptions {
+    self.pattern_source = pattern;
+    self.detect_language = false;
+    self.compile_pattern();
+    return self;
+  }
+
+  /// How the pattern matches layer names. The default is `MatchMode::Search`.
+  pub fn match_mode(mut self, mode: MatchMode) -> RenameOptions {
+    self.match_mode = mode;
+    self.compile_pattern();
+    return self;
+  }
+
+  /// Base name of top level layers. Top level layers are not renamed if empty.
+  pub fn root_layer_base_name(mut self, name: &str) -> RenameOptions {
+    self.root_layer_base_name = name.to_string();
+    return self;
+  }
+
+  /// Replace all rules deciding new layer names.
+  pub fn rules(mut self, rules: RenameRules) -> RenameOptions {
+    self.rules = rules;
+    return self;
+  }
+
+  /// Default template of new layer names (e.g. `{parent} {n}`).
+  pub fn template(mut self, template: &str) -> Result<RenameOptions, ClipError> {
+    self.rules.template = NameTemplate::parse(template)?;
+    return Ok(self);
+  }
+
+  /// Per kind rule (`KIND=TEMPLATE` or `KIND=skip`).
+  pub fn kind_rule(mut self, rule: &str) -> Result<RenameOptions, ClipError> {
+    self.rules.add_kind_rule(rule)?;
+    return Ok(self);
+  }
+
+  /// Numbering order in a folder.
+  pub fn order(mut self, order: NumberingOrder) -> RenameOptions {
+    self.rules.order = order;
+    return self;
+  }
+
+  /// Name material layers after their source material.
+  pub fn material_names(mut self, enable: bool) -> RenameOptions {
+    self.rules.use_material_names = enable;
+    return self;
+  }
+
+  /// Name layers with a color label after the color, e.g. `red1`, `red2`, `blue1`.
+  pub fn color_labels(mut self, enable: bool) -> RenameOptions {
+    self.rules.use_color_labels = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Rename layers and folders found in the dictionary to the replacements verbatim,
+  /// whether they match the pattern or not (e.g. "線画" → "Lineart").
+  /// Translated folder names are used as `{parent}` of the layers in them.
+  /// See `load_dictionary`.
+  pub fn dictionary(mut self, dictionary: HashMap<String, String>) -> RenameOptions {
+    self.rules.dictionary = dictionary;
+    return self;
+  }
+
+  /// Add one entry to the dictionary (`dictionary`), replacing the entry of the same name.
+  pub fn dictionary_entry(mut self, name: &str, replacement: &str) -> RenameOptions {
+    self
+      .rules
+      .dictionary
+      .insert(name.to_string(), replacement.to_string());
+    return self;
+  }
+
+  /// Layers whose names start with `prefix` are never renamed. Empty disables it.
+  pub fn protect_prefix(mut self, prefix: &str) -> RenameOptions {
+    self.rules.protect_prefix = prefix.to_string();
+    return self;
+  }
+
+  /// Allow renaming the special layer.
+  pub fn allow_special(mut self, special: SpecialLayer) -> RenameOptions {
+    if !self.allow_special.contains(&special) {
+      self.allow_special.push(special);
+    }
+    return self;
+  }
+
+  /// Start numbering after the largest number already used by names in the form of the templates.
+  pub fn continue_numbering(mut self, enable: bool) -> RenameOptions {
+    self.continue_numbering = enable;
+    return self;
+  }
+
+  /// Rename folders matching the pattern with the default template, too.
+  /// Folders are numbered together with the layers in the same folder.
+  pub fn include_folders(mut self, enable: bool) -> RenameOptions {
+    self.include_folders = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Name layers clipped to a layer below after the base layer, e.g. "肌 clip 1", "肌 clip 2"
+  /// for layers clipped to "肌", so that shading layers stay associated with their base by name.
+  /// The base name is the new name if the base layer is renamed.
+  /// Clipped layers are still renamed only if they match the pattern.
+  pub fn clipping_names(mut self, enable: bool) -> RenameOptions {
+    self.clipping_names = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Rename only the layers and folders of the main_ids (e.g. layers selected in Clip Studio Paint).
+  /// New names are decided as if all layers are renamed, and the others keep their names.
+  /// Renaming fails with `ClipError::LayerNotFound` if a main_id is not in the file.
+  pub fn selection(mut self, main_ids: HashSet<u64>) -> RenameOptions {
+    self.selection = Some(main_ids);
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Rename only the layers of the `n`th canvas (from 1, in the order of the Canvas table)
+  /// of a file with several canvases (e.g. a story file).
+  /// By default the layers of all canvases are renamed, each canvas with its own root folder.
+  /// Renaming fails with `ClipError::CanvasNotFound` if the file has less canvases.
+  pub fn canvas(mut self, n: usize) -> RenameOptions {
+    self.canvas = Some(n);
+    return self;
+  }
+
+  /// Number layers through the whole tree with one counter instead of starting from 1 in each folder.
+  pub fn global_counter(mut self, enable: bool) -> RenameOptions {
+    self.global_counter = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Count every layer and folder in a folder, not only renamed layers,
+  /// so that the numbers reflect the positions (e.g. "3" for the third layer from the bottom
+  /// even if the second layer keeps its name).
+  pub fn count_all_layers(mut self, enable: bool) -> RenameOptions {
+    self.count_all_layers = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Rename only top level folders matching the pattern with the default template,
+  /// leaving their contents untouched (e.g. "フォルダー 3" → "シーン 1").
+  /// The pattern and the template are not changed; see `DEFAULT_FOLDER_PATTERN` and `DEFAULT_SCENE_TEMPLATE`.
+  pub fn renumber_folders(mut self, enable: bool) -> RenameOptions {
+    self.renumber_folders = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Add the frame range on the timeline to the names of cels instead of renaming by the templates,
+  /// e.g. "a" → "a (1-8)". The pattern is not used.
+  pub fn frame_ranges(mut self, enable: bool) -> RenameOptions {
+    self.frame_ranges = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Add the old names of renamed layers to the layer comment (search keywords),
+  /// so that artists can find the layers by the old names in Clip Studio Paint.
+  /// Nothing is added if the file has no comment field (`ClipDocument::layer_comment_column`).
+  pub fn keep_old_names(mut self, enable: bool) -> RenameOptions {
+    self.keep_old_names = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Also update copies of the layer name in vector data tables (`vector::get_vector_name_columns`).
+  /// The tables are not part of a known schema, so this is off by default.
+  pub fn vector_names(mut self, enable: bool) -> RenameOptions {
+    self.vector_names = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Keep the old names of renamed layers in the new names, e.g. "背景 3 (旧: レイヤー 15)" with `OldNameStyle::Suffix`,
+  /// so that artists can still find the layers referred by the old names during a transition period.
+  /// Names in the style whose old names match the pattern are treated as already renamed.
+  /// Not used with `frame_ranges`.
+  pub fn keep_old_in_name(mut self, style: Option<OldNameStyle>) -> RenameOptions {
+    self.old_name_style = style;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// What to do when a new name equals the name of another layer in the same folder.
+  /// The default is `CollisionPolicy::Overwrite`, i.e. layers of the same name are made.
+  pub fn on_collision(mut self, policy: CollisionPolicy) -> RenameOptions {
+    self.on_collision = policy;
+    return self;
+  }
+
+  /// Match the pattern case insensitively ("layer 12" and "Layer 12").
+  pub fn ignore_case(mut self, enable: bool) -> RenameOptions {
+    self.ignore_case = enable;
+    self.compile_pattern();
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Match the pattern against names with normalized character width:
+  /// full width ASCII and spaces ("レイヤー　１２") are matched as ASCII ("レイヤー 12"),
+  /// half width katakana ("ﾚｲﾔｰ") as full width katakana ("レイヤー").
+  /// The pattern should be written in ASCII and full width katakana.
+  pub fn normalize_width(mut self, enable: bool) -> RenameOptions {
+    self.normalize_width = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Replace the pattern with the default layer names of the language detected from the file
+  /// (e.g. `Layer \d+` if most default names are "Layer N"), see `detect_layer_name_locale`.
+  /// The pattern is kept if no default layer name is found.
+  pub fn detect_language(mut self, enable: bool) -> RenameOptions {
+    self.detect_language = enable;
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Options used for a file with the layer names: the pattern of the detected language
+  /// if `detect_language` is enabled, otherwise these options as they are.
+  ///
+  /// * `names`: all layer names of the file
+  pub fn for_layer_names<'a, I: IntoIterator<Item = &'a str>>(
+    &self,
+    names: I,
+  ) -> Cow<'_, RenameOptions> {
+    if !self.detect_language {
+      return Cow::Borrowed(self);
+    }
+    return match detect_layer_name_locale(names) {
+      Some(locale) => Cow::Owned(self.clone().pattern(locale.pattern())),
+      None => Cow::Borrowed(self),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Whether the layer name matches the pattern (with the match mode and the normalization).
+  /// Names renamed with the old name kept (`keep_old_in_name`) do not match.
+  pub fn matches_pattern(&self, name: &str) -> bool {
+    if let Some((_, old)) = self.old_name_style.and_then(|x| x.split(name)) {
+      if self.matches_name(old) {
+        return false;
+      }
+    }
+    return self.matches_name(name);
+  }
+
+  /// Brief.
+  ///
+  /// New name part of a name renamed with the old name kept (`keep_old_in_name`),
+  /// or the name as it is.
+  pub(super) fn without_old_name<'a>(&self, name: &'a str) -> &'a str {
+    return match self.old_name_style.and_then(|x| x.split(name)) {
+      Some((new, old)) if self.matches_name(old) => new,
+      _ => name,
+    };
+  }
+
+  /// Whether the name matches the pattern (with the match mode and the normalization).
+  fn matches_name(&self, name: &str) -> bool {
+    if self.normalize_width {
+      return self.pattern.is_match(&normalize::normalize_width(name));
+    }
+    return self.pattern.is_match(name);
+  }
+
+  /// Whether the layer name matches the pattern and is not protected.
+  pub fn need_rename(&self, name: &str) -> bool {
+    return self.matches_pattern(name) && !self.rules.is_protected(name);
+  }
+
+  /// Whether a layer keeps its name regardless of the rules: protected, or special and not allowed.
+  ///
+  /// * `name`: layer name
+  /// * `special`: kind of the special layer (`LayerInfo::special`)
+  pub fn is_kept(&self, name: &str, special: Option<SpecialLayer>) -> bool {
+    return self.rules.is_protected(name)
+      || special.is_some_and(|x| !self.allow_special.contains(&x));
+  }
+
+  /// Build the regex used for matching from the given pattern and flags.
+  fn compile_pattern(&mut self) {
+    let mut source = self.pattern_source.as_str().to_string();
+    if self.match_mode == MatchMode::Exact {
+      source = format!("^(?:{})$", source);
+    }
+    if self.ignore_case {
+      source = format!("(?i){}", source);
+    }
+    self.pattern = match Regex::new(&source) {
+      Ok(x) => x,
+      Err(_) => self.pattern_source.clone(),
+    };
+  }
+}
+
+impl Default for RenameOptions {
+  fn default() -> Self {
+    return RenameOptions::new();
+  }
+}
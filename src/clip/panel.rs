@@ -0,0 +1,282 @@
+use super::{
+  collision, dictionary::split_csv_line, find_layer_index, get_children, get_layer_roots,
+  ClipError, ClipLayer, CollisionPolicy, RenamePlan, RenameReport, RenamedLayer, Warning,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Brief.
+///
+/// A panel of a comic page: a named rectangle on the canvas in pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panel {
+  /// layer name given to the layers in the panel
+  pub name: String,
+  /// left edge
+  pub x: i64,
+  /// top edge
+  pub y: i64,
+  pub width: i64,
+  pub height: i64,
+}
+
+impl Panel {
+  /// Whether the point is in the panel. The right and bottom edges are not included.
+  pub fn contains(&self, x: i64, y: i64) -> bool {
+    return self.x <= x && x < self.x + self.width && self.y <= y && y < self.y + self.height;
+  }
+}
+
+/// Brief.
+///
+/// Read panel definitions from a CSV file (`parse_panels`).
+///
+/// * `path`: CSV file path
+pub fn load_panels<P: AsRef<Path>>(path: P) -> Result<Vec<Panel>, ClipError> {
+  let text = match std::fs::read_to_string(path) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileReadError),
+  };
+  return parse_panels(&text);
+}
+
+/// Brief.
+///
+/// Parse panel definitions: one `name,x,y,width,height` per line (e.g. `コマ1,0,0,1200,800`).
+///
+/// Coordinates are pixels of the canvas from the upper left corner. Fields may be quoted as in `parse_dictionary`.
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// * `text`: CSV text
+///
+/// Return.
+///
+/// panels in the order of the lines
+pub fn parse_panels(text: &str) -> Result<Vec<Panel>, ClipError> {
+  let mut panels: Vec<Panel> = Vec::new();
+  let text = text.trim_start_matches('\u{feff}');
+  for (i, line) in text.lines().enumerate() {
+    if line.trim().is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let invalid = || ClipError::InvalidPanels(format!("line {}: {}", i + 1, line));
+    let fields = match split_csv_line(line) {
+      Some(x) => x,
+      None => return Err(invalid()),
+    };
+    if fields.len() != 5 || fields[0].is_empty() {
+      return Err(invalid());
+    }
+    let mut numbers = [0i64; 4];
+    for (n, f) in numbers.iter_mut().zip(fields[1..].iter()) {
+      *n = match f.parse() {
+        Ok(x) => x,
+        Err(_) => return Err(invalid()),
+      };
+    }
+    let [x, y, width, height] = numbers;
+    if width <= 0 || height <= 0 {
+      return Err(invalid());
+    }
+    panels.push(Panel {
+      name: fields[0].clone(),
+      x,
+      y,
+      width,
+      height,
+    });
+  }
+  return Ok(panels);
+}
+
+/// Brief
+///
+/// Name each layer after the panel containing it, e.g. for comic pages whose panels are drawn on their own layers.
+///
+/// The position of a layer is its offset (`LayerOffsetX`, `LayerOffsetY` of the Layer table).
+/// If panels overlap, the earlier panel is used. Several layers of a panel in one folder
+/// are named "コマ1", "コマ1 (2)", ... from the bottom, and names used by other layers in the folder
+/// get a number as `CollisionPolicy::Suffix` does.
+/// Folders, special layers and layers in no panel (`Warning::NoPanel`) keep their names.
+///
+/// * `conn`: sqlite3. It is only read.
+/// * `panels`: panel definitions (`parse_panels`)
+pub(super) fn plan_panels_in(
+  conn: &rusqlite::Connection,
+  panels: &[Panel],
+) -> Result<RenamePlan, ClipError> {
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let roots = get_layer_roots(conn, &mut v)?;
+  let offsets = get_layer_offsets(conn)?;
+  let mut report = RenameReport {
+    total_layers: v.iter().filter(|l| l.layer_folder == 0).count(),
+    ..RenameReport::default()
+  };
+  for root in roots {
+    let index = match find_layer_index(&v, root) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    name_folder(&v, index, panels, &offsets, &mut report)?;
+  }
+  collision::resolve_collisions(&v, &mut report, CollisionPolicy::Suffix)?;
+  return Ok(RenamePlan::from_report(report, false, false));
+}
+
+/// Brief
+///
+/// `plan_panels_in` with a sqlite3 file.
+///
+/// * `sqlfile`: sqlite3 file path. It is only read.
+/// * `panels`: panel definitions (`parse_panels`)
+pub fn plan_panels_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  panels: &[Panel],
+) -> Result<RenamePlan, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return plan_panels_in(&conn, panels);
+}
+
+/// offset of each layer (main_id → (x, y))
+fn get_layer_offsets(conn: &rusqlite::Connection) -> Result<HashMap<u64, (i64, i64)>, ClipError> {
+  let mut stmt = match conn.prepare("SELECT MainId, LayerOffsetX, LayerOffsetY FROM Layer") {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((
+      row.get::<_, u64>(0)?,
+      row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+      row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+    ))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut offsets: HashMap<u64, (i64, i64)> = HashMap::new();
+  for r in rows {
+    match r {
+      Ok((id, x, y)) => offsets.insert(id, (x, y)),
+      Err(_) => return Err(ClipError::SQLError),
+    };
+  }
+  return Ok(offsets);
+}
+
+/// * `index`: folder index of `v`
+fn name_folder(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  panels: &[Panel],
+  offsets: &HashMap<u64, (i64, i64)>,
+  report: &mut RenameReport,
+) -> Result<(), ClipError> {
+  // layers of each panel in this folder so far
+  let mut counts: HashMap<&str, usize> = HashMap::new();
+  // from the bottom
+  for ci in get_children(v, index)? {
+    let c = &v[ci];
+    if c.layer_folder != 0 {
+      name_folder(v, ci, panels, offsets, report)?;
+      continue;
+    }
+    if c.special.is_some() {
+      continue;
+    }
+    let (x, y) = offsets.get(&c.main_id).cloned().unwrap_or((0, 0));
+    let panel = match panels.iter().find(|p| p.contains(x, y)) {
+      Some(x) => x,
+      None => {
+        report.warnings.push(Warning::NoPanel {
+          main_id: c.main_id,
+          name: c.layer_name.clone(),
+        });
+        continue;
+      }
+    };
+    let count = counts.entry(&panel.name).or_insert(0);
+    *count += 1;
+    let new_name = match *count {
+      1 => panel.name.clone(),
+      n => format!("{} ({})", panel.name, n),
+    };
+    if new_name != c.layer_name {
+      report.renamed.push(RenamedLayer {
+        main_id: c.main_id,
+        old_name: c.layer_name.clone(),
+        new_name,
+      });
+    }
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn panels() {
+    let panels = parse_panels(
+      "# name,x,y,width,height\nコマ1,0,0,1200,800\n\n\"コマ,2\", 0, 800, 1200, 400\n",
+    )
+    .unwrap();
+    assert_eq!(
+      panels,
+      vec![
+        Panel {
+          name: "コマ1".to_string(),
+          x: 0,
+          y: 0,
+          width: 1200,
+          height: 800,
+        },
+        Panel {
+          name: "コマ,2".to_string(),
+          x: 0,
+          y: 800,
+          width: 1200,
+          height: 400,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn invalid_panels() {
+    for text in [
+      "コマ1,0,0,1200",
+      "コマ1,0,0,1200,800,1",
+      ",0,0,1200,800",
+      "コマ1,0,0,a,800",
+      "コマ1,0,0,0,800",
+      "コマ1,0,0,1200,-1",
+    ]
+    .iter()
+    {
+      match parse_panels(text) {
+        Err(ClipError::InvalidPanels(x)) => assert!(x.starts_with("line 1: "), "{}", x),
+        x => panic!("{:?}: {:?}", text, x),
+      }
+    }
+  }
+
+  #[test]
+  fn contains() {
+    let p = Panel {
+      name: "コマ1".to_string(),
+      x: 10,
+      y: 20,
+      width: 100,
+      height: 50,
+    };
+    assert!(p.contains(10, 20));
+    assert!(p.contains(109, 69));
+    assert!(!p.contains(110, 20));
+    assert!(!p.contains(10, 70));
+    assert!(!p.contains(9, 20));
+  }
+}
@@ -0,0 +1,171 @@
+use super::share::open_shared;
+use super::ClipError;
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// `BlockDataBeginChunk` in UTF-16BE
+const BLOCK_BEGIN: &[u8] = b"\0B\0l\0o\0c\0k\0D\0a\0t\0a\0B\0e\0g\0i\0n\0C\0h\0u\0n\0k";
+/// index, uncompressed size, width, height, has data
+const BLOCK_HEADER_SIZE: usize = 20;
+/// width and height of a block
+const BLOCK_SIZE: u32 = 256;
+/// `LayerType` of raster layers
+const RASTER_LAYER_TYPE: u64 = 1;
+
+/// Brief
+///
+/// Find layers which have no visible pixels.
+///
+/// The pixels of a layer are stored in the external chunks (`CHNKExta`) referenced by
+/// `Offscreen.BlockData`. Offscreens referenced by `MipmapInfo` are render caches and ignored.
+/// An offscreen is divided into 256x256 blocks; a block either has no data,
+/// or zlib compressed data starting with the alpha channel.
+/// A layer is empty when no block of its offscreens has a non zero alpha value.
+/// Only raster layers are checked: vector layers keep their strokes in the vector tables,
+/// and their offscreens may have no pixels even if they have strokes.
+///
+/// * `conn`: sqlite3
+/// * `clip`: clip file (the part before the sqlite3 data is enough)
+///
+/// Return.
+///
+/// main_id of empty layers
+pub(super) fn find_empty_layers(
+  conn: &rusqlite::Connection,
+  clip: &Path,
+) -> Result<HashSet<u64>, ClipError> {
+  let mut offscreens: HashMap<Vec<u8>, u64> = HashMap::new();
+  {
+    let sql = format!(
+      "SELECT LayerId, BlockData FROM Offscreen WHERE MainId NOT IN (SELECT Offscreen FROM MipmapInfo) AND LayerId IN (SELECT MainId FROM Layer WHERE LayerType = {})",
+      RASTER_LAYER_TYPE
+    );
+    let mut stmt = match conn.prepare(&sql) {
+      Ok(x) => x,
+      // no raster data in this file
+      Err(_) => return Ok(HashSet::new()),
+    };
+    let rows = match stmt.query_map([], |row| {
+      Ok((
+        row.get::<_, Option<u64>>(0)?,
+        row.get::<_, Option<Vec<u8>>>(1)?,
+      ))
+    }) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    for r in rows {
+      if let Ok((Some(layer), Some(id))) = r {
+        offscreens.insert(id, layer);
+      }
+    }
+  }
+
+  let mut layers: HashSet<u64> = offscreens.values().cloned().collect();
+  for_each_external_chunk(clip, |id, data| {
+    if let Some(layer) = offscreens.get(id) {
+      if has_pixels(data) {
+        layers.remove(layer);
+      }
+    }
+  })?;
+  return Ok(layers);
+}
+
+/// Call `f(external id, chunk data)` for each `CHNKExta` chunk before the sqlite3 data.
+fn for_each_external_chunk<F>(clip: &Path, mut f: F) -> Result<(), ClipError>
+where
+  F: FnMut(&[u8], &[u8]),
+{
+  let file = match open_shared(clip) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileOpenError),
+  };
+  let mut inf = BufReader::new(file);
+  let mut offset: u64 = 24;
+  loop {
+    if let Err(_) = inf.seek(SeekFrom::Start(offset)) {
+      return Err(ClipError::IOError);
+    }
+    let mut header = [0u8; 16];
+    if let Err(_) = inf.read_exact(&mut header) {
+      return Ok(());
+    }
+    let size = u64::from_be_bytes(header[8..16].try_into().unwrap());
+    match &header[0..8] {
+      b"CHNKExta" => {
+        let mut data = Vec::new();
+        if let Err(_) = (&mut inf).take(size).read_to_end(&mut data) {
+          return Err(ClipError::FileReadError);
+        }
+        if data.len() as u64 != size || data.len() < 8 {
+          return Err(ClipError::UnknownFileStruct);
+        }
+        let id_end = match usize::try_from(u64::from_be_bytes(data[0..8].try_into().unwrap()))
+          .ok()
+          .and_then(|x| x.checked_add(8))
+        {
+          Some(x) if x <= data.len() => x,
+          _ => return Err(ClipError::UnknownFileStruct),
+        };
+        f(&data[8..id_end], &data[id_end..]);
+      }
+      b"CHNKHead" => {}
+      _ => return Ok(()),
+    }
+    offset = match size.checked_add(16).and_then(|x| offset.checked_add(x)) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+  }
+}
+
+/// Whether any block of the offscreen data has a non zero alpha value.
+/// Blocks which cannot be read or are larger than `BLOCK_SIZE` are regarded as having pixels.
+fn has_pixels(data: &[u8]) -> bool {
+  let mut pos = 0;
+  while let Some(i) = memchr::memmem::find(&data[pos..], BLOCK_BEGIN) {
+    pos += i + BLOCK_BEGIN.len();
+    let header = match data.get(pos..pos + BLOCK_HEADER_SIZE) {
+      Some(x) => x,
+      None => return true,
+    };
+    let value = |i: usize| u32::from_be_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+    let (width, height, has_data) = (value(2), value(3), value(4));
+    if has_data == 0 {
+      continue;
+    }
+    pos += BLOCK_HEADER_SIZE;
+    // big endian size of the rest, little endian size of the zlib data
+    let size = match data.get(pos + 4..pos + 8) {
+      Some(x) => u32::from_le_bytes(x.try_into().unwrap()) as usize,
+      None => return true,
+    };
+    let end = match pos.checked_add(8).and_then(|x| x.checked_add(size)) {
+      Some(x) => x,
+      None => return true,
+    };
+    let compressed = match data.get(pos + 8..end) {
+      Some(x) => x,
+      None => return true,
+    };
+    if width > BLOCK_SIZE || height > BLOCK_SIZE {
+      return true;
+    }
+    let mut alpha = match width.checked_mul(height) {
+      Some(x) => vec![0u8; x as usize],
+      None => return true,
+    };
+    if let Err(_) = ZlibDecoder::new(compressed).read_exact(&mut alpha) {
+      return true;
+    }
+    if alpha.iter().any(|x| *x != 0) {
+      return true;
+    }
+    pos = end;
+  }
+  return false;
+}
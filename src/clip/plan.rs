@@ -0,0 +1,132 @@
+use super::{
+  comment, find_layer_index, get_layer_roots, rename_layers_batched, vector, CancelToken,
+  ClipError, ClipLayer, RenameReport, RenamedLayer, Warning,
+};
+use serde::{Deserialize, Serialize};
+
+/// Brief.
+///
+/// New layer names decided by the rename rules, before the data base is changed.
+///
+/// A plan is made by `ClipDocument::plan_renames` (or `plan_renames_in_sqlite`) and written by
+/// `ClipDocument::apply_plan`. It can be inspected, edited or serialized in between,
+/// e.g. to ask the user for approval.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamePlan {
+  /// number of layers (folders are not included)
+  pub total_layers: usize,
+  /// layers to be renamed in the order of renaming
+  pub renames: Vec<RenamedLayer>,
+  /// record the old names in the layer comments when applied (`RenameOptions::keep_old_names`)
+  #[serde(default)]
+  pub keep_old_names: bool,
+  /// update copies of the layer names in vector data tables when applied (`RenameOptions::vector_names`)
+  #[serde(default)]
+  pub vector_names: bool,
+  /// non fatal conditions found while planning
+  #[serde(skip)]
+  pub warnings: Vec<Warning>,
+}
+
+impl RenamePlan {
+  /// Brief.
+  ///
+  /// Make a plan from the layers collected by the rename rules.
+  pub(super) fn from_report(
+    report: RenameReport,
+    keep_old_names: bool,
+    vector_names: bool,
+  ) -> RenamePlan {
+    return RenamePlan {
+      total_layers: report.total_layers,
+      renames: report.renamed,
+      keep_old_names,
+      vector_names,
+      warnings: report.warnings,
+    };
+  }
+
+  /// true if no layer is renamed
+  pub fn is_empty(&self) -> bool {
+    return self.renames.is_empty();
+  }
+}
+
+/// Brief
+///
+/// Write the new names of a plan to the data base.
+///
+/// * `conn`: sqlite3
+/// * `plan`: new names
+/// * `batch_size`: layers updated by one statement (`ClipDocument::set_update_batch_size`)
+/// * `cancel`: checked for each layer
+///
+/// Return.
+///
+/// renamed layers. `ClipError::StalePlan` if a layer was renamed after the plan was made.
+pub(super) fn apply_plan_in(
+  conn: &rusqlite::Connection,
+  plan: &RenamePlan,
+  batch_size: usize,
+  cancel: &CancelToken,
+) -> Result<RenameReport, ClipError> {
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  get_layer_roots(conn, &mut v)?;
+  let vector_name_columns = if plan.vector_names {
+    vector::get_vector_name_columns(conn)?
+  } else {
+    Vec::new()
+  };
+  let mut report = RenameReport {
+    total_layers: plan.total_layers,
+    renamed: Vec::new(),
+    warnings: plan.warnings.clone(),
+  };
+  let mut renames: Vec<(&ClipLayer, &str)> = Vec::with_capacity(plan.renames.len());
+  for r in plan.renames.iter() {
+    cancel.check()?;
+    let index = match find_layer_index(&v, r.main_id) {
+      Some(x) => x,
+      None => return Err(ClipError::LayerNotFound(r.main_id)),
+    };
+    if v[index].layer_name != r.old_name {
+      return Err(ClipError::StalePlan(r.main_id));
+    }
+    renames.push((&v[index], &r.new_name));
+  }
+  rename_layers_batched(
+    conn,
+    &renames,
+    batch_size,
+    &vector_name_columns,
+    &mut report,
+    cancel,
+  )?;
+  if plan.keep_old_names {
+    if comment::get_comment_column(conn)?.is_none() {
+      report.warnings.push(Warning::NoCommentField);
+    }
+    comment::record_old_names(conn, &report)?;
+  }
+  return Ok(report);
+}
+
+/// Brief
+///
+/// `apply_plan_in` in one transaction, committed only if all names are written.
+pub(super) fn apply_plan_in_transaction(
+  conn: &rusqlite::Connection,
+  plan: &RenamePlan,
+  batch_size: usize,
+  cancel: &CancelToken,
+) -> Result<RenameReport, ClipError> {
+  let tx = match conn.unchecked_transaction() {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let report = apply_plan_in(&tx, plan, batch_size, cancel)?;
+  if let Err(_) = tx.commit() {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(report);
+}
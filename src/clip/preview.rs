@@ -0,0 +1,212 @@
+use super::{ClipError, LayerInfo, RenameReport};
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Fonts tried when no font is specified. CJK fonts first.
+const FONT_CANDIDATES: &[&str] = &[
+  "C:\\Windows\\Fonts\\meiryo.ttc",
+  "C:\\Windows\\Fonts\\msgothic.ttc",
+  "C:\\Windows\\Fonts\\YuGothM.ttc",
+  "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+  "/System/Library/Fonts/Hiragino Sans GB.ttc",
+  "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+  "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+  "/usr/share/fonts/truetype/fonts-japanese-gothic.ttf",
+  "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+];
+
+const FONT_SIZE: f32 = 16.0;
+const ROW_HEIGHT: u32 = 22;
+const INDENT: u32 = 16;
+const MARGIN: u32 = 12;
+const GAP: u32 = 80;
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const TEXT: [u8; 3] = [32, 32, 32];
+const FOLDER_TEXT: [u8; 3] = [32, 64, 160];
+const RENAMED_TEXT: [u8; 3] = [192, 48, 32];
+const LINE: [u8; 3] = [208, 208, 208];
+const RENAMED_LINE: [u8; 3] = [224, 96, 64];
+
+struct Canvas {
+  width: u32,
+  height: u32,
+  pixels: Vec<u8>,
+}
+
+impl Canvas {
+  fn new(width: u32, height: u32) -> Canvas {
+    let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+    for _ in 0..width * height {
+      pixels.extend_from_slice(&BACKGROUND);
+    }
+    return Canvas {
+      width,
+      height,
+      pixels,
+    };
+  }
+
+  fn blend(&mut self, x: i64, y: i64, color: [u8; 3], alpha: f32) {
+    if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+      return;
+    }
+    let alpha = alpha.clamp(0.0, 1.0);
+    let i = ((y as u32 * self.width + x as u32) * 3) as usize;
+    for (p, c) in self.pixels[i..i + 3].iter_mut().zip(color.iter()) {
+      let old = *p as f32;
+      *p = (old + (*c as f32 - old) * alpha).round() as u8;
+    }
+  }
+
+  fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: [u8; 3]) {
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+    for s in 0..=steps {
+      let x = x0 + (x1 - x0) * s / steps;
+      let y = y0 + (y1 - y0) * s / steps;
+      self.blend(x, y, color, 1.0);
+    }
+  }
+
+  fn text(&mut self, font: &FontVec, x: f32, baseline: f32, text: &str, color: [u8; 3]) {
+    let scaled = font.as_scaled(PxScale::from(FONT_SIZE));
+    let mut caret = x;
+    for ch in text.chars() {
+      let glyph = scaled.scaled_glyph(ch);
+      let advance = scaled.h_advance(glyph.id);
+      let glyph = glyph
+        .id
+        .with_scale_and_position(PxScale::from(FONT_SIZE), ab_glyph::point(caret, baseline));
+      if let Some(outlined) = font.outline_glyph(glyph) {
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+          self.blend(
+            bounds.min.x as i64 + gx as i64,
+            bounds.min.y as i64 + gy as i64,
+            color,
+            coverage,
+          );
+        });
+      }
+      caret += advance;
+    }
+  }
+}
+
+fn text_width(font: &FontVec, text: &str) -> f32 {
+  let scaled = font.as_scaled(PxScale::from(FONT_SIZE));
+  return text
+    .chars()
+    .map(|c| scaled.h_advance(font.glyph_id(c)))
+    .sum();
+}
+
+/// Brief.
+///
+/// Load a font for the preview image.
+///
+/// * `path`: font file. If `None`, common system fonts are tried.
+pub fn load_preview_font(path: Option<&Path>) -> Result<FontVec, ClipError> {
+  let candidates: Vec<&Path> = match path {
+    Some(p) => vec![p],
+    None => FONT_CANDIDATES.iter().map(Path::new).collect(),
+  };
+  for p in candidates {
+    let data = match std::fs::read(p) {
+      Ok(x) => x,
+      Err(_) => continue,
+    };
+    if let Ok(font) = FontVec::try_from_vec(data) {
+      return Ok(font);
+    }
+  }
+  return Err(ClipError::FontNotFound);
+}
+
+/// Brief.
+///
+/// Write a PNG image of the layer tree before and after renaming.
+/// The left column is the tree before renaming and the right column is the tree after renaming.
+/// Each layer is connected by a line, and renamed layers are highlighted.
+///
+/// * `before`: layers before renaming (`list_layers_in_sqlite`)
+/// * `report`: result of renaming
+/// * `font`: font to draw layer names (`load_preview_font`)
+/// * `dst`: output png file
+pub fn write_rename_preview<P: AsRef<Path>>(
+  before: &[LayerInfo],
+  report: &RenameReport,
+  font: &FontVec,
+  dst: P,
+) -> Result<(), ClipError> {
+  let renamed: HashMap<u64, &str> = report
+    .renamed
+    .iter()
+    .map(|r| (r.main_id, r.new_name.as_str()))
+    .collect();
+  let rows: Vec<(&LayerInfo, &str)> = before
+    .iter()
+    .map(|l| match renamed.get(&l.main_id) {
+      Some(n) => (l, *n),
+      None => (l, l.name.as_str()),
+    })
+    .collect();
+
+  let column_width = |name: fn(&(&LayerInfo, &str)) -> String| -> u32 {
+    return rows
+      .iter()
+      .map(|r| r.0.depth as u32 * INDENT + text_width(font, &name(r)).ceil() as u32)
+      .max()
+      .unwrap_or(0);
+  };
+  let left_width = column_width(|r| r.0.name.clone());
+  let right_width = column_width(|r| r.1.to_string());
+  let right_x = MARGIN + left_width + GAP;
+  let width = right_x + right_width + MARGIN;
+  let height = MARGIN * 2 + ROW_HEIGHT * (rows.len().max(1) as u32);
+  let mut canvas = Canvas::new(width, height);
+
+  let ascent = font.as_scaled(PxScale::from(FONT_SIZE)).ascent();
+  for (i, (layer, new_name)) in rows.iter().enumerate() {
+    let top = MARGIN + ROW_HEIGHT * i as u32;
+    let baseline = top as f32 + (ROW_HEIGHT as f32 - FONT_SIZE) / 2.0 + ascent;
+    let indent = layer.depth as u32 * INDENT;
+    let is_renamed = renamed.contains_key(&layer.main_id);
+    let color = if layer.folder {
+      FOLDER_TEXT
+    } else if is_renamed {
+      RENAMED_TEXT
+    } else {
+      TEXT
+    };
+    let old_x = MARGIN + indent;
+    let new_x = right_x + indent;
+    canvas.text(font, old_x as f32, baseline, &layer.name, color);
+    canvas.text(font, new_x as f32, baseline, new_name, color);
+
+    let y = (top + ROW_HEIGHT / 2) as i64;
+    let from = (old_x as f32 + text_width(font, &layer.name)).ceil() as i64 + 4;
+    let to = new_x as i64 - 4;
+    let line_color = if is_renamed { RENAMED_LINE } else { LINE };
+    canvas.line(from, y, to, y, line_color);
+  }
+
+  let file = match File::create(dst) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.set_depth(png::BitDepth::Eight);
+  let mut writer = match encoder.write_header() {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::FileSaveError),
+  };
+  if let Err(_) = writer.write_image_data(&canvas.pixels) {
+    return Err(ClipError::FileSaveError);
+  }
+  return Ok(());
+}
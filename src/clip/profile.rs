@@ -0,0 +1,82 @@
+use super::{LayerKind, NameTemplate, NumberingOrder, RenameOptions, RenameRules, RuleAction};
+use regex::Regex;
+
+/// Brief.
+///
+/// Built-in set of rename settings for a common workflow.
+#[derive(Debug, Clone, Copy)]
+pub struct Profile {
+  /// profile name
+  pub name: &'static str,
+  /// regex of layer names to be renamed
+  pub pattern: &'static str,
+  /// top level layer base name. Top level layers are not renamed if empty.
+  pub root_layer_base_name: &'static str,
+  /// default template
+  pub template: &'static str,
+  /// per kind rules (`KIND`, `TEMPLATE` or `skip`)
+  pub kind_rules: &'static [(LayerKind, &'static str)],
+  /// numbering order
+  pub order: NumberingOrder,
+}
+
+/// built-in profiles
+pub const PROFILES: &[Profile] = &[
+  Profile {
+    name: "illustration",
+    pattern: r"^(レイヤー|Layer) ?\d+$",
+    root_layer_base_name: "ルートレイヤ ",
+    template: "{parent} {n}",
+    kind_rules: &[],
+    order: NumberingOrder::BottomUp,
+  },
+  // Pages are read from top to bottom, and line art is usually vector.
+  Profile {
+    name: "webtoon",
+    pattern: r"^(レイヤー|Layer|레이어) ?\d+$",
+    root_layer_base_name: "page",
+    template: "{parent}_{n}",
+    kind_rules: &[(LayerKind::Vector, "{parent}_line_{n}")],
+    order: NumberingOrder::TopDown,
+  },
+  // Cels are named like "A1", "A2" in the cel folder "A".
+  // Top level layers and non drawing layers are left as they are.
+  Profile {
+    name: "animation",
+    pattern: r"^(レイヤー|Layer) ?\d+$",
+    root_layer_base_name: "",
+    template: "{parent}{n}",
+    kind_rules: &[(LayerKind::Other, "skip")],
+    order: NumberingOrder::BottomUp,
+  },
+];
+
+impl Profile {
+  /// Find a built-in profile by name.
+  pub fn find(name: &str) -> Option<&'static Profile> {
+    return PROFILES.iter().find(|x| x.name == name);
+  }
+
+  /// Create rename rules of this profile.
+  pub fn rules(&self) -> RenameRules {
+    let mut rules = RenameRules::new(NameTemplate::parse(self.template).unwrap());
+    for (kind, action) in self.kind_rules.iter() {
+      let action = if *action == "skip" {
+        RuleAction::Skip
+      } else {
+        RuleAction::Template(NameTemplate::parse(action).unwrap())
+      };
+      rules.kind_rules.push((*kind, action));
+    }
+    rules.order = self.order;
+    return rules;
+  }
+
+  /// Create rename options of this profile.
+  pub fn options(&self) -> RenameOptions {
+    return RenameOptions::new()
+      .pattern(Regex::new(self.pattern).unwrap())
+      .root_layer_base_name(self.root_layer_base_name)
+      .rules(self.rules());
+  }
+}
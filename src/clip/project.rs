@@ -0,0 +1,140 @@
+use super::ClipError;
+
+/// Brief
+///
+/// Read the project name (title) in the Project table.
+///
+/// Return.
+///
+/// `None` if the file has no project record
+pub(super) fn get_project_name(conn: &rusqlite::Connection) -> Result<Option<String>, ClipError> {
+  return match conn.query_row("SELECT ProjectName FROM Project LIMIT 1", [], |row| {
+    row.get::<_, Option<String>>(0)
+  }) {
+    Ok(x) => Ok(Some(x.unwrap_or_default())),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+    // no Project table or ProjectName column
+    Err(rusqlite::Error::SqliteFailure(_, _)) => Ok(None),
+    Err(_) => Err(ClipError::SQLError),
+  };
+}
+
+/// Brief
+///
+/// Set the project name (title) in the Project table.
+///
+/// `ClipError::UnknownSchema` if the file has no project record.
+pub(super) fn set_project_name(conn: &rusqlite::Connection, name: &str) -> Result<(), ClipError> {
+  if get_project_name(conn)?.is_none() {
+    return Err(ClipError::UnknownSchema("no project name".to_string()));
+  }
+  if let Err(_) = conn.execute("UPDATE Project SET ProjectName = $1", [name]) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+/// `Canvas` column holding the name of a canvas: the story name (作品名) of the story information
+const CANVAS_NAME_COLUMN: &str = "ComicStoryStoryName";
+
+/// Brief
+///
+/// Read the names of the canvases (`CANVAS_NAME_COLUMN`) in the order of `RenameOptions::canvas`.
+///
+/// `ClipError::UnknownSchema` if the Canvas table has no name column.
+///
+/// Return.
+///
+/// canvas names. Unnamed canvases are empty.
+pub(super) fn get_canvas_names(conn: &rusqlite::Connection) -> Result<Vec<String>, ClipError> {
+  let mut stmt = match conn.prepare(&format!(
+    "SELECT {} FROM Canvas ORDER BY MainId",
+    CANVAS_NAME_COLUMN
+  )) {
+    Ok(x) => x,
+    // no Canvas table or name column
+    Err(rusqlite::Error::SqliteFailure(_, _)) => {
+      return Err(ClipError::UnknownSchema("no canvas name".to_string()))
+    }
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| row.get::<_, Option<String>>(0)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut names: Vec<String> = Vec::new();
+  for r in rows {
+    match r {
+      Ok(x) => names.push(x.unwrap_or_default()),
+      Err(_) => return Err(ClipError::SQLError),
+    }
+  }
+  return Ok(names);
+}
+
+/// Brief
+///
+/// Set the name of a canvas (`CANVAS_NAME_COLUMN`).
+///
+/// * `canvas`: canvas number from 1 (`RenameOptions::canvas`)
+/// * `name`: new canvas name
+pub(super) fn set_canvas_name(
+  conn: &rusqlite::Connection,
+  canvas: usize,
+  name: &str,
+) -> Result<(), ClipError> {
+  let count = get_canvas_names(conn)?.len();
+  if canvas == 0 || canvas > count {
+    return Err(ClipError::CanvasNotFound(canvas, count));
+  }
+  if let Err(_) = conn.execute(
+    &format!(
+      "UPDATE Canvas SET {} = $1 WHERE _PW_ID = (SELECT _PW_ID FROM Canvas ORDER BY MainId LIMIT 1 OFFSET $2)",
+      CANVAS_NAME_COLUMN
+    ),
+    rusqlite::params![name, canvas - 1],
+  ) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn canvases() -> rusqlite::Connection {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "CREATE TABLE Canvas (_PW_ID INTEGER PRIMARY KEY, MainId INTEGER, ComicStoryStoryName TEXT);
+         INSERT INTO Canvas (MainId, ComicStoryStoryName) VALUES (3, 'b'), (1, NULL);",
+      )
+      .unwrap();
+    return conn;
+  }
+
+  #[test]
+  fn canvas_names_in_main_id_order() {
+    let conn = canvases();
+    assert_eq!(get_canvas_names(&conn).unwrap(), vec!["", "b"]);
+    set_canvas_name(&conn, 1, "a").unwrap();
+    assert_eq!(get_canvas_names(&conn).unwrap(), vec!["a", "b"]);
+    assert!(matches!(
+      set_canvas_name(&conn, 3, "c"),
+      Err(ClipError::CanvasNotFound(3, 2))
+    ));
+  }
+
+  #[test]
+  fn no_canvas_name_column() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch("CREATE TABLE Canvas (_PW_ID INTEGER PRIMARY KEY, MainId INTEGER);")
+      .unwrap();
+    assert!(matches!(
+      get_canvas_names(&conn),
+      Err(ClipError::UnknownSchema(_))
+    ));
+  }
+}
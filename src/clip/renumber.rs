@@ -0,0 +1,66 @@
+use super::{
+  creation, get_children, get_max_layer_number, order_children, plan_rename, ClipError, ClipLayer,
+  NumberingOrder, RenameOptions, RenameReport,
+};
+use std::collections::HashMap;
+
+/// default regex of folder names to be renumbered (`RenameOptions::renumber_folders`)
+pub const DEFAULT_FOLDER_PATTERN: &str = r"^フォルダー ?\d+$";
+/// default template of renumbered folder names
+pub const DEFAULT_SCENE_TEMPLATE: &str = "シーン {n}";
+
+/// Brief
+///
+/// Rename top level folders matching the pattern with the default template.
+/// Layers in the folders are not renamed.
+///
+/// * `conn`: sqlite3
+/// * `v`: all layer information
+/// * `root_index`: root folder index of `v`
+/// * `options`: options of renaming
+pub(super) fn renumber_top_folders(
+  conn: &rusqlite::Connection,
+  v: &[Box<ClipLayer>],
+  root_index: usize,
+  options: &RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  let parent_name = &options.root_layer_base_name;
+  let template = &options.rules.template;
+  let mut folders: Vec<usize> = get_children(v, root_index)?
+    .into_iter()
+    .filter(|x| v[*x].layer_folder != 0)
+    .collect();
+  let creation_times = if options.rules.order == NumberingOrder::Created {
+    creation::get_creation_times(conn)?
+  } else {
+    HashMap::new()
+  };
+  order_children(v, &mut folders, options.rules.order, &creation_times);
+  let mut report = RenameReport {
+    total_layers: folders.len(),
+    renamed: Vec::new(),
+    warnings: Vec::new(),
+  };
+  if template.uses_parent() && parent_name.is_empty() {
+    return Ok(report);
+  }
+
+  let mut number = 1;
+  if options.continue_numbering {
+    number += get_max_layer_number(v, root_index, parent_name, template, true, options)?;
+  }
+  for fi in folders {
+    let f = &v[fi];
+    if f
+      .special
+      .is_some_and(|x| !options.allow_special.contains(&x))
+      || !options.need_rename(&f.layer_name)
+    {
+      continue;
+    }
+    let name = template.format_layer(parent_name, number, f.main_id, &f.layer_name);
+    number += 1;
+    plan_rename(f, &name, &mut report);
+  }
+  return Ok(report);
+}
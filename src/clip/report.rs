@@ -0,0 +1,115 @@
+use super::SpecialLayer;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A renamed layer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamedLayer {
+  /// main_id of the layer
+  pub main_id: u64,
+  /// name before renaming
+  pub old_name: String,
+  /// name after renaming
+  pub new_name: String,
+}
+
+/// Brief.
+///
+/// Result of renaming.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenameReport {
+  /// number of layers (folders are not included)
+  pub total_layers: usize,
+  /// renamed layers in the order of renaming
+  pub renamed: Vec<RenamedLayer>,
+  /// non fatal conditions found while renaming
+  pub warnings: Vec<Warning>,
+}
+
+impl RenameReport {
+  /// "renamed 42 of 180 layers"
+  pub fn summary(&self) -> String {
+    return format!(
+      "renamed {} of {} layers",
+      self.renamed.len(),
+      self.total_layers
+    );
+  }
+}
+
+/// Brief.
+///
+/// A non fatal condition found while loading or renaming.
+/// The result is valid, but it may differ from what the caller expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+  /// a layer matching the pattern was not renamed because it is a special layer
+  SkippedSpecial {
+    main_id: u64,
+    name: String,
+    special: SpecialLayer,
+  },
+  /// a layer matching the pattern was not renamed because it is protected (`RenameOptions::protect_prefix`)
+  SkippedProtected { main_id: u64, name: String },
+  /// old names were not kept because the file has no layer comment field (`RenameOptions::keep_old_names`)
+  NoCommentField,
+  /// the embedded data base was in WAL mode. It is checkpointed when saved.
+  WalCheckpointed,
+  /// bytes appended after the footer chunk are kept as they are
+  TrailingBytesKept(u64),
+  /// the embedded data base was saved in the middle of writing and passed an integrity check
+  InconsistentSize,
+  /// a layer was not renamed because the reference has no layer of the same structure at its position
+  NoReferenceLayer { main_id: u64, name: String },
+  /// a layer was not renamed because its offset is in no panel (`ClipDocument::plan_panels`)
+  NoPanel { main_id: u64, name: String },
+}
+
+impl fmt::Display for Warning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return match self {
+      Warning::SkippedSpecial {
+        main_id,
+        name,
+        special,
+      } => write!(
+        f,
+        "layer {} ({}) was skipped: {} layer",
+        main_id,
+        name,
+        special.name()
+      ),
+      Warning::SkippedProtected { main_id, name } => {
+        write!(f, "layer {} ({}) was skipped: protected", main_id, name)
+      }
+      Warning::NoCommentField => write!(
+        f,
+        "this file has no layer comment field. Old names are not kept."
+      ),
+      Warning::WalCheckpointed => write!(
+        f,
+        "the embedded data base was in WAL mode. All changes were checkpointed into it."
+      ),
+      Warning::TrailingBytesKept(x) => {
+        write!(
+          f,
+          "{} bytes after the footer chunk were kept as they are.",
+          x
+        )
+      }
+      Warning::InconsistentSize => write!(
+        f,
+        "the embedded data base was saved in the middle of writing. It passed an integrity check."
+      ),
+      Warning::NoReferenceLayer { main_id, name } => write!(
+        f,
+        "layer {} ({}) was skipped: no layer of the same kind at its position in the reference",
+        main_id, name
+      ),
+      Warning::NoPanel { main_id, name } => {
+        write!(f, "layer {} ({}) was skipped: in no panel", main_id, name)
+      }
+    };
+  }
+}
@@ -0,0 +1,174 @@
+use super::{ClipError, NameTemplate};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Default prefix of layer names which must not be renamed.
+pub const DEFAULT_PROTECT_PREFIX: &str = "!";
+/// `KIND` of the rule for empty layers
+const EMPTY_RULE_NAME: &str = "empty";
+
+/// Kind of a (non folder) layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+  /// raster layer
+  Raster,
+  /// vector layer
+  Vector,
+  /// other layers (text, fill, gradient, ...)
+  Other,
+}
+
+impl LayerKind {
+  /// all layer kinds
+  pub const ALL: [LayerKind; 3] = [LayerKind::Raster, LayerKind::Vector, LayerKind::Other];
+
+  /// name used in command line options
+  pub fn name(&self) -> &'static str {
+    return match self {
+      LayerKind::Raster => "raster",
+      LayerKind::Vector => "vector",
+      LayerKind::Other => "other",
+    };
+  }
+}
+
+impl FromStr for LayerKind {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    return match LayerKind::ALL.iter().find(|x| x.name() == s) {
+      Some(x) => Ok(*x),
+      None => Err(format!("unknown layer kind: {}", s)),
+    };
+  }
+}
+
+/// Order in which layers in a folder are numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberingOrder {
+  /// from the bottom layer of the folder (Clip Studio Paint's default)
+  BottomUp,
+  /// from the top layer of the folder
+  TopDown,
+  /// in the order of main_id, i.e. the order in which the layers were made
+  Id,
+  /// in the order of the creation times recorded in the Layer table.
+  /// Layers without the time come first in the order of main_id.
+  Created,
+}
+
+/// What to do with layers of a kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+  /// keep the name
+  Skip,
+  /// rename with the template
+  Template(NameTemplate),
+}
+
+/// Brief.
+///
+/// Rules deciding new names of layers.
+///
+/// `template` is used for layers whose kind has no rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameRules {
+  /// default template
+  pub template: NameTemplate,
+  /// per layer kind rules
+  pub kind_rules: Vec<(LayerKind, RuleAction)>,
+  /// numbering order
+  pub order: NumberingOrder,
+  /// name material layers after their source material instead of the templates
+  pub use_material_names: bool,
+  /// name layers with a color label `{color}{n}`, numbered for each color in the folder
+  pub use_color_labels: bool,
+  /// layers whose names start with this prefix are never renamed. Empty disables it.
+  pub protect_prefix: String,
+  /// rule for raster layers without visible pixels. `None` treats them as other layers.
+  pub empty_rule: Option<RuleAction>,
+  /// layers and folders with these names are renamed to the replacements verbatim (e.g. "線画" → "Lineart")
+  pub dictionary: HashMap<String, String>,
+}
+
+impl RenameRules {
+  /// Brief.
+  ///
+  /// Rules renaming every layer kind with `template` from the bottom layer.
+  pub fn new(template: NameTemplate) -> RenameRules {
+    return RenameRules {
+      template,
+      kind_rules: Vec::new(),
+      order: NumberingOrder::BottomUp,
+      use_material_names: false,
+      use_color_labels: false,
+      protect_prefix: DEFAULT_PROTECT_PREFIX.to_string(),
+      empty_rule: None,
+      dictionary: HashMap::new(),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Parse a per kind rule (`KIND=TEMPLATE` or `KIND=skip`) and add it.
+  /// A later rule of the same kind replaces the former.
+  /// `KIND` may be `empty` for raster layers without visible pixels.
+  pub fn add_kind_rule(&mut self, rule: &str) -> Result<(), ClipError> {
+    let (kind, action) = match rule.find('=') {
+      Some(i) => (&rule[..i], &rule[i + 1..]),
+      None => return Err(ClipError::InvalidRule(rule.to_string())),
+    };
+    let action = if action == "skip" {
+      RuleAction::Skip
+    } else {
+      RuleAction::Template(NameTemplate::parse(action)?)
+    };
+    if kind.trim() == EMPTY_RULE_NAME {
+      self.empty_rule = Some(action);
+      return Ok(());
+    }
+    let kind: LayerKind = match kind.trim().parse() {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::InvalidRule(rule.to_string())),
+    };
+    self.kind_rules.retain(|(k, _)| *k != kind);
+    self.kind_rules.push((kind, action));
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// The template for the layer kind.
+  ///
+  /// Return.
+  ///
+  /// `None` if layers of the kind must not be renamed.
+  pub fn template_for(&self, kind: LayerKind) -> Option<&NameTemplate> {
+    return match self.kind_rules.iter().find(|(k, _)| *k == kind) {
+      Some((_, RuleAction::Skip)) => None,
+      Some((_, RuleAction::Template(t))) => Some(t),
+      None => Some(&self.template),
+    };
+  }
+
+  /// Whether the layer name is pinned by the artist with `protect_prefix`.
+  pub fn is_protected(&self, name: &str) -> bool {
+    return !self.protect_prefix.is_empty() && name.starts_with(&self.protect_prefix);
+  }
+
+  /// All templates which may be used.
+  pub fn templates(&self) -> Vec<&NameTemplate> {
+    let mut v = vec![&self.template];
+    for (_, a) in self.kind_rules.iter() {
+      if let RuleAction::Template(t) = a {
+        v.push(t);
+      }
+    }
+    return v;
+  }
+}
+
+impl Default for RenameRules {
+  fn default() -> Self {
+    return RenameRules::new(NameTemplate::default());
+  }
+}
@@ -0,0 +1,271 @@
+use super::material::get_columns;
+use super::ClipError;
+use std::path::Path;
+
+/// Brief.
+///
+/// A schema of the data base written by Clip Studio Paint.
+pub(super) struct KnownSchema {
+  /// name shown in messages
+  pub(super) name: &'static str,
+  /// `(name, declared type)` of all columns of the Layer table
+  pub(super) layer_columns: &'static [(&'static str, &'static str)],
+}
+
+/// schemas accepted by `check_schema`
+pub(super) const KNOWN_SCHEMAS: &[KnownSchema] = &[KnownSchema {
+  // resource/sample.clip
+  name: "schema 1",
+  layer_columns: &[
+    ("_PW_ID", "INTEGER"),
+    ("MainId", "INTEGER"),
+    ("CanvasId", "INTEGER"),
+    ("LayerName", "TEXT"),
+    ("LayerType", "INTEGER"),
+    ("LayerLock", "INTEGER"),
+    ("LayerClip", "INTEGER"),
+    ("LayerMasking", "INTEGER"),
+    ("LayerOffsetX", "INTEGER"),
+    ("LayerOffsetY", "INTEGER"),
+    ("LayerRenderOffscrOffsetX", "INTEGER"),
+    ("LayerRenderOffscrOffsetY", "INTEGER"),
+    ("LayerMaskOffsetX", "INTEGER"),
+    ("LayerMaskOffsetY", "INTEGER"),
+    ("LayerMaskOffscrOffsetX", "INTEGER"),
+    ("LayerMaskOffscrOffsetY", "INTEGER"),
+    ("LayerOpacity", "INTEGER"),
+    ("LayerComposite", "INTEGER"),
+    ("LayerUsePaletteColor", "INTEGER"),
+    ("LayerNoticeablePaletteColor", "INTEGER"),
+    ("LayerPaletteRed", "INTEGER"),
+    ("LayerPaletteGreen", "INTEGER"),
+    ("LayerPaletteBlue", "INTEGER"),
+    ("LayerFolder", "INTEGER"),
+    ("LayerVisibility", "INTEGER"),
+    ("LayerSelect", "INTEGER"),
+    ("LayerNextIndex", "INTEGER"),
+    ("LayerFirstChildIndex", "INTEGER"),
+    ("LayerUuid", "TEXT"),
+    ("LayerRenderMipmap", "INTEGER"),
+    ("LayerLayerMaskMipmap", "INTEGER"),
+    ("LayerRenderThumbnail", "INTEGER"),
+    ("LayerLayerMaskThumbnail", "INTEGER"),
+    ("UsePreviewColorType", ""),
+    ("UsePreviewMaskColorType", ""),
+    ("EffectRangeType", ""),
+    ("DraftLayer", ""),
+    ("FilterLayerV132", ""),
+    ("DrawColorMainRed", "INTEGER"),
+    ("DrawColorMainGreen", "INTEGER"),
+    ("DrawColorMainBlue", "INTEGER"),
+    ("DrawColorEnable", "INTEGER"),
+    ("DrawToRenderOffscreenType", "INTEGER"),
+    ("SpecialRenderType", "INTEGER"),
+    ("DrawToRenderMipmapType", "INTEGER"),
+    ("MoveOffsetAndExpandType", "INTEGER"),
+    ("FixOffsetAndExpandType", "INTEGER"),
+    ("RenderBoundForLayerMoveType", "INTEGER"),
+    ("SetRenderThumbnailInfoType", "INTEGER"),
+    ("DrawRenderThumbnailType", "INTEGER"),
+    ("MonochromeFillInfo", "BLOB"),
+    ("LayerColorTypeIndex", "INTEGER"),
+    ("LayerColorTypeBlackChecked", "INTEGER"),
+    ("LayerColorTypeWhiteChecked", "INTEGER"),
+    ("VectorNormalStrokeIndex", "INTEGER"),
+    ("VectorNormalFillIndex", "INTEGER"),
+    ("VectorNormalBalloonIndex", "INTEGER"),
+    ("VectorNormalType", "INTEGER"),
+    ("MixSubColorForEveryPlot", "INTEGER"),
+  ],
+}];
+
+/// Names of the known schemas of the embedded data base, e.g. for compatibility checks.
+pub fn known_schema_names() -> Vec<&'static str> {
+  return KNOWN_SCHEMAS.iter().map(|x| x.name).collect();
+}
+
+/// `(table, columns)` which must exist besides the Layer table
+pub(super) const REQUIRED_TABLES: &[(&str, &[&str])] = &[
+  ("Canvas", &["MainId", "CanvasRootFolder"]),
+  ("Mipmap", &["MainId", "LayerId"]),
+  ("MipmapInfo", &["MainId", "LayerId", "Offscreen"]),
+  ("Offscreen", &["MainId", "LayerId", "BlockData"]),
+  ("LayerThumbnail", &["MainId", "LayerId"]),
+];
+
+/// Brief
+///
+/// Check that the Layer table is exactly one of the known schemas (names and declared types of all columns),
+/// and that the other tables used with it exist.
+///
+/// The other functions guess unknown columns and tables from their names; this check refuses them instead.
+///
+/// Return.
+///
+/// `ClipError::UnknownSchema` with the differences from the nearest known schema
+pub(super) fn check_schema(conn: &rusqlite::Connection) -> Result<(), ClipError> {
+  known_schema(conn)?;
+  return Ok(());
+}
+
+/// `check_schema` returning the name of the matching known schema.
+fn known_schema(conn: &rusqlite::Connection) -> Result<&'static str, ClipError> {
+  for (table, columns) in REQUIRED_TABLES.iter() {
+    let actual = get_columns(conn, table)?;
+    if actual.is_empty() {
+      return Err(ClipError::UnknownSchema(format!(
+        "table {} not found",
+        table
+      )));
+    }
+    if let Some(c) = columns.iter().find(|c| !actual.iter().any(|x| x == *c)) {
+      return Err(ClipError::UnknownSchema(format!(
+        "column {}.{} not found",
+        table, c
+      )));
+    }
+  }
+
+  let actual = get_column_types(conn, "Layer")?;
+  let mut nearest: Option<(usize, String)> = None;
+  for schema in KNOWN_SCHEMAS.iter() {
+    let mut differences: Vec<String> = Vec::new();
+    for (name, ty) in schema.layer_columns.iter() {
+      match actual.iter().find(|(n, _)| n == name) {
+        None => differences.push(format!("missing {}", name)),
+        Some((_, t)) if !t.eq_ignore_ascii_case(ty) => {
+          differences.push(format!("{} is {} instead of {}", name, t, ty))
+        }
+        Some(_) => {}
+      }
+    }
+    for (name, _) in actual.iter() {
+      if !schema.layer_columns.iter().any(|(n, _)| n == name) {
+        differences.push(format!("unexpected {}", name));
+      }
+    }
+    if differences.is_empty() {
+      return Ok(schema.name);
+    }
+    if nearest
+      .as_ref()
+      .map_or(true, |(n, _)| differences.len() < *n)
+    {
+      nearest = Some((
+        differences.len(),
+        format!(
+          "Layer table differs from {}: {}",
+          schema.name,
+          differences.join(", ")
+        ),
+      ));
+    }
+  }
+  return Err(ClipError::UnknownSchema(
+    nearest.map(|(_, x)| x).unwrap_or_default(),
+  ));
+}
+
+/// Brief.
+///
+/// A table of the embedded data base.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+  /// table name
+  pub name: String,
+  /// `(name, declared type)` of the columns
+  pub columns: Vec<(String, String)>,
+  /// number of rows
+  pub rows: u64,
+}
+
+/// Brief.
+///
+/// Schema of the embedded data base, e.g. to see what changed in files of a new Clip Studio Paint version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseSchema {
+  /// tables in name order
+  pub tables: Vec<TableInfo>,
+  /// name of the known schema the Layer table matches,
+  /// or the differences from the nearest known schema
+  pub known: Result<&'static str, String>,
+}
+
+/// Brief.
+///
+/// Read the tables, columns and row counts of the data base.
+///
+/// * `sqlfile`: sqlite3 data base file path
+pub fn describe_schema_in_sqlite<P: AsRef<Path>>(sqlfile: P) -> Result<DatabaseSchema, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let names: Vec<String> = {
+    let mut stmt =
+      match conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name") {
+        Ok(x) => x,
+        Err(_) => return Err(ClipError::SQLError),
+      };
+    let rows = match stmt.query_map([], |row| row.get::<_, String>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let mut names = Vec::new();
+    for r in rows {
+      match r {
+        Ok(x) => names.push(x),
+        Err(_) => return Err(ClipError::SQLError),
+      }
+    }
+    names
+  };
+  let mut tables: Vec<TableInfo> = Vec::new();
+  for name in names.into_iter() {
+    let columns = get_column_types(&conn, &name)?;
+    let rows: i64 = match conn.query_row(
+      &format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")),
+      [],
+      |row| row.get(0),
+    ) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    tables.push(TableInfo {
+      name,
+      columns,
+      rows: rows as u64,
+    });
+  }
+  let known = match known_schema(&conn) {
+    Ok(x) => Ok(x),
+    Err(ClipError::UnknownSchema(x)) => Err(x),
+    Err(e) => return Err(e),
+  };
+  return Ok(DatabaseSchema { tables, known });
+}
+
+/// `(name, declared type)` of the columns of the table.
+fn get_column_types(
+  conn: &rusqlite::Connection,
+  table: &str,
+) -> Result<Vec<(String, String)>, ClipError> {
+  let mut stmt = match conn.prepare(&format!("PRAGMA table_info(\"{}\")", table)) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut columns: Vec<(String, String)> = Vec::new();
+  for r in rows {
+    match r {
+      Ok(x) => columns.push(x),
+      Err(_) => return Err(ClipError::SQLError),
+    }
+  }
+  return Ok(columns);
+}
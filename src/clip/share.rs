@@ -0,0 +1,29 @@
+use super::long_path::extended_path;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+#[cfg(windows)]
+const FILE_SHARE_READ: u32 = 0x1;
+#[cfg(windows)]
+const FILE_SHARE_WRITE: u32 = 0x2;
+#[cfg(windows)]
+const FILE_SHARE_DELETE: u32 = 0x4;
+
+/// Brief.
+///
+/// Open a clip file only for reading.
+///
+/// Other programs may read, write and delete the file meanwhile, so that a file opened
+/// in Clip Studio Paint can be read without closing it. The file is never locked nor written.
+///
+/// * `path`: file path
+pub fn open_shared<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
+  let mut options = OpenOptions::new();
+  options.read(true);
+  #[cfg(windows)]
+  {
+    use std::os::windows::fs::OpenOptionsExt;
+    options.share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE);
+  }
+  return options.open(extended_path(path.as_ref()));
+}
@@ -0,0 +1,91 @@
+use super::share::open_shared;
+use super::ClipError;
+use std::io::Read;
+use std::path::Path;
+
+/// magic number at the beginning of a clip file
+pub(super) const FILE_MAGIC: &[u8; 8] = b"CSFCHUNK";
+/// bytes read to detect the file type
+pub(super) const SNIFF_SIZE: usize = 16;
+/// extensions (lower case) of clip files: documents and the backups of Clip Studio Paint
+pub const CLIP_EXTENSIONS: &[&str] = &["clip", "clipbak"];
+
+/// `(magic number, offset, file type)` of files often given by mistake
+const KNOWN_TYPES: &[(&[u8], usize, &str)] = &[
+  (b"8BPS", 0, "PSD"),
+  (b"\x89PNG\r\n\x1a\n", 0, "PNG"),
+  (b"\xff\xd8\xff", 0, "JPEG"),
+  (b"GIF8", 0, "GIF"),
+  (b"WEBP", 8, "WebP"),
+  (b"II*\0", 0, "TIFF"),
+  (b"MM\0*", 0, "TIFF"),
+  (b"%PDF", 0, "PDF"),
+  (b"PK\x03\x04", 0, "zip"),
+  (b"PK\x05\x06", 0, "zip"),
+  (b"\x1f\x8b", 0, "gzip"),
+  (b"SQLite format 3\0", 0, "SQLite"),
+];
+
+/// Brief.
+///
+/// Check the magic number of a clip file before scanning the whole file.
+///
+/// * `head`: the first `SNIFF_SIZE` bytes of the file (shorter if the file is small)
+///
+/// Return.
+///
+/// `ClipError::UnexpectedFileType` if the data is a known other file type,
+/// `ClipError::NotClipFile` if it is unknown.
+pub(super) fn check_file_magic(head: &[u8]) -> Result<(), ClipError> {
+  if head.starts_with(FILE_MAGIC) {
+    return Ok(());
+  }
+  for (magic, offset, name) in KNOWN_TYPES.iter() {
+    if head.get(*offset..*offset + magic.len()) == Some(*magic) {
+      return Err(ClipError::UnexpectedFileType(name));
+    }
+  }
+  return Err(ClipError::NotClipFile);
+}
+
+/// Brief.
+///
+/// Read up to `SNIFF_SIZE` bytes for `check_file_magic`.
+pub(super) fn read_head<R: Read>(reader: &mut R) -> Result<Vec<u8>, ClipError> {
+  let mut head: Vec<u8> = Vec::with_capacity(SNIFF_SIZE);
+  if let Err(_) = reader
+    .by_ref()
+    .take(SNIFF_SIZE as u64)
+    .read_to_end(&mut head)
+  {
+    return Err(ClipError::FileReadError);
+  }
+  return Ok(head);
+}
+
+/// Brief.
+///
+/// Whether the file is a clip file, e.g. for collecting inputs in a directory.
+///
+/// Files with an extension in `CLIP_EXTENSIONS` are clip files.
+/// Other files (autosave and recovered files are saved with various names) are clip files
+/// if they start with the magic number of clip files.
+///
+/// * `path`: file path
+pub fn is_clip_file<P: AsRef<Path>>(path: P) -> bool {
+  let path = path.as_ref();
+  if let Some(ext) = path.extension() {
+    let ext = ext.to_string_lossy().to_lowercase();
+    if CLIP_EXTENSIONS.contains(&ext.as_str()) {
+      return true;
+    }
+  }
+  let mut file = match open_shared(path) {
+    Ok(x) => x,
+    Err(_) => return false,
+  };
+  return match read_head(&mut file) {
+    Ok(head) => head.starts_with(FILE_MAGIC),
+    Err(_) => false,
+  };
+}
@@ -0,0 +1,128 @@
+use super::{get_children, get_layer_roots, ClipError, ClipLayer, Collation};
+use std::path::Path;
+
+/// Brief
+///
+/// Sort layers in folders by name, from the top layer of each folder.
+///
+/// * `sqlfile`: sqlite3 file path
+/// * `folders`: names of the target folders. If empty, the top level layers are sorted.
+/// * `recursive`: sort the folders in the target folders too
+/// * `collation`: how names are compared
+///
+/// Special layers (e.g. the paper layer) keep their positions, and the other layers are sorted among them.
+/// Note that the order of layers changes the image.
+///
+/// Return.
+///
+/// number of folders whose layers were reordered
+pub fn sort_layers_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+  folders: &[&str],
+  recursive: bool,
+  collation: Collation,
+) -> Result<usize, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let roots = get_layer_roots(&conn, &mut v)?;
+
+  let mut targets: Vec<usize> = Vec::new();
+  for (i, l) in v.iter().enumerate() {
+    if l.layer_folder == 0 {
+      continue;
+    }
+    let target = if folders.is_empty() {
+      roots.contains(&l.main_id)
+    } else {
+      !roots.contains(&l.main_id) && folders.contains(&l.layer_name.as_str())
+    };
+    if target {
+      collect_folders(&v, i, recursive, &mut targets)?;
+    }
+  }
+  targets.sort_unstable();
+  targets.dedup();
+
+  let mut count = 0;
+  for index in targets {
+    if sort_folder(&conn, &v, index, collation)? {
+      count += 1;
+    }
+  }
+  return Ok(count);
+}
+
+/// Brief
+///
+/// Collect the folder and, if `recursive`, the folders in it.
+///
+/// * `index`: folder index of `v`
+/// * `out`: output. indexes of `v`
+fn collect_folders(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  recursive: bool,
+  out: &mut Vec<usize>,
+) -> Result<(), ClipError> {
+  out.push(index);
+  if !recursive {
+    return Ok(());
+  }
+  for ci in get_children(v, index)? {
+    if v[ci].layer_folder != 0 {
+      collect_folders(v, ci, true, out)?;
+    }
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Sort the children of a folder and relink them.
+///
+/// Return.
+///
+/// whether the order was changed
+fn sort_folder(
+  conn: &rusqlite::Connection,
+  v: &[Box<ClipLayer>],
+  index: usize,
+  collation: Collation,
+) -> Result<bool, ClipError> {
+  let children = get_children(v, index)?;
+  // positions of layers to be sorted, from the bottom
+  let positions: Vec<usize> = (0..children.len())
+    .filter(|x| v[children[*x]].special.is_none())
+    .collect();
+  let mut sorted: Vec<usize> = positions.iter().map(|x| children[*x]).collect();
+  // the first name in the collation is the top layer
+  sorted.sort_by(|a, b| collation.compare(&v[*b].layer_name, &v[*a].layer_name));
+  let mut order = children.clone();
+  for (p, ci) in positions.iter().zip(sorted) {
+    order[*p] = ci;
+  }
+  if order == children {
+    return Ok(false);
+  }
+
+  let first = order.first().map(|x| v[*x].main_id).unwrap_or(0);
+  if let Err(_) = conn.execute(
+    "UPDATE Layer SET LayerFirstChildIndex = $1 WHERE MainId = $2",
+    rusqlite::params![first, v[index].main_id],
+  ) {
+    return Err(ClipError::SQLError);
+  }
+  for (i, ci) in order.iter().enumerate() {
+    let next = order.get(i + 1).map(|x| v[*x].main_id).unwrap_or(0);
+    if let Err(_) = conn.execute(
+      "UPDATE Layer SET LayerNextIndex = $1 WHERE MainId = $2",
+      rusqlite::params![next, v[*ci].main_id],
+    ) {
+      return Err(ClipError::SQLError);
+    }
+  }
+  return Ok(true);
+}
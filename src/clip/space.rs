@@ -0,0 +1,65 @@
+use super::ClipError;
+use std::path::Path;
+
+/// Brief.
+///
+/// Fail fast when the file system of `dir` does not have `required` bytes free,
+/// instead of failing in the middle of writing a large file.
+/// Nothing is checked where the free space is not known.
+///
+/// * `dir`: existing directory to write
+/// * `required`: estimated bytes to write
+pub(super) fn check_space(dir: &Path, required: u64) -> Result<(), ClipError> {
+  return match available_space(dir) {
+    Some(available) if available < required => Err(ClipError::NotEnoughSpace(
+      dir.to_path_buf(),
+      required,
+      available,
+    )),
+    _ => Ok(()),
+  };
+}
+
+/// Free bytes usable by this process in the file system of `dir`. `None` if it is not known (e.g. on Windows).
+#[cfg(unix)]
+pub fn available_space(dir: &Path) -> Option<u64> {
+  return match rustix::fs::statvfs(dir) {
+    Ok(x) => Some(x.f_bavail.saturating_mul(x.f_frsize)),
+    Err(_) => None,
+  };
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_dir: &Path) -> Option<u64> {
+  return None;
+}
+
+/// Whether `a` and `b` are on the same file system, so that files are moved between them without copying.
+#[cfg(unix)]
+pub(super) fn same_file_system(a: &Path, b: &Path) -> bool {
+  use std::os::unix::fs::MetadataExt;
+  return match (std::fs::metadata(a), std::fs::metadata(b)) {
+    (Ok(x), Ok(y)) => x.dev() == y.dev(),
+    _ => false,
+  };
+}
+
+#[cfg(not(unix))]
+pub(super) fn same_file_system(_a: &Path, _b: &Path) -> bool {
+  return false;
+}
+
+/// `3_200_000_000` → `3.2 GB`
+pub fn format_size(size: u64) -> String {
+  const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+  if size < 1000 {
+    return format!("{} B", size);
+  }
+  let mut value = size as f64 / 1000.0;
+  let mut unit = 0;
+  while value >= 1000.0 && unit + 1 < UNITS.len() {
+    value /= 1000.0;
+    unit += 1;
+  }
+  return format!("{:.1} {}", value, UNITS[unit]);
+}
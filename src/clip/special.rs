@@ -0,0 +1,112 @@
+use super::{ClipError, ClipLayer};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// `LayerType` of the paper layer (用紙).
+const LAYER_TYPE_PAPER: u64 = 1584;
+/// `LayerType` of the selection layer (選択範囲レイヤー).
+const LAYER_TYPE_SELECTION: u64 = 2;
+
+/// Layers which Clip Studio Paint treats specially.
+///
+/// These layers are not renamed unless they are explicitly allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialLayer {
+  /// paper layer
+  Paper,
+  /// layer which owns rulers or guides
+  Ruler,
+  /// selection layer
+  Selection,
+}
+
+impl SpecialLayer {
+  /// all special layer types
+  pub const ALL: [SpecialLayer; 3] = [
+    SpecialLayer::Paper,
+    SpecialLayer::Ruler,
+    SpecialLayer::Selection,
+  ];
+
+  /// name used in command line options
+  pub fn name(&self) -> &'static str {
+    return match self {
+      SpecialLayer::Paper => "paper",
+      SpecialLayer::Ruler => "ruler",
+      SpecialLayer::Selection => "selection",
+    };
+  }
+}
+
+impl FromStr for SpecialLayer {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    return match SpecialLayer::ALL.iter().find(|x| x.name() == s) {
+      Some(x) => Ok(*x),
+      None => Err(format!("unknown special layer type: {}", s)),
+    };
+  }
+}
+
+/// Brief
+///
+/// Set `special` of each layer.
+///
+/// * `conn`: sqlite3
+/// * `v`: all layer information
+pub(super) fn detect_special_layers(
+  conn: &rusqlite::Connection,
+  v: &mut [Box<ClipLayer>],
+) -> Result<(), ClipError> {
+  let ruler_owners = get_ruler_owners(conn)?;
+  for l in v.iter_mut() {
+    l.special = if l.layer_type == LAYER_TYPE_PAPER {
+      Some(SpecialLayer::Paper)
+    } else if l.layer_type == LAYER_TYPE_SELECTION && l.layer_folder == 0 {
+      Some(SpecialLayer::Selection)
+    } else if ruler_owners.contains(&l.main_id) {
+      Some(SpecialLayer::Ruler)
+    } else {
+      None
+    };
+  }
+  return Ok(());
+}
+
+/// Brief
+///
+/// Collect main_id of the layers referenced from the ruler tables (`Ruler*`, `SpecialRulerManager`).
+fn get_ruler_owners(conn: &rusqlite::Connection) -> Result<HashSet<u64>, ClipError> {
+  let mut owners: HashSet<u64> = HashSet::new();
+  let tables: Vec<String> = {
+    let mut stmt = match conn.prepare(
+      "SELECT name FROM sqlite_master WHERE type = 'table' AND (name LIKE 'Ruler%' OR name = 'SpecialRulerManager')",
+    ) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| row.get::<_, String>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    rows.filter_map(|x| x.ok()).collect()
+  };
+
+  for table in tables.iter() {
+    // Tables without a LayerId column do not reference layers.
+    let mut stmt = match conn.prepare(&format!("SELECT LayerId FROM \"{}\"", table)) {
+      Ok(x) => x,
+      Err(_) => continue,
+    };
+    let rows = match stmt.query_map([], |row| row.get::<_, Option<u64>>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    for r in rows {
+      if let Ok(Some(id)) = r {
+        owners.insert(id);
+      }
+    }
+  }
+  return Ok(owners);
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+/// Brief.
+///
+/// Atomically swap `staged` and `dst` (Linux: `renameat2(RENAME_EXCHANGE)`),
+/// leaving the old content of `dst` at the `staged` path.
+///
+/// * `staged`: new file in the same directory as `dst`
+/// * `dst`: existing file to replace
+///
+/// Return.
+///
+/// `false` if swapping is not supported here (e.g. by the file system). Nothing is changed then,
+/// and the caller renames `staged` over `dst`, which is atomic too but drops the old data at once.
+/// On Windows the rename replaces the file atomically (`MoveFileEx`).
+#[cfg(target_os = "linux")]
+pub(super) fn exchange(staged: &Path, dst: &Path) -> bool {
+  use rustix::fs::{renameat_with, RenameFlags, CWD};
+  return renameat_with(CWD, staged, CWD, dst, RenameFlags::EXCHANGE).is_ok();
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn exchange(_staged: &Path, _dst: &Path) -> bool {
+  return false;
+}
+
+/// Brief.
+///
+/// Flush the directory entries of `dir` so that renames in it survive a crash.
+/// Directories cannot be synced on Windows, where this does nothing.
+pub(super) fn sync_directory(dir: &Path) {
+  #[cfg(unix)]
+  {
+    if let Ok(d) = std::fs::File::open(dir) {
+      let _ = d.sync_all();
+    }
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = dir;
+  }
+}
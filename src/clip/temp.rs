@@ -0,0 +1,54 @@
+use super::ClipError;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+/// environment variable giving the directory of temporary files
+pub const TEMP_DIR_ENV: &str = "RENAMELAYER_TMP";
+
+/// directory set by `set_temp_dir`
+static TEMP_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Brief.
+///
+/// Set the directory where the extracted sqlite3 data and the rebuilt clip files are written,
+/// e.g. the same disk as the output or a fast scratch disk.
+/// The directory is created when needed.
+///
+/// * `dir`: directory. `None` uses `RENAMELAYER_TMP` or the OS temporary directory.
+pub fn set_temp_dir(dir: Option<PathBuf>) {
+  if let Ok(mut x) = TEMP_DIR.lock() {
+    *x = dir;
+  }
+}
+
+/// Brief.
+///
+/// The directory of temporary files.
+///
+/// Return.
+///
+/// The directory given by `set_temp_dir`, `RENAMELAYER_TMP` (if not empty), or the OS temporary directory.
+pub fn temp_dir() -> PathBuf {
+  if let Ok(x) = TEMP_DIR.lock() {
+    if let Some(dir) = x.as_ref() {
+      return dir.clone();
+    }
+  }
+  return match std::env::var_os(TEMP_DIR_ENV) {
+    Some(x) if !x.is_empty() => PathBuf::from(x),
+    _ => std::env::temp_dir(),
+  };
+}
+
+/// Create a new temporary directory in `temp_dir()`.
+pub(super) fn create_temp_dir() -> Result<TempDir, ClipError> {
+  let parent = temp_dir();
+  if let Err(_) = std::fs::create_dir_all(&parent) {
+    return Err(ClipError::TmpDirError);
+  }
+  return match tempfile::tempdir_in(&parent) {
+    Ok(x) => Ok(x),
+    Err(_) => Err(ClipError::TmpDirError),
+  };
+}
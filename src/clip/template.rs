@@ -0,0 +1,437 @@
+use super::ClipError;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+/// default template of new layer names
+pub const DEFAULT_TEMPLATE: &str = "{parent} {n}";
+/// maximum digits of `{n|padN}` (digits of `u64::MAX`)
+const MAX_PAD: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+  Text(String),
+  Parent(Vec<Filter>),
+  /// the current layer name
+  Old(Vec<Filter>),
+  /// minimum number of digits (`pad`)
+  Number(usize),
+  /// UUID made from the main_id
+  Uuid,
+  /// 8 hex digits made from the main_id
+  Hash8,
+  /// layer kind (`raster`, `vector`, `other` or `folder`)
+  Kind,
+  /// number of the layer among the layers of the same kind in the folder, with minimum digits
+  KindNumber(usize),
+}
+
+/// Filter of `{parent}` and `{old}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+  Upper,
+  Lower,
+  Trim,
+}
+
+impl Filter {
+  fn apply(&self, text: &str) -> String {
+    return match self {
+      Filter::Upper => text.to_uppercase(),
+      Filter::Lower => text.to_lowercase(),
+      Filter::Trim => text.trim().to_string(),
+    };
+  }
+}
+
+/// Brief.
+///
+/// Template of new layer names.
+///
+/// * `{parent}`: name of the parent folder (top level: root layer base name)
+/// * `{old}`: the current name of the layer
+/// * `{n}`: layer number in the folder
+/// * `{uuid}`: UUID made from the main_id of the layer (e.g. `1b4e28ba-2fa1-81d2-883f-0016d3cca427`)
+/// * `{hash8}`: the first 8 hex digits of a hash of the main_id (e.g. `1b4e28ba`)
+/// * `{type}`: layer kind (`raster`, `vector`, `other`, or `folder`)
+/// * `{type_index}`: layer number among the layers of the same kind in the folder
+/// * `{{`, `}}`: `{`, `}`
+///
+/// `{uuid}` and `{hash8}` are the same for the same main_id in any file and any run,
+/// e.g. to embed stable identifiers of layers in names for pipelines.
+///
+/// Filters are written after `|` and applied from left to right:
+///
+/// * `{parent|upper}`, `{old|upper}`, `{parent|lower}`, `{old|lower}`: upper / lower case
+/// * `{parent|trim}`, `{old|trim}`: remove white spaces at both ends
+/// * `{n|pad3}`, `{type_index|pad3}`: zero padding to 3 digits (`007`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+  parts: Vec<Part>,
+}
+
+impl NameTemplate {
+  /// Brief.
+  ///
+  /// Parse a template string.
+  pub fn parse(template: &str) -> Result<NameTemplate, ClipError> {
+    let mut parts: Vec<Part> = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+      if rest.starts_with("{{") || rest.starts_with("}}") {
+        text.push(c);
+        rest = &rest[2..];
+        continue;
+      }
+      if c == '}' {
+        return Err(ClipError::InvalidTemplate(template.to_string()));
+      }
+      if c != '{' {
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+        continue;
+      }
+      let end = match rest.find('}') {
+        Some(x) => x,
+        None => return Err(ClipError::InvalidTemplate(template.to_string())),
+      };
+      let part = match parse_part(&rest[1..end]) {
+        Some(x) => x,
+        None => return Err(ClipError::InvalidTemplate(template.to_string())),
+      };
+      if !text.is_empty() {
+        parts.push(Part::Text(std::mem::take(&mut text)));
+      }
+      parts.push(part);
+      rest = &rest[end + 1..];
+    }
+    if !text.is_empty() {
+      parts.push(Part::Text(text));
+    }
+    return Ok(NameTemplate { parts });
+  }
+
+  /// Whether the template uses `{type_index}`.
+  pub fn uses_kind_number(&self) -> bool {
+    return self.parts.iter().any(|x| matches!(x, Part::KindNumber(_)));
+  }
+
+  /// Whether the template uses `{parent}`.
+  pub fn uses_parent(&self) -> bool {
+    return self.parts.iter().any(|x| matches!(x, Part::Parent(_)));
+  }
+
+  /// Brief.
+  ///
+  /// Create a layer name. `{uuid}` and `{hash8}` are made from main_id 0 and `{old}` is empty;
+  /// use `format_layer` for them.
+  ///
+  /// * `parent`: parent folder name
+  /// * `n`: layer number
+  pub fn format(&self, parent: &str, n: u64) -> String {
+    return self.format_layer(parent, n, 0, "");
+  }
+
+  /// Brief.
+  ///
+  /// Create the name of a layer.
+  ///
+  /// * `parent`: parent folder name
+  /// * `n`: layer number
+  /// * `main_id`: main_id of the layer, used by `{uuid}` and `{hash8}`
+  /// * `old`: the current layer name, `{old}`
+  pub fn format_layer(&self, parent: &str, n: u64, main_id: u64, old: &str) -> String {
+    return self.format_kind_layer(parent, n, main_id, old, "", n);
+  }
+
+  /// Brief.
+  ///
+  /// Create the name of a layer with its kind.
+  ///
+  /// * `parent`: parent folder name
+  /// * `n`: layer number
+  /// * `main_id`: main_id of the layer, used by `{uuid}` and `{hash8}`
+  /// * `old`: the current layer name, `{old}`
+  /// * `kind`: `{type}`
+  /// * `kind_n`: `{type_index}`
+  pub fn format_kind_layer(
+    &self,
+    parent: &str,
+    n: u64,
+    main_id: u64,
+    old: &str,
+    kind: &str,
+    kind_n: u64,
+  ) -> String {
+    let mut s = String::new();
+    for p in self.parts.iter() {
+      match p {
+        Part::Text(x) => s.push_str(x),
+        Part::Parent(filters) => s.push_str(&apply_filters(parent, filters)),
+        Part::Old(filters) => s.push_str(&apply_filters(old, filters)),
+        Part::Number(width) => s.push_str(&format!("{:0width$}", n, width = width)),
+        Part::Uuid => s.push_str(&layer_uuid(main_id)),
+        Part::Hash8 => s.push_str(&layer_uuid(main_id)[..8]),
+        Part::Kind => s.push_str(kind),
+        Part::KindNumber(width) => s.push_str(&format!("{:0width$}", kind_n, width = width)),
+      }
+    }
+    return s;
+  }
+
+  /// Brief.
+  ///
+  /// Create a regex which matches names created by this template in the `parent` folder.
+  /// The first capture group is the layer number. White spaces may differ.
+  ///
+  /// Return.
+  ///
+  /// `None` if the template has no `{n}`.
+  pub fn number_regex(&self, parent: &str) -> Option<Regex> {
+    return self.regex(parent, None);
+  }
+
+  /// Brief.
+  ///
+  /// Create a regex which matches names created by this template for layers of the `kind` in the `parent` folder.
+  /// The first capture group is the `{type_index}` number. White spaces may differ.
+  ///
+  /// Return.
+  ///
+  /// `None` if the template has no `{type_index}`.
+  pub fn kind_number_regex(&self, parent: &str, kind: &str) -> Option<Regex> {
+    return self.regex(parent, Some(kind));
+  }
+
+  /// `number_regex` capturing `{n}`, or `kind_number_regex` capturing `{type_index}` if `kind` is given.
+  fn regex(&self, parent: &str, kind: Option<&str>) -> Option<Regex> {
+    let captured = |p: &Part| match kind {
+      Some(_) => matches!(p, Part::KindNumber(_)),
+      None => matches!(p, Part::Number(_)),
+    };
+    if !self.parts.iter().any(captured) {
+      return None;
+    }
+    let mut s = String::from(r"^\s*");
+    let mut number = false;
+    for p in self.parts.iter() {
+      match p {
+        Part::Text(x) => s.push_str(&escape_loose(x)),
+        Part::Parent(filters) => s.push_str(&escape_loose(&apply_filters(parent, filters))),
+        Part::Old(_) => s.push_str(".*?"),
+        _ if captured(p) && !number => {
+          s.push_str(r"(\d+)");
+          number = true;
+        }
+        Part::Number(_) | Part::KindNumber(_) => s.push_str(r"\d+"),
+        Part::Uuid => s.push_str(r"[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}"),
+        Part::Hash8 => s.push_str(r"[0-9a-f]{8}"),
+        Part::Kind => match kind {
+          Some(x) => s.push_str(&regex::escape(x)),
+          None => s.push_str("(?:raster|vector|other|folder)"),
+        },
+      }
+    }
+    s.push_str(r"\s*$");
+    return Regex::new(&s).ok();
+  }
+}
+
+impl Default for NameTemplate {
+  fn default() -> Self {
+    return NameTemplate::parse(DEFAULT_TEMPLATE).unwrap();
+  }
+}
+
+/// Parse `name|filter|...` in `{}`. `None` if it is unknown.
+fn parse_part(text: &str) -> Option<Part> {
+  let mut items = text.split('|');
+  return match items.next()? {
+    name @ ("parent" | "old") => {
+      let mut filters: Vec<Filter> = Vec::new();
+      for f in items {
+        filters.push(match f {
+          "upper" => Filter::Upper,
+          "lower" => Filter::Lower,
+          "trim" => Filter::Trim,
+          _ => return None,
+        });
+      }
+      if name == "parent" {
+        Some(Part::Parent(filters))
+      } else {
+        Some(Part::Old(filters))
+      }
+    }
+    name @ ("n" | "type_index") => {
+      let mut width = 0;
+      for f in items {
+        width = f.strip_prefix("pad")?.parse::<usize>().ok()?;
+        if width > MAX_PAD {
+          return None;
+        }
+      }
+      if name == "n" {
+        Some(Part::Number(width))
+      } else {
+        Some(Part::KindNumber(width))
+      }
+    }
+    "type" if items.next().is_none() => Some(Part::Kind),
+    "uuid" if items.next().is_none() => Some(Part::Uuid),
+    "hash8" if items.next().is_none() => Some(Part::Hash8),
+    _ => None,
+  };
+}
+
+/// Brief.
+///
+/// UUID (version 8, RFC 9562) made from the SHA-256 of the main_id.
+fn layer_uuid(main_id: u64) -> String {
+  let hash = Sha256::digest(format!("renamelayer layer {}", main_id).as_bytes());
+  let mut bytes = [0u8; 16];
+  bytes.copy_from_slice(&hash[..16]);
+  bytes[6] = (bytes[6] & 0x0f) | 0x80;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  return format!(
+    "{}-{}-{}-{}-{}",
+    &hex[0..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..32]
+  );
+}
+
+fn apply_filters(text: &str, filters: &[Filter]) -> String {
+  let mut s = text.to_string();
+  for f in filters.iter() {
+    s = f.apply(&s);
+  }
+  return s;
+}
+
+/// Escape regex meta characters. White spaces match any number of white spaces.
+fn escape_loose(text: &str) -> String {
+  let mut s = String::new();
+  let mut space = false;
+  for c in text.chars() {
+    if c.is_whitespace() {
+      if !space {
+        s.push_str(r"\s*");
+      }
+      space = true;
+      continue;
+    }
+    space = false;
+    s.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+  }
+  return s;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn old_with_filters() {
+    let t = NameTemplate::parse("{old|trim|upper}_{n|pad2}").unwrap();
+    assert_eq!(t.format_layer("線画", 3, 5, " line "), "LINE_03");
+    assert_eq!(t.format("線画", 3), "_03");
+    assert!(NameTemplate::parse("{old|pad3}").is_err());
+  }
+
+  #[test]
+  fn old_in_number_regex() {
+    let t = NameTemplate::parse("{old} {n}").unwrap();
+    let re = t.number_regex("線画").unwrap();
+    assert_eq!(&re.captures("a b 12").unwrap()[1], "12");
+  }
+
+  #[test]
+  fn escapes() {
+    let t = NameTemplate::parse("{{{parent}}} {n}").unwrap();
+    assert_eq!(t.format("線画", 1), "{線画} 1");
+    assert_eq!(NameTemplate::parse("a}}b").unwrap().format("", 1), "a}b");
+  }
+
+  #[test]
+  fn invalid_templates() {
+    for text in [
+      "{",
+      "}",
+      "{n",
+      "{x}",
+      "{parent|x}",
+      "{n|pad}",
+      "{n|3}",
+      "{uuid|upper}",
+      "{type|trim}",
+    ]
+    .iter()
+    {
+      match NameTemplate::parse(text) {
+        Err(ClipError::InvalidTemplate(x)) => assert_eq!(&x, text),
+        x => panic!("{:?}: {:?}", text, x),
+      }
+    }
+  }
+
+  #[test]
+  fn pad_limit() {
+    let t = NameTemplate::parse("{n|pad20}").unwrap();
+    assert_eq!(t.format("", u64::MAX), u64::MAX.to_string());
+    assert_eq!(t.format("", 7), "00000000000000000007");
+    assert!(NameTemplate::parse("{n|pad21}").is_err());
+    assert!(NameTemplate::parse("{type_index|pad21}").is_err());
+  }
+
+  #[test]
+  fn parent_filters() {
+    let t = NameTemplate::parse("{parent|trim|upper}-{parent|lower}").unwrap();
+    assert_eq!(t.format(" Line ", 1), "LINE- line ");
+    assert!(t.uses_parent());
+    assert!(!NameTemplate::parse("{n}").unwrap().uses_parent());
+  }
+
+  #[test]
+  fn kind_and_type_index() {
+    let t = NameTemplate::parse("{type}_{type_index|pad2}_{n}").unwrap();
+    assert!(t.uses_kind_number());
+    assert_eq!(
+      t.format_kind_layer("", 5, 0, "", "raster", 2),
+      "raster_02_5"
+    );
+    let re = t.kind_number_regex("", "vector").unwrap();
+    assert_eq!(&re.captures("vector_07_5").unwrap()[1], "07");
+    assert!(re.captures("raster_07_5").is_none());
+    assert_eq!(
+      &t.number_regex("").unwrap().captures("folder_07_5").unwrap()[1],
+      "5"
+    );
+  }
+
+  #[test]
+  fn stable_ids() {
+    let t = NameTemplate::parse("{uuid} {hash8}").unwrap();
+    let name = t.format_layer("", 1, 42, "");
+    assert_eq!(name, t.format_layer("", 2, 42, ""));
+    assert_ne!(name, t.format_layer("", 1, 43, ""));
+    assert_eq!(&name[..8], &name[37..]);
+    assert_eq!(&name[14..15], "8");
+  }
+
+  #[test]
+  fn number_regex() {
+    let t = NameTemplate::default();
+    let re = t.number_regex("線画 (1)").unwrap();
+    assert_eq!(&re.captures(" 線画  (1) 12").unwrap()[1], "12");
+    assert!(re.captures("線画 12").is_none());
+    assert!(re.captures("線画 (1) 1a").is_none());
+    assert!(NameTemplate::parse("{parent}")
+      .unwrap()
+      .number_regex("")
+      .is_none());
+  }
+}
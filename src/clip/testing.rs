@@ -0,0 +1,332 @@
+use super::schema::{KNOWN_SCHEMAS, REQUIRED_TABLES};
+use super::temp::create_temp_dir;
+use super::{ClipError, FOOT_CHANK_DATA, SQL_CHANK};
+use std::path::Path;
+
+/// `LayerType` of the root folder
+const ROOT_LAYER_TYPE: u64 = 256;
+/// `LayerType` of raster layers
+const RASTER_LAYER_TYPE: u64 = 1;
+/// `LayerType` of the paper layer
+const PAPER_LAYER_TYPE: u64 = 1584;
+/// main_id of the first canvas. The root folder of the first canvas is the next.
+const CANVAS_MAIN_ID: u64 = 1;
+/// `CSFCHUNK`, file size, offset of the first chunk
+const FILE_HEADER_SIZE: u64 = 24;
+/// `CHNK`, chunk type, data size
+const CHUNK_HEADER_SIZE: u64 = 16;
+/// data of the `Head` chunk: 0x100, offset of the `SQLi` chunk, 0x10, 16 bytes id
+const HEAD_DATA_SIZE: u64 = 40;
+
+/// Brief.
+///
+/// A layer of a synthesized clip file (`ClipBuilder`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestLayer {
+  name: String,
+  layer_type: u64,
+  vector: bool,
+  /// `Some` for folders. Children are given from the bottom layer.
+  children: Option<Vec<TestLayer>>,
+}
+
+impl TestLayer {
+  /// Raster layer.
+  pub fn raster(name: &str) -> TestLayer {
+    return TestLayer::new(name, RASTER_LAYER_TYPE, false, None);
+  }
+
+  /// Vector layer.
+  pub fn vector(name: &str) -> TestLayer {
+    return TestLayer::new(name, 0, true, None);
+  }
+
+  /// Paper layer (用紙).
+  pub fn paper(name: &str) -> TestLayer {
+    return TestLayer::new(name, PAPER_LAYER_TYPE, false, None);
+  }
+
+  /// Folder. `children` are given from the bottom layer.
+  pub fn folder(name: &str, children: Vec<TestLayer>) -> TestLayer {
+    return TestLayer::new(name, 0, false, Some(children));
+  }
+
+  fn new(name: &str, layer_type: u64, vector: bool, children: Option<Vec<TestLayer>>) -> TestLayer {
+    return TestLayer {
+      name: name.to_string(),
+      layer_type,
+      vector,
+      children,
+    };
+  }
+}
+
+/// Brief.
+///
+/// Synthesize a minimal clip file with a given layer tree,
+/// e.g. to test code using this crate without binary fixtures.
+///
+/// The file has the chunk structure of Clip Studio Paint (`Head`, `SQLi` and `Foot`) and
+/// the tables and columns read by this crate. It has no pixel data and cannot be opened by Clip Studio Paint.
+/// Layers are given from the bottom layer, and main_ids are numbered from 3 in the given order (depth first).
+/// With `canvas`, the file has several canvases like a story file; the root folder of each canvas
+/// takes the main_id before its layers.
+///
+/// ```no_run
+/// use renamelayer::clip::testing::{ClipBuilder, TestLayer};
+/// use renamelayer::clip::{self, RenameOptions};
+/// ClipBuilder::new()
+///   .layer(TestLayer::paper("用紙"))
+///   .layer(TestLayer::folder(
+///     "線画",
+///     vec![TestLayer::raster("レイヤー 1"), TestLayer::vector("レイヤー 2")],
+///   ))
+///   .write("in.clip")?;
+/// clip::create_layer_renamed_clip_file("in.clip", "out.clip", &RenameOptions::new())?;
+/// # Ok::<(), clip::ClipError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClipBuilder {
+  /// top level layers of each canvas
+  canvases: Vec<Vec<TestLayer>>,
+}
+
+impl ClipBuilder {
+  /// Empty canvas.
+  pub fn new() -> ClipBuilder {
+    return ClipBuilder::default();
+  }
+
+  /// Add a top level layer above the layers added so far.
+  pub fn layer(mut self, layer: TestLayer) -> ClipBuilder {
+    if self.canvases.is_empty() {
+      self.canvases.push(Vec::new());
+    }
+    let last = self.canvases.len() - 1;
+    self.canvases[last].push(layer);
+    return self;
+  }
+
+  /// Start the next canvas. Layers added after this belong to the new canvas.
+  pub fn canvas(mut self) -> ClipBuilder {
+    if self.canvases.is_empty() {
+      self.canvases.push(Vec::new());
+    }
+    self.canvases.push(Vec::new());
+    return self;
+  }
+
+  /// Brief.
+  ///
+  /// Make the clip file data.
+  ///
+  /// Return.
+  ///
+  /// whole clip file
+  pub fn build(&self) -> Result<Vec<u8>, ClipError> {
+    let sql = self.build_sqlite()?;
+    let sql_chunk_offset = FILE_HEADER_SIZE + CHUNK_HEADER_SIZE + HEAD_DATA_SIZE;
+    let file_size =
+      sql_chunk_offset + CHUNK_HEADER_SIZE + sql.len() as u64 + FOOT_CHANK_DATA.len() as u64;
+
+    let mut data: Vec<u8> = Vec::with_capacity(file_size as usize);
+    data.extend_from_slice(b"CSFCHUNK");
+    data.extend_from_slice(&file_size.to_be_bytes());
+    data.extend_from_slice(&FILE_HEADER_SIZE.to_be_bytes());
+
+    data.extend_from_slice(b"CHNKHead");
+    data.extend_from_slice(&HEAD_DATA_SIZE.to_be_bytes());
+    data.extend_from_slice(&0x100u64.to_be_bytes());
+    data.extend_from_slice(&sql_chunk_offset.to_be_bytes());
+    data.extend_from_slice(&0x10u64.to_be_bytes());
+    data.extend_from_slice(&[0u8; 16]);
+
+    data.extend_from_slice(SQL_CHANK);
+    data.extend_from_slice(&(sql.len() as u64).to_be_bytes());
+    data.extend_from_slice(&sql);
+    data.extend_from_slice(&FOOT_CHANK_DATA);
+    return Ok(data);
+  }
+
+  /// Write the clip file (`build`).
+  pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ClipError> {
+    let data = self.build()?;
+    if let Err(_) = std::fs::write(path, data) {
+      return Err(ClipError::FileSaveError);
+    }
+    return Ok(());
+  }
+
+  /// Make the sqlite3 data base in a temporary directory and read it.
+  fn build_sqlite(&self) -> Result<Vec<u8>, ClipError> {
+    let dir = create_temp_dir()?;
+    let sql_path = dir.path().join("sql.sql");
+    let conn = match rusqlite::Connection::open(&sql_path) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    if let Err(_) = conn.execute_batch(&schema_sql()) {
+      return Err(ClipError::SQLError);
+    }
+    let empty: Vec<Vec<TestLayer>> = vec![Vec::new()];
+    let canvases = if self.canvases.is_empty() {
+      &empty
+    } else {
+      &self.canvases
+    };
+    let mut last_id = CANVAS_MAIN_ID;
+    for (canvas, layers) in (CANVAS_MAIN_ID..).zip(canvases.iter()) {
+      last_id += 1;
+      let root = last_id;
+      if let Err(_) = conn.execute(
+        "INSERT INTO Canvas (MainId, CanvasRootFolder) VALUES ($1, $2)",
+        [canvas, root],
+      ) {
+        return Err(ClipError::SQLError);
+      }
+      let first_child = insert_layers(&conn, canvas, layers, &mut last_id)?;
+      insert_layer(
+        &conn,
+        canvas,
+        root,
+        "",
+        ROOT_LAYER_TYPE,
+        true,
+        false,
+        0,
+        first_child,
+      )?;
+    }
+    // the largest main_id of each table, read when layers are added
+    if let Err(_) = conn.execute(
+      "INSERT INTO ElemScheme (TableName, ElemType, MaxIndex) VALUES ('Layer', 4, $1), ('Canvas', 2, $2)",
+      [last_id, canvases.len() as u64],
+    ) {
+      return Err(ClipError::SQLError);
+    }
+    if let Err(_) = conn.close() {
+      return Err(ClipError::SQLError);
+    }
+    return match std::fs::read(&sql_path) {
+      Ok(x) => Ok(x),
+      Err(_) => Err(ClipError::FileReadError),
+    };
+  }
+}
+
+/// Brief
+///
+/// `CREATE TABLE` of the Layer table of the latest known schema, the other required tables
+/// and `ElemScheme`, so that the file passes `ClipDocument::check_schema` and layers can be added.
+fn schema_sql() -> String {
+  let mut tables: Vec<(&str, Vec<(&str, &str)>)> = vec![(
+    "Layer",
+    KNOWN_SCHEMAS[KNOWN_SCHEMAS.len() - 1]
+      .layer_columns
+      .to_vec(),
+  )];
+  for (table, columns) in REQUIRED_TABLES.iter() {
+    let columns = columns
+      .iter()
+      .map(|x| (*x, if *x == "BlockData" { "BLOB" } else { "INTEGER" }))
+      .collect();
+    tables.push((table, columns));
+  }
+  tables.push((
+    "ElemScheme",
+    vec![
+      ("TableName", "TEXT"),
+      ("ElemType", "INTEGER"),
+      ("MaxIndex", "INTEGER"),
+    ],
+  ));
+  let mut sql = String::new();
+  for (table, columns) in tables.iter() {
+    let columns: Vec<String> = columns
+      .iter()
+      .filter(|(name, _)| *name != "_PW_ID")
+      .map(|(name, ty)| format!("{} {} DEFAULT NULL", name, ty))
+      .collect();
+    sql.push_str(&format!(
+      "CREATE TABLE {}(_PW_ID INTEGER PRIMARY KEY AUTOINCREMENT, {});\n",
+      table,
+      columns.join(", ")
+    ));
+  }
+  return sql;
+}
+
+/// Brief
+///
+/// Insert sibling layers and their descendants.
+///
+/// * `canvas`: main_id of the canvas
+/// * `last_id`: the last used main_id
+///
+/// Return.
+///
+/// main_id of the first (bottom) layer, 0 if `layers` is empty
+fn insert_layers(
+  conn: &rusqlite::Connection,
+  canvas: u64,
+  layers: &[TestLayer],
+  last_id: &mut u64,
+) -> Result<u64, ClipError> {
+  let ids: Vec<u64> = layers
+    .iter()
+    .map(|_| {
+      *last_id += 1;
+      *last_id
+    })
+    .collect();
+  for (i, l) in layers.iter().enumerate() {
+    let first_child = match &l.children {
+      Some(children) => insert_layers(conn, canvas, children, last_id)?,
+      None => 0,
+    };
+    let next = ids.get(i + 1).copied().unwrap_or(0);
+    insert_layer(
+      conn,
+      canvas,
+      ids[i],
+      &l.name,
+      l.layer_type,
+      l.children.is_some(),
+      l.vector,
+      next,
+      first_child,
+    )?;
+  }
+  return Ok(ids.first().copied().unwrap_or(0));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_layer(
+  conn: &rusqlite::Connection,
+  canvas: u64,
+  main_id: u64,
+  name: &str,
+  layer_type: u64,
+  folder: bool,
+  vector: bool,
+  next: u64,
+  first_child: u64,
+) -> Result<(), ClipError> {
+  if let Err(_) = conn.execute(
+    "INSERT INTO Layer (MainId, CanvasId, LayerName, LayerType, LayerLock, LayerUsePaletteColor, LayerFolder, LayerVisibility, LayerNextIndex, LayerFirstChildIndex, LayerUuid, VectorNormalType) VALUES ($1, $2, $3, $4, 0, 0, $5, 1, $6, $7, $8, $9)",
+    rusqlite::params![
+      main_id,
+      canvas,
+      name,
+      layer_type,
+      folder as u64,
+      next,
+      first_child,
+      format!("{:010x}-0000-0000-0000-000000000000", main_id),
+      if vector { Some(0u64) } else { None },
+    ],
+  ) {
+    return Err(ClipError::SQLError);
+  }
+  return Ok(());
+}
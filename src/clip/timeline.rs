@@ -0,0 +1,158 @@
+use super::material::get_columns;
+use super::{
+  collect_descendants, find_layer_index, plan_rename, ClipError, ClipLayer, RenameOptions,
+  RenameReport,
+};
+use std::collections::{BTreeMap, HashSet};
+
+/// Brief
+///
+/// Collect the frame ranges of cels placed on the timeline.
+///
+/// Timeline tables (names containing `TimeLine`, `Track` or `Cel`) are searched for
+/// a layer column (`LayerId`, or a name ending with `LayerId` or `Layer`) and frame columns:
+/// a start column (ending with `StartFrame` or named `Frame`) and optionally
+/// an end column (ending with `EndFrame`) or a length column (ending with `Length` or `Duration`).
+/// A row without an end or a length is one frame.
+/// All rows of a layer are merged into one range from the first to the last frame.
+/// Frame numbers are used as stored in the file.
+///
+/// Return.
+///
+/// `(first frame, last frame)` for each layer main_id
+pub(super) fn get_frame_ranges(
+  conn: &rusqlite::Connection,
+) -> Result<BTreeMap<u64, (i64, i64)>, ClipError> {
+  let mut ranges: BTreeMap<u64, (i64, i64)> = BTreeMap::new();
+  let tables: Vec<String> = {
+    let mut stmt = match conn.prepare(
+      "SELECT name FROM sqlite_master WHERE type = 'table' AND (name LIKE '%TimeLine%' OR name LIKE '%Track%' OR name LIKE '%Cel%')",
+    ) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| row.get::<_, String>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    rows.filter_map(|x| x.ok()).collect()
+  };
+
+  for table in tables.iter() {
+    let columns = get_columns(conn, table)?;
+    let find = |f: &dyn Fn(&str) -> bool| columns.iter().find(|x| f(x)).cloned();
+    let layer = match find(&|x| x == "LayerId" || x.ends_with("LayerId") || x.ends_with("Layer")) {
+      Some(x) => x,
+      None => continue,
+    };
+    let start = match find(&|x| x.ends_with("StartFrame") || x == "Frame") {
+      Some(x) => x,
+      None => continue,
+    };
+    let end = match find(&|x| x.ends_with("EndFrame")) {
+      Some(x) => format!("\"{}\"", x),
+      None => match find(&|x| x.ends_with("Length") || x.ends_with("Duration")) {
+        // the last frame of `length` frames
+        Some(x) => format!("\"{}\" + \"{}\" - 1", start, x),
+        None => format!("\"{}\"", start),
+      },
+    };
+    let mut stmt = match conn.prepare(&format!(
+      "SELECT \"{}\", \"{}\", {} FROM \"{}\"",
+      layer, start, end, table
+    )) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| {
+      Ok((
+        row.get::<_, Option<u64>>(0)?,
+        row.get::<_, Option<i64>>(1)?,
+        row.get::<_, Option<i64>>(2)?,
+      ))
+    }) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    for r in rows {
+      if let Ok((Some(id), Some(first), Some(last))) = r {
+        let last = std::cmp::max(first, last);
+        let range = ranges.entry(id).or_insert((first, last));
+        range.0 = std::cmp::min(range.0, first);
+        range.1 = std::cmp::max(range.1, last);
+      }
+    }
+  }
+  return Ok(ranges);
+}
+
+/// Brief
+///
+/// Add the frame range to the names of cels: "a" → "a (1-8)", "b" (one frame) → "b (9)".
+/// A range added before is replaced, so the names follow the timeline when run again.
+/// The pattern is not used; protected layers and special layers are kept.
+/// Only the cels in the canvas of `root_index` are renamed, but all cels of the file are counted.
+///
+/// * `conn`: sqlite3
+/// * `v`: all layer information
+/// * `root_index`: root folder index of `v`
+/// * `options`: options of renaming
+pub(super) fn rename_cels(
+  conn: &rusqlite::Connection,
+  v: &[Box<ClipLayer>],
+  root_index: usize,
+  options: &RenameOptions,
+) -> Result<RenameReport, ClipError> {
+  let ranges = get_frame_ranges(conn)?;
+  let mut canvas: Vec<u64> = Vec::new();
+  collect_descendants(v, root_index, &mut canvas)?;
+  let canvas: HashSet<u64> = canvas.into_iter().collect();
+  let mut report = RenameReport {
+    total_layers: 0,
+    renamed: Vec::new(),
+    warnings: Vec::new(),
+  };
+  for (id, (first, last)) in ranges {
+    let layer = match find_layer_index(v, id) {
+      Some(i) => &v[i],
+      // tracks may refer to other objects than layers
+      None => continue,
+    };
+    report.total_layers += 1;
+    if !canvas.contains(&id)
+      || layer
+        .special
+        .is_some_and(|x| !options.allow_special.contains(&x))
+      || options.rules.is_protected(&layer.layer_name)
+    {
+      continue;
+    }
+    let range = if first == last {
+      first.to_string()
+    } else {
+      format!("{}-{}", first, last)
+    };
+    let name = format!("{} ({})", strip_frame_range(&layer.layer_name), range);
+    if name != layer.layer_name {
+      plan_rename(layer, &name, &mut report);
+    }
+  }
+  return Ok(report);
+}
+
+/// "a (1-8)" → "a", "b (9)" → "b"
+fn strip_frame_range(name: &str) -> &str {
+  if !name.ends_with(')') {
+    return name;
+  }
+  let open = match name.rfind(" (") {
+    Some(x) => x,
+    None => return name,
+  };
+  let range = &name[open + 2..name.len() - 1];
+  let is_range = !range.is_empty()
+    && range
+      .split('-')
+      .all(|x| !x.is_empty() && x.bytes().all(|b| b.is_ascii_digit()));
+  return if is_range { &name[..open] } else { name };
+}
@@ -0,0 +1,89 @@
+use super::{
+  find_layer_index, get_children, get_layer_roots, ClipError, ClipLayer, LayerKind, SpecialLayer,
+};
+use std::path::Path;
+
+/// Brief.
+///
+/// A layer in the layer tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerInfo {
+  /// main_id of the layer
+  pub main_id: u64,
+  /// layer name
+  pub name: String,
+  /// whether the layer is a folder
+  pub folder: bool,
+  /// kind of the layer (`None` for folders)
+  pub kind: Option<LayerKind>,
+  /// kind of the special layer, which is not renamed by default (`None` for normal layers)
+  pub special: Option<SpecialLayer>,
+  /// depth in the tree. Top level layers are 0.
+  pub depth: usize,
+  /// names of the folders containing the layer from the top level, followed by the name of the layer
+  pub path: Vec<String>,
+}
+
+impl LayerInfo {
+  /// `path` joined with `/` (e.g. `線画/顔/レイヤー 3`)
+  pub fn path_string(&self) -> String {
+    return self.path.join("/");
+  }
+}
+
+/// Brief
+///
+/// List all layers (except the root folder) in the order of the layer palette of Clip Studio Paint:
+/// from the top, folders are followed by their contents.
+/// The layers of several canvases (e.g. a story file) follow one another in the order of the Canvas table.
+///
+/// * `sqlfile`: sqlite3 file path
+pub fn list_layers_in_sqlite<P: AsRef<Path>>(sqlfile: P) -> Result<Vec<LayerInfo>, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  return list_layers_in(&conn);
+}
+
+/// `list_layers_in_sqlite` with a connection.
+pub(super) fn list_layers_in(conn: &rusqlite::Connection) -> Result<Vec<LayerInfo>, ClipError> {
+  let mut v: Vec<Box<ClipLayer>> = Vec::new();
+  let mut out: Vec<LayerInfo> = Vec::new();
+  for root in get_layer_roots(conn, &mut v)? {
+    let root_index = match find_layer_index(&v, root) {
+      Some(x) => x,
+      None => return Err(ClipError::UnknownFileStruct),
+    };
+    list_layers_in_folder(&v, root_index, &[], &mut out)?;
+  }
+  return Ok(out);
+}
+
+/// * `folders`: names of the folders containing the folder of `index`, and the folder itself
+fn list_layers_in_folder(
+  v: &[Box<ClipLayer>],
+  index: usize,
+  folders: &[String],
+  out: &mut Vec<LayerInfo>,
+) -> Result<(), ClipError> {
+  for ci in get_children(v, index)?.into_iter().rev() {
+    let c = &v[ci];
+    let folder = c.layer_folder != 0;
+    let mut path = folders.to_vec();
+    path.push(c.layer_name.clone());
+    out.push(LayerInfo {
+      main_id: c.main_id,
+      name: c.layer_name.clone(),
+      folder,
+      kind: if folder { None } else { Some(c.kind) },
+      special: c.special,
+      depth: folders.len(),
+      path: path.clone(),
+    });
+    if folder {
+      list_layers_in_folder(v, ci, &path, out)?;
+    }
+  }
+  return Ok(());
+}
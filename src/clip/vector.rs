@@ -0,0 +1,126 @@
+use super::material::{get_columns, get_text_columns};
+use super::ClipError;
+
+/// Brief
+///
+/// Find the auxiliary name fields of vector layers.
+///
+/// Vector layer data (stroke/object lists) may be kept in tables whose names contain "Vector"
+/// with a `LayerId` column. Their `TEXT` columns whose names end with `Name` are assumed to hold
+/// a copy of the layer name. This is not a known schema: the sample files have no such table,
+/// so it is only used with `RenameOptions::vector_names`.
+///
+/// Return.
+///
+/// `(table, column)` list
+pub(super) fn get_vector_name_columns(
+  conn: &rusqlite::Connection,
+) -> Result<Vec<(String, String)>, ClipError> {
+  let tables: Vec<String> = {
+    let mut stmt = match conn
+      .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE '%Vector%'")
+    {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    let rows = match stmt.query_map([], |row| row.get::<_, String>(0)) {
+      Ok(x) => x,
+      Err(_) => return Err(ClipError::SQLError),
+    };
+    rows.filter_map(|x| x.ok()).collect()
+  };
+
+  let mut v: Vec<(String, String)> = Vec::new();
+  for table in tables {
+    let columns = get_columns(conn, &table)?;
+    if !columns.iter().any(|x| x == "LayerId") {
+      continue;
+    }
+    for c in get_text_columns(conn, &table)? {
+      if c.ends_with("Name") {
+        v.push((table.clone(), c));
+      }
+    }
+  }
+  return Ok(v);
+}
+
+/// Brief
+///
+/// Update the auxiliary name fields of a renamed vector layer.
+/// Only fields holding the old layer name are changed.
+///
+/// * `columns`: `get_vector_name_columns`
+/// * `main_id`: main_id of the layer
+/// * `old_name`: layer name before renaming
+/// * `new_name`: layer name after renaming
+pub(super) fn rename_vector_names(
+  conn: &rusqlite::Connection,
+  columns: &[(String, String)],
+  main_id: u64,
+  old_name: &str,
+  new_name: &str,
+) -> Result<(), ClipError> {
+  for (table, column) in columns {
+    if let Err(_) = conn.execute(
+      &format!(
+        "UPDATE \"{}\" SET \"{}\" = $1 WHERE LayerId = $2 AND \"{}\" = $3",
+        table, column, column
+      ),
+      rusqlite::params![new_name, main_id, old_name],
+    ) {
+      return Err(ClipError::SQLError);
+    }
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn vector_name_columns() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "CREATE TABLE VectorObjectList (LayerId INTEGER, ObjectName TEXT, BrushName BLOB, Data BLOB);
+         CREATE TABLE VectorCache (CacheName TEXT);
+         CREATE TABLE Other (LayerId INTEGER, OtherName TEXT);",
+      )
+      .unwrap();
+    assert_eq!(
+      get_vector_name_columns(&conn).unwrap(),
+      vec![("VectorObjectList".to_string(), "ObjectName".to_string())]
+    );
+  }
+
+  #[test]
+  fn rename_only_old_names() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "CREATE TABLE VectorObjectList (LayerId INTEGER, ObjectName TEXT);
+         INSERT INTO VectorObjectList VALUES (1, 'a'), (1, 'b'), (2, 'a');",
+      )
+      .unwrap();
+    let columns = get_vector_name_columns(&conn).unwrap();
+    rename_vector_names(&conn, &columns, 1, "a", "c").unwrap();
+    let mut stmt = conn
+      .prepare("SELECT LayerId, ObjectName FROM VectorObjectList ORDER BY rowid")
+      .unwrap();
+    let rows: Vec<(u64, String)> = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+      .unwrap()
+      .map(|x| x.unwrap())
+      .collect();
+    assert_eq!(
+      rows,
+      vec![
+        (1, "c".to_string()),
+        (1, "b".to_string()),
+        (2, "a".to_string())
+      ]
+    );
+  }
+}
@@ -0,0 +1,110 @@
+use super::lock::LAYER_LOCK_BIT;
+use super::material::get_columns;
+use super::tree::list_layers_in;
+use super::ClipError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// `LayerOpacity` of a fully opaque layer
+const OPAQUE: u64 = 256;
+
+/// Brief.
+///
+/// Display state of a layer or folder, a row of the visibility matrix (`list_layer_states_in_sqlite`).
+///
+/// A field is `None` if the file has no column of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LayerState {
+  /// main_id of the layer
+  pub main_id: u64,
+  /// names of the folders containing the layer and the layer joined with `/`
+  pub path: String,
+  /// whether the layer is a folder
+  pub folder: bool,
+  /// shown in the canvas (`LayerVisibility`)
+  pub visible: Option<bool>,
+  /// opacity in percent as shown in the layer palette (`LayerOpacity`)
+  pub opacity: Option<u64>,
+  /// blending mode (`LayerComposite`). 0 is normal.
+  pub blend: Option<u64>,
+  /// "lock layer" is set (`LayerLock`)
+  pub locked: Option<bool>,
+  /// clipped to the layer below (`LayerClip`)
+  pub clipping: Option<bool>,
+}
+
+/// Brief
+///
+/// List the display states of all layers and folders in the order of `list_layers_in_sqlite`,
+/// e.g. to review hidden, translucent or locked layers before delivering a file.
+///
+/// * `sqlfile`: sqlite3 file path
+pub fn list_layer_states_in_sqlite<P: AsRef<Path>>(
+  sqlfile: P,
+) -> Result<Vec<LayerState>, ClipError> {
+  let conn = match rusqlite::Connection::open(sqlfile) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let layers = list_layers_in(&conn)?;
+  let columns = get_columns(&conn, "Layer")?;
+  let column = |name: &'static str| -> &str {
+    if columns.iter().any(|x| x == name) {
+      name
+    } else {
+      "NULL"
+    }
+  };
+  let sql = format!(
+    "SELECT MainId, {}, {}, {}, {}, {} FROM Layer",
+    column("LayerVisibility"),
+    column("LayerOpacity"),
+    column("LayerComposite"),
+    column("LayerLock"),
+    column("LayerClip")
+  );
+  let mut stmt = match conn.prepare(&sql) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let rows = match stmt.query_map([], |row| {
+    Ok((
+      row.get::<_, u64>(0)?,
+      [
+        row.get::<_, Option<u64>>(1)?,
+        row.get::<_, Option<u64>>(2)?,
+        row.get::<_, Option<u64>>(3)?,
+        row.get::<_, Option<u64>>(4)?,
+        row.get::<_, Option<u64>>(5)?,
+      ],
+    ))
+  }) {
+    Ok(x) => x,
+    Err(_) => return Err(ClipError::SQLError),
+  };
+  let mut values: HashMap<u64, [Option<u64>; 5]> = HashMap::new();
+  for r in rows {
+    match r {
+      Ok((id, x)) => values.insert(id, x),
+      Err(_) => return Err(ClipError::SQLError),
+    };
+  }
+
+  let mut states: Vec<LayerState> = Vec::with_capacity(layers.len());
+  for l in layers {
+    let [visibility, opacity, composite, lock, clip] =
+      values.get(&l.main_id).cloned().unwrap_or_default();
+    states.push(LayerState {
+      main_id: l.main_id,
+      path: l.path_string(),
+      folder: l.folder,
+      visible: visibility.map(|x| x & 1 != 0),
+      opacity: opacity.map(|x| (x * 100 + OPAQUE / 2) / OPAQUE),
+      blend: composite,
+      locked: lock.map(|x| x & LAYER_LOCK_BIT != 0),
+      clipping: clip.map(|x| x != 0),
+    });
+  }
+  return Ok(states);
+}
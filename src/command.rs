@@ -0,0 +1,994 @@
+use crate::args::{Args, OptionSpec};
+use renamelayer::clip;
+use sha2::{Digest, Sha256};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub mod affix;
+pub mod audit;
+pub mod batch;
+pub mod check;
+pub mod comment;
+pub mod conform;
+pub mod doctor;
+pub mod inspect;
+pub mod lint;
+pub mod lock;
+pub mod panel;
+pub mod project;
+pub mod provenance;
+pub mod rename;
+pub mod snapshot;
+pub mod sort;
+pub mod test_pattern;
+pub mod tui;
+pub mod version;
+
+/// `(backup, original, previous)` of the input backed up by `prepare_io` last.
+/// `previous` is the former file at the backup path moved aside, put back if the backup is undone.
+static BACKUP: Mutex<Option<(PathBuf, PathBuf, Option<PathBuf>)>> = Mutex::new(None);
+/// `(backup, original, keep)` of the versioned backup created by `prepare_io` last,
+/// whose old backups are removed by `commit_backup`
+static PRUNE: Mutex<Option<(PathBuf, PathBuf, usize)>> = Mutex::new(None);
+/// lock of the output taken by `prepare_io` last
+static OUTPUT_LOCK: Mutex<Option<OutputLock>> = Mutex::new(None);
+/// empty output file created by `prepare_io` last to reserve the name in an output directory
+static RESERVED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Brief.
+///
+/// Install the Ctrl-C handler.
+///
+/// On interrupt, temporary files and partially written outputs are removed,
+/// and the backup of the input is removed unless the output already replaced the input.
+pub fn install_interrupt_handler() {
+  let result = ctrlc::set_handler(|| {
+    clip::remove_temporary_files();
+    restore_backup();
+    release_output();
+    println!("interrupted");
+    std::process::exit(130);
+  });
+  if let Err(e) = result {
+    println!("Fail to install Ctrl-C handler :{}", e);
+  }
+}
+
+/// Brief.
+///
+/// Undo the backup created by `prepare_io` when the input was not replaced.
+///
+/// The backup is removed if the input still has the same data,
+/// and moved back if the input is missing.
+/// A former backup overwritten by `prepare_io` is put back in its place.
+pub fn restore_backup() {
+  let backup = match BACKUP.lock() {
+    Ok(mut x) => x.take(),
+    Err(_) => None,
+  };
+  if let Ok(mut x) = PRUNE.lock() {
+    x.take();
+  }
+  remove_reserved();
+  if let Some((backup, original, previous)) = backup {
+    if !original.exists() {
+      let _ = fs::rename(&backup, &original);
+    } else if let (Ok(x), Ok(y)) = (file_hash(&backup), file_hash(&original)) {
+      if x == y {
+        let _ = fs::remove_file(clip::extended_path(&backup));
+      }
+    }
+    if let Some(previous) = previous {
+      if backup.exists() {
+        // the input was replaced and the backup is needed
+        let _ = fs::remove_file(clip::extended_path(&previous));
+      } else {
+        let _ = fs::rename(clip::extended_path(&previous), clip::extended_path(&backup));
+      }
+    }
+  }
+}
+
+/// Brief.
+///
+/// Keep the backup created by `prepare_io` after the output was written,
+/// and remove old versioned backups of the input (`--backup-keep`).
+pub fn commit_backup() {
+  let backup = match BACKUP.lock() {
+    Ok(mut x) => x.take(),
+    Err(_) => None,
+  };
+  if let Some((_, _, Some(previous))) = backup {
+    let _ = fs::remove_file(clip::extended_path(&previous));
+  }
+  let prune = match PRUNE.lock() {
+    Ok(mut x) => x.take(),
+    Err(_) => None,
+  };
+  if let Some((backup, original, keep)) = prune {
+    remove_old_backups(&backup, &original, keep);
+  }
+}
+
+/// Brief.
+///
+/// Call `restore_backup` when dropped, unless `keep` is called after the output is written.
+///
+/// Created right after `prepare_io`, so that every error returned before the output is written
+/// undoes the backup.
+pub struct BackupGuard {
+  armed: bool,
+}
+
+impl BackupGuard {
+  /// Guard the backup created by `prepare_io`.
+  pub fn guard() -> BackupGuard {
+    return BackupGuard { armed: true };
+  }
+
+  /// Keep the backup (`commit_backup`): the output was written.
+  pub fn keep(mut self) {
+    self.armed = false;
+    commit_backup();
+  }
+}
+
+impl Drop for BackupGuard {
+  fn drop(&mut self) {
+    if self.armed {
+      restore_backup();
+    }
+  }
+}
+
+/// Brief.
+///
+/// Release the lock of the output taken by `prepare_io`,
+/// and remove the output reserved in an output directory if nothing was written to it.
+pub fn release_output() {
+  remove_reserved();
+  if let Ok(mut x) = OUTPUT_LOCK.lock() {
+    x.take();
+  }
+}
+
+/// Brief.
+///
+/// `release_output` and exit the process. Sub commands exit with this function,
+/// because `std::process::exit` does not run the destructors of the lock.
+///
+/// * `code`: exit code
+pub fn exit(code: i32) -> ! {
+  release_output();
+  std::process::exit(code);
+}
+
+/// Remove the reserved output (`RESERVED`) if it is still empty.
+fn remove_reserved() {
+  let reserved = match RESERVED.lock() {
+    Ok(mut x) => x.take(),
+    Err(_) => None,
+  };
+  if let Some(path) = reserved {
+    if fs::metadata(&path).is_ok_and(|x| x.len() == 0) {
+      let _ = fs::remove_file(&path);
+    }
+  }
+}
+
+/// Brief.
+///
+/// Exclusive lock of an output file, so that processes writing the same file (e.g. parallel CI jobs)
+/// run one after another instead of overwriting each other's output and backup.
+///
+/// The lock is taken by the OS on `.<file name>.lock` next to the output, which is removed when the lock is released.
+/// The OS releases the lock when the process ends, so a lock file left by a crash does not block later runs.
+struct OutputLock {
+  path: PathBuf,
+  _file: fs::File,
+}
+
+impl OutputLock {
+  /// Brief.
+  ///
+  /// Lock the output file, waiting for the other process holding the lock.
+  ///
+  /// The lock is not taken on file systems which do not support file locks.
+  fn acquire(output: &Path) -> Result<OutputLock, String> {
+    let mut name = OsString::from(".");
+    name.push(output.file_name().unwrap_or(OsStr::new("output")));
+    name.push(".lock");
+    let path = output.with_file_name(name);
+    if let Some(dir) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+      if let Err(e) = fs::create_dir_all(clip::extended_path(dir)) {
+        return Err(format!("cannot create {}: {}", dir.display(), e));
+      }
+    }
+    let file = lock_file(&path, output)?;
+    return Ok(OutputLock { path, _file: file });
+  }
+}
+
+impl Drop for OutputLock {
+  fn drop(&mut self) {
+    // removed before the file is closed and unlocked (on Windows, the file is deleted when closed)
+    let _ = fs::remove_file(clip::extended_path(&self.path));
+  }
+}
+
+/// Open and `flock` the lock file.
+#[cfg(unix)]
+fn lock_file(path: &Path, output: &Path) -> Result<fs::File, String> {
+  use rustix::fs::{flock, FlockOperation};
+  use std::os::unix::fs::MetadataExt;
+  let mut waiting = false;
+  loop {
+    let file = match fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(path)
+    {
+      Ok(x) => x,
+      Err(e) => return Err(format!("cannot create {}: {}", path.display(), e)),
+    };
+    match flock(&file, FlockOperation::NonBlockingLockExclusive) {
+      Ok(_) => {}
+      Err(rustix::io::Errno::WOULDBLOCK) => {
+        if !waiting {
+          println!("waiting for another process writing {}", output.display());
+          waiting = true;
+        }
+        if let Err(e) = flock(&file, FlockOperation::LockExclusive) {
+          return Err(format!("cannot lock {}: {}", path.display(), e));
+        }
+      }
+      // e.g. a network file system without locks
+      Err(rustix::io::Errno::NOLCK) | Err(rustix::io::Errno::OPNOTSUPP) => return Ok(file),
+      Err(e) => return Err(format!("cannot lock {}: {}", path.display(), e)),
+    }
+    // the former holder removes the lock file when it releases the lock
+    let same = match (file.metadata(), fs::metadata(path)) {
+      (Ok(x), Ok(y)) => x.dev() == y.dev() && x.ino() == y.ino(),
+      _ => false,
+    };
+    if same {
+      return Ok(file);
+    }
+  }
+}
+
+/// Open the lock file without sharing. Other processes cannot open it until it is closed.
+#[cfg(windows)]
+fn lock_file(path: &Path, output: &Path) -> Result<fs::File, String> {
+  use std::os::windows::fs::OpenOptionsExt;
+  const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+  const ERROR_SHARING_VIOLATION: i32 = 32;
+  let mut waiting = false;
+  loop {
+    let opened = fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .share_mode(0)
+      .custom_flags(FILE_FLAG_DELETE_ON_CLOSE)
+      .open(clip::extended_path(path));
+    match opened {
+      Ok(x) => return Ok(x),
+      Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => {
+        if !waiting {
+          println!("waiting for another process writing {}", output.display());
+          waiting = true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+      }
+      Err(e) => return Err(format!("cannot create {}: {}", path.display(), e)),
+    }
+  }
+}
+
+/// No lock on the other platforms.
+#[cfg(not(any(unix, windows)))]
+fn lock_file(path: &Path, _output: &Path) -> Result<fs::File, String> {
+  return match fs::OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(path)
+  {
+    Ok(x) => Ok(x),
+    Err(e) => Err(format!("cannot create {}: {}", path.display(), e)),
+  };
+}
+
+/// Take the lock of the output (`OutputLock`) until the next `prepare_io` or `release_output`.
+fn lock_output(output: &Path) -> Result<(), String> {
+  let lock = OutputLock::acquire(output)?;
+  if let Ok(mut x) = OUTPUT_LOCK.lock() {
+    *x = Some(lock);
+  }
+  return Ok(());
+}
+
+/// Whether `backup` is the backup of `original` created by `prepare_io`.
+fn is_backup_of(backup: &Path, original: &Path) -> bool {
+  return match BACKUP.lock() {
+    Ok(x) => x
+      .as_ref()
+      .is_some_and(|(b, o, _)| b == backup && o == original),
+    Err(_) => false,
+  };
+}
+
+/// options limiting the input files
+pub const LIMIT_OPTIONS: &[OptionSpec] = &[
+  ("--max-layers", true),
+  ("--max-file-size", true),
+  ("--strict", false),
+];
+
+/// Brief.
+///
+/// Guards against too large inputs (`LIMIT_OPTIONS`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+  /// maximum number of layers (`--max-layers`)
+  pub max_layers: Option<usize>,
+  /// maximum input file size in bytes (`--max-file-size`)
+  pub max_file_size: Option<u64>,
+  /// refuse files whose data base schema is not known (`--strict`)
+  pub strict: bool,
+}
+
+impl Limits {
+  pub fn from_args(args: &Args) -> Result<Limits, String> {
+    let max_layers = match args.value("--max-layers") {
+      Some(x) => match x.parse() {
+        Ok(n) => Some(n),
+        Err(_) => return Err(format!("invalid --max-layers: {}", x)),
+      },
+      None => None,
+    };
+    let max_file_size = match args.value("--max-file-size") {
+      Some(x) => match parse_size(x) {
+        Some(n) => Some(n),
+        None => return Err(format!("invalid --max-file-size: {} (e.g. 500M)", x)),
+      },
+      None => None,
+    };
+    return Ok(Limits {
+      max_layers,
+      max_file_size,
+      strict: args.flag("--strict"),
+    });
+  }
+
+  /// Check the size of the input file. Call this before opening the file.
+  pub fn check_file(&self, input: &Path) -> Result<(), String> {
+    let max = match self.max_file_size {
+      Some(x) => x,
+      None => return Ok(()),
+    };
+    let size = match fs::metadata(input) {
+      Ok(x) => x.len(),
+      // reported when the file is opened
+      Err(_) => return Ok(()),
+    };
+    if size > max {
+      return Err(format!(
+        "{} is too large: {} bytes (--max-file-size {})",
+        input.display(),
+        size,
+        max
+      ));
+    }
+    return Ok(());
+  }
+
+  /// Check the schema and the number of layers of the opened file. Call this before changing it.
+  pub fn check_document(&self, doc: &clip::ClipDocument) -> Result<(), String> {
+    if self.strict {
+      if let Err(e) = doc.check_schema() {
+        return Err(e.to_string());
+      }
+    }
+    let max = match self.max_layers {
+      Some(x) => x,
+      None => return Ok(()),
+    };
+    let mut layers = match doc.layers() {
+      Ok(x) => x,
+      Err(e) => return Err(e.to_string()),
+    };
+    let count = match layers.iter() {
+      Ok(x) => x.count(),
+      Err(e) => return Err(e.to_string()),
+    };
+    if count > max {
+      return Err(format!("too many layers: {} (--max-layers {})", count, max));
+    }
+    return Ok(());
+  }
+}
+
+/// Parse a size like `1048576`, `512K`, `500M` or `2G` (powers of 1024).
+fn parse_size(text: &str) -> Option<u64> {
+  let text = text.trim();
+  let (number, unit) = match text.char_indices().last() {
+    Some((i, c)) if c.is_ascii_alphabetic() => (&text[..i], c.to_ascii_uppercase()),
+    _ => (text, 'B'),
+  };
+  let scale: u64 = match unit {
+    'B' => 1,
+    'K' => 1 << 10,
+    'M' => 1 << 20,
+    'G' => 1 << 30,
+    _ => return None,
+  };
+  return number.trim().parse::<u64>().ok()?.checked_mul(scale);
+}
+
+/// Brief.
+///
+/// How the output is written when nothing in the input needs to be changed (`--unchanged`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unchanged {
+  /// rebuild the output from the extracted data as usual
+  Rebuild,
+  /// copy the input (reflink on file systems which support it)
+  Copy,
+  /// hard link the output to the input, or copy if it is not possible
+  Link,
+  /// write no output. With `--in-place`, the input is left as it is without a backup.
+  Skip,
+}
+
+impl Unchanged {
+  /// `--unchanged`. The default is `Copy`, or `Rebuild` with `--compact`.
+  pub fn from_args(args: &Args) -> Result<Unchanged, String> {
+    return match args.value("--unchanged") {
+      None if args.flag("--compact") => Ok(Unchanged::Rebuild),
+      None | Some("copy") => Ok(Unchanged::Copy),
+      Some("rebuild") => Ok(Unchanged::Rebuild),
+      Some("link") => Ok(Unchanged::Link),
+      Some("skip") => Ok(Unchanged::Skip),
+      Some(x) => Err(format!(
+        "invalid --unchanged: {} (copy|link|skip|rebuild)",
+        x
+      )),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Write the output of an input which needs no change, instead of rebuilding it.
+  ///
+  /// * `input`, `output`: paths returned by `prepare_io`
+  ///
+  /// Return.
+  ///
+  /// `false` if no output was written (`Skip`)
+  pub fn write(&self, input: &Path, output: &Path) -> Result<bool, String> {
+    match self {
+      Unchanged::Skip => {
+        // with --in-place, remove the backup of the intact input
+        restore_backup();
+        return Ok(false);
+      }
+      Unchanged::Link => {
+        let _ = fs::remove_file(clip::extended_path(output));
+        if let Ok(_) = fs::hard_link(clip::extended_path(input), clip::extended_path(output)) {
+          return Ok(true);
+        }
+      }
+      Unchanged::Copy | Unchanged::Rebuild => {}
+    }
+    if output.exists() && is_backup_of(input, output) {
+      // with --in-place, the input is still intact (and may be hard linked to the backup)
+      return Ok(true);
+    }
+    if let Err(e) = fs::copy(clip::extended_path(input), clip::extended_path(output)) {
+      return Err(format!("cannot write {}: {}", output.display(), e));
+    }
+    return Ok(true);
+  }
+}
+
+/// options deciding the output file and the backup of the input
+pub const OUTPUT_OPTIONS: &[OptionSpec] = &[
+  ("--in-place", false),
+  ("--backup-suffix", true),
+  ("--backup-dir", true),
+  ("--backup-keep", true),
+  ("--suffix-output", true),
+];
+/// options skipping inputs whose outputs are up to date (rename and batch)
+pub const INCREMENTAL_OPTIONS: &[OptionSpec] = &[("--only-if-newer", false)];
+/// default suffix of backup files: `a.clip` → `a.bk.clip`
+const DEFAULT_BACKUP_SUFFIX: &str = ".bk.clip";
+/// hex digits of the content hash in versioned backup file names
+const BACKUP_HASH_LENGTH: usize = 6;
+
+/// Brief.
+///
+/// How the output file is written (`OUTPUT_OPTIONS`).
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+  /// overwrite the input file (`--in-place`)
+  pub in_place: bool,
+  /// Without Output, write `<Input stem><suffix>.<ext>` next to Input (`--suffix-output`).
+  pub output_suffix: Option<OsString>,
+  /// backup file name is `<Input stem><suffix>`
+  pub backup_suffix: OsString,
+  /// directory of backup files. `None` is the directory of the input file.
+  pub backup_dir: Option<PathBuf>,
+  /// Keep this number of versioned backups `<Input stem>.<date>.<hash>.clip` (`--backup-keep`)
+  /// instead of one backup with `backup_suffix`. 0 is invalid.
+  pub backup_keep: Option<usize>,
+  /// write the same bytes for the same input and options (`--deterministic` of rename and batch)
+  pub deterministic: bool,
+  /// rebuild the embedded data base to remove free pages (`--compact` of rename and batch)
+  pub compact: bool,
+  /// layers whose names are updated by one SQL statement (`--update-batch` of rename and batch). 0 is invalid.
+  pub update_batch_size: usize,
+  /// Skip the input if the output is not older than it, and give the output the modification time of the input
+  /// (`--only-if-newer` of rename and batch). An output directory is written to `<Output>/<Input stem>.clip` as it is.
+  pub only_if_newer: bool,
+}
+
+impl OutputOptions {
+  pub fn from_args(args: &Args) -> OutputOptions {
+    return OutputOptions {
+      in_place: args.flag("--in-place"),
+      output_suffix: args
+        .path("--suffix-output")
+        .map(|x| x.as_os_str().to_owned()),
+      backup_suffix: match args.path("--backup-suffix") {
+        Some(x) => x.as_os_str().to_owned(),
+        None => OsString::from(DEFAULT_BACKUP_SUFFIX),
+      },
+      backup_dir: args.path("--backup-dir").map(Path::to_path_buf),
+      backup_keep: args.value("--backup-keep").map(|x| x.parse().unwrap_or(0)),
+      deterministic: args.flag("--deterministic"),
+      compact: args.flag("--compact"),
+      update_batch_size: match args.value("--update-batch") {
+        Some(x) => x.parse().unwrap_or(0),
+        None => clip::DEFAULT_UPDATE_BATCH_SIZE,
+      },
+      only_if_newer: args.flag("--only-if-newer"),
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Check the number of `Input [Output]` arguments.
+  ///
+  /// Output is required unless `--in-place` or `--suffix-output` is given,
+  /// and must not be given with `--in-place`.
+  pub fn check(&self, positional: &[OsString]) -> Result<(), String> {
+    if self.in_place && self.output_suffix.is_some() {
+      return Err("--suffix-output cannot be used with --in-place".to_string());
+    }
+    if self.in_place && self.only_if_newer {
+      return Err("--only-if-newer cannot be used with --in-place".to_string());
+    }
+    if self.in_place && positional.len() != 1 {
+      return Err("Output cannot be given with --in-place".to_string());
+    }
+    let outputs = if self.output_suffix.is_some() {
+      1..=2
+    } else {
+      2..=2
+    };
+    if !self.in_place && !outputs.contains(&positional.len()) {
+      return Err(
+        "Output is not given. Use --in-place to overwrite Input or --suffix-output SUFFIX"
+          .to_string(),
+      );
+    }
+    if self.backup_keep == Some(0) {
+      return Err("--backup-keep must be a positive number".to_string());
+    }
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// Output file path of `Input [Output]` without `--in-place`.
+  ///
+  /// If Output is a directory, the output file is `<Output>/<Input stem>.clip`,
+  /// with `_1`, `_2`, ... if it exists unless `only_if_newer` is set.
+  fn output_path(&self, input: &Path, positional: &[OsString]) -> PathBuf {
+    let output = match (positional.get(1), &self.output_suffix) {
+      (Some(x), _) => PathBuf::from(x),
+      (None, Some(suffix)) => suffixed_output(input, suffix),
+      (None, None) => unreachable!(),
+    };
+    if !output.is_dir() {
+      return output;
+    }
+    if self.only_if_newer {
+      return output.join(stem_with(input, ".clip"));
+    }
+    return output_in_directory(&output, input);
+  }
+
+  /// Brief.
+  ///
+  /// Whether the input need not be processed with `--only-if-newer`:
+  /// the output exists and its modification time is not older than the input's.
+  ///
+  /// * `positional`: `Input [Output]`
+  ///
+  /// Return.
+  ///
+  /// the output file path if it is up to date
+  pub fn up_to_date(&self, positional: &[OsString]) -> Option<PathBuf> {
+    if !self.only_if_newer || self.check(positional).is_err() {
+      return None;
+    }
+    let input = Path::new(&positional[0]);
+    let output = self.output_path(input, positional);
+    let modified = |p: &Path| fs::metadata(p).and_then(|x| x.modified()).ok();
+    return match (modified(input), modified(&output)) {
+      (Some(i), Some(o)) if i <= o => Some(output),
+      _ => None,
+    };
+  }
+
+  /// Brief.
+  ///
+  /// Give the output the modification time of the input with `--only-if-newer`,
+  /// so that the next run compares the times of the same clock even if the output is on another machine.
+  ///
+  /// * `input`, `output`: paths returned by `prepare_io`
+  pub fn preserve_modified(&self, input: &Path, output: &Path) -> Result<(), String> {
+    if !self.only_if_newer {
+      return Ok(());
+    }
+    let modified = match fs::metadata(clip::extended_path(input)).and_then(|x| x.modified()) {
+      Ok(x) => x,
+      Err(e) => return Err(format!("cannot read {}: {}", input.display(), e)),
+    };
+    let result = fs::OpenOptions::new()
+      .write(true)
+      .open(clip::extended_path(output))
+      .and_then(|f| f.set_modified(modified));
+    if let Err(e) = result {
+      return Err(format!(
+        "cannot set the modification time of {}: {}",
+        output.display(),
+        e
+      ));
+    }
+    return Ok(());
+  }
+
+  /// Brief.
+  ///
+  /// Backup file path of the input file.
+  ///
+  /// With `backup_keep`, the name has the date and a short hash of the input,
+  /// e.g. `a.2024-06-01.ab12cd.clip`, so that backups of repeated runs are not overwritten.
+  fn backup_path(&self, input: &Path) -> Result<PathBuf, String> {
+    let mut name = match input.file_stem() {
+      Some(x) => x.to_owned(),
+      None => OsString::from("backup"),
+    };
+    if self.backup_keep.is_some() {
+      let hash = match file_hash(input) {
+        Ok(x) => x,
+        Err(e) => return Err(format!("cannot read {}: {}", input.display(), e)),
+      };
+      name.push(format!(
+        ".{}.{}.",
+        utc_date(SystemTime::now()),
+        &hash[..BACKUP_HASH_LENGTH]
+      ));
+      name.push(backup_extension(input));
+    } else {
+      name.push(&self.backup_suffix);
+    }
+    return Ok(match &self.backup_dir {
+      Some(dir) => dir.join(name),
+      None => input.with_file_name(name),
+    });
+  }
+}
+
+/// extension of versioned backup files: the extension of the input file, or `clip`
+fn backup_extension(input: &Path) -> OsString {
+  return match input.extension() {
+    Some(x) => x.to_owned(),
+    None => OsString::from("clip"),
+  };
+}
+
+/// Brief.
+///
+/// Remove old versioned backups of the input file, keeping `keep` files.
+/// Backups are ordered by the date in the name, then by the modification time.
+///
+/// Names are compared as bytes, so that backups of inputs whose names are not UTF-8 are found too.
+///
+/// * `backup`: backup file just created
+/// * `input`: input file
+/// * `keep`: number of backups kept (`--backup-keep`)
+fn remove_old_backups(backup: &Path, input: &Path, keep: usize) {
+  let stem = match input.file_stem() {
+    Some(x) => x.to_owned(),
+    None => OsString::from("backup"),
+  };
+  let mut extension = OsString::from(".");
+  extension.push(backup_extension(input));
+  let dir = match backup.parent() {
+    Some(x) if !x.as_os_str().is_empty() => x,
+    _ => Path::new("."),
+  };
+  let entries = match fs::read_dir(dir) {
+    Ok(x) => x,
+    Err(_) => return,
+  };
+  // between the stem and the extension
+  let pattern = format!(
+    r"^\.(\d{{4}}-\d{{2}}-\d{{2}})\.[0-9a-f]{{{}}}$",
+    BACKUP_HASH_LENGTH
+  );
+  let pattern = regex::bytes::Regex::new(&pattern).unwrap();
+  let mut backups: Vec<(Vec<u8>, SystemTime, PathBuf)> = Vec::new();
+  for e in entries.flatten() {
+    let name = e.file_name();
+    let date = match name
+      .as_encoded_bytes()
+      .strip_prefix(stem.as_encoded_bytes())
+      .and_then(|x| x.strip_suffix(extension.as_encoded_bytes()))
+      .and_then(|x| pattern.captures(x))
+    {
+      Some(x) => x[1].to_vec(),
+      None => continue,
+    };
+    let modified = match e.metadata().and_then(|x| x.modified()) {
+      Ok(x) => x,
+      Err(_) => SystemTime::UNIX_EPOCH,
+    };
+    backups.push((date, modified, e.path()));
+  }
+  backups.sort_by(|a, b| (&b.0, b.1).cmp(&(&a.0, a.1)));
+  for (_, _, path) in backups.iter().skip(keep) {
+    if !is_same_file(path, backup) {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+/// `YYYY-MM-DD` of the time in UTC
+fn utc_date(time: SystemTime) -> String {
+  let secs = match time.duration_since(SystemTime::UNIX_EPOCH) {
+    Ok(x) => x.as_secs(),
+    Err(_) => 0,
+  };
+  // civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+  let z = (secs / 86400) as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  return format!("{:04}-{:02}-{:02}", year, month, day);
+}
+
+/// Brief.
+///
+/// Resolve `Input [Output]` arguments.
+///
+/// With `--in-place`, Input is hard linked (or copied) to the backup file path and the returned input path
+/// points to the backup file. Input itself stays intact until the output atomically replaces it.
+/// An error is returned if the backup cannot be created.
+/// If Output is a directory, the output file is `<Output>/<Input stem>.clip`.
+/// Without Output, the output file is derived from Input with `--suffix-output`.
+///
+/// The output (the input with `--in-place`) is locked until the next call or `release_output`,
+/// so that another process writing the same file waits for this process.
+///
+/// Return.
+///
+/// `(input, output)`
+pub fn prepare_io(
+  positional: &[OsString],
+  output_options: &OutputOptions,
+) -> Result<(PathBuf, PathBuf), String> {
+  output_options.check(positional)?;
+  // the output of the former input of batch
+  release_output();
+  let input = PathBuf::from(&positional[0]);
+  if !input.exists() {
+    return Err(format!("{} file not found.", input.display()));
+  }
+  if !output_options.in_place {
+    let output = output_options.output_path(&input, positional);
+    if is_same_file(&input, &output) {
+      return Err("Output is the same as Input. Use --in-place to overwrite Input".to_string());
+    }
+    lock_output(&output)?;
+    return Ok((input, output));
+  }
+  lock_output(&input)?;
+
+  // backup
+  let backup = output_options.backup_path(&input)?;
+  if is_same_file(&input, &backup) {
+    return Err(format!(
+      "backup file is the same as Input: {}",
+      backup.display()
+    ));
+  }
+  if let Some(dir) = &output_options.backup_dir {
+    if let Err(e) = fs::create_dir_all(dir) {
+      return Err(format!("cannot create {}: {}", dir.display(), e));
+    }
+  }
+  // Input is kept until the output replaces it atomically.
+  // The backup is made with a name unique to this process and renamed,
+  // so that other processes backing up a file of the same name to the same directory do not mix them.
+  let staged = process_unique_path(&backup);
+  let (from, to) = (clip::extended_path(&input), clip::extended_path(&staged));
+  let _ = fs::remove_file(&to);
+  if let Err(e) = fs::hard_link(&from, &to) {
+    // e.g. the backup directory is on another file system
+    if let Err(_) = fs::copy(&from, &to) {
+      let _ = fs::remove_file(&to);
+      return Err(format!("cannot create backup {}: {}", backup.display(), e));
+    }
+  }
+  // An existing backup is moved aside instead of overwritten,
+  // so that it is put back if this run fails (`restore_backup`).
+  let previous = if fs::symlink_metadata(clip::extended_path(&backup)).is_ok() {
+    let previous = process_unique_path(&backup).with_extension("old");
+    if let Err(e) = fs::rename(clip::extended_path(&backup), clip::extended_path(&previous)) {
+      let _ = fs::remove_file(&to);
+      return Err(format!("cannot move {} aside: {}", backup.display(), e));
+    }
+    Some(previous)
+  } else {
+    None
+  };
+  let renamed = fs::rename(&to, clip::extended_path(&backup));
+  let _ = fs::remove_file(&to);
+  if let Err(e) = renamed {
+    if let Some(previous) = &previous {
+      let _ = fs::rename(clip::extended_path(previous), clip::extended_path(&backup));
+    }
+    return Err(format!("cannot create backup {}: {}", backup.display(), e));
+  }
+  if let Ok(mut x) = BACKUP.lock() {
+    *x = Some((backup.clone(), input.clone(), previous));
+  }
+  // old backups are removed after the output is written (`commit_backup`)
+  if let Ok(mut x) = PRUNE.lock() {
+    *x = output_options
+      .backup_keep
+      .map(|keep| (backup.clone(), input.clone(), keep));
+  }
+  return Ok((backup, input));
+}
+
+/// Whether two paths point to the same file. Paths which do not exist are compared as written.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+  return match (fs::canonicalize(a), fs::canonicalize(b)) {
+    (Ok(x), Ok(y)) => x == y,
+    _ => a == b,
+  };
+}
+
+/// Output file path next to the input file: `a.clip` → `a<suffix>.clip`
+fn suffixed_output(input: &Path, suffix: &OsStr) -> PathBuf {
+  let mut name = match input.file_stem() {
+    Some(x) => x.to_owned(),
+    None => OsString::from("output"),
+  };
+  name.push(suffix);
+  if let Some(ext) = input.extension() {
+    name.push(".");
+    name.push(ext);
+  }
+  return input.with_file_name(name);
+}
+
+/// `.<file name>.<process id>.tmp` next to the path: a temporary file name which other processes do not use
+pub fn process_unique_path(path: &Path) -> PathBuf {
+  let mut name = OsString::from(".");
+  name.push(path.file_name().unwrap_or(OsStr::new("output")));
+  name.push(format!(".{}.tmp", std::process::id()));
+  return path.with_file_name(name);
+}
+
+/// Brief.
+///
+/// Derive an output file path in the directory from the input file name.
+///
+/// If `<dir>/<stem>.clip` already exists, `<dir>/<stem>_1.clip`, `<dir>/<stem>_2.clip`, ... is used.
+/// The name is reserved by creating an empty file exclusively (`RESERVED`),
+/// so that processes writing to the same directory at the same time do not choose the same name.
+fn output_in_directory(dir: &Path, input: &Path) -> PathBuf {
+  let mut output = dir.join(stem_with(input, ".clip"));
+  let mut n = 1;
+  loop {
+    let created = fs::OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(clip::extended_path(&output));
+    match created {
+      Ok(_) => {
+        if let Ok(mut x) = RESERVED.lock() {
+          *x = Some(output.clone());
+        }
+        return output;
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+      // not reserved. The error is reported when the output is written.
+      Err(_) if !output.exists() => return output,
+      Err(_) => {}
+    }
+    output = dir.join(stem_with(input, &format!("_{}.clip", n)));
+    n += 1;
+  }
+}
+
+/// file name of the input without the extension (or `output`) followed by `suffix`: `a.clip` → `a<suffix>`
+fn stem_with(input: &Path, suffix: &str) -> OsString {
+  let mut name = match input.file_stem() {
+    Some(x) => x.to_owned(),
+    None => OsString::from("output"),
+  };
+  name.push(suffix);
+  return name;
+}
+
+/// Brief.
+///
+/// SHA-256 of the file as a lower case hex string.
+pub fn file_hash(path: &Path) -> std::io::Result<String> {
+  let mut file = fs::File::open(path)?;
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut file, &mut hasher)?;
+  let hash = hasher.finalize();
+  return Ok(hash.iter().map(|b| format!("{:02x}", b)).collect());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  #[test]
+  fn sizes() {
+    assert_eq!(parse_size("100"), Some(100));
+    assert_eq!(parse_size("100B"), Some(100));
+    assert_eq!(parse_size(" 512k "), Some(512 << 10));
+    assert_eq!(parse_size("3M"), Some(3 << 20));
+    assert_eq!(parse_size("2G"), Some(2 << 30));
+    assert_eq!(parse_size("1x"), None);
+    assert_eq!(parse_size("K"), None);
+    assert_eq!(parse_size("-1"), None);
+    assert_eq!(parse_size("18446744073709551615G"), None);
+  }
+
+  #[test]
+  fn dates() {
+    let date = |secs: u64| utc_date(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(1_717_200_000), "2024-06-01");
+    assert_eq!(date(1_709_164_800), "2024-02-29");
+    assert_eq!(date(951_868_799), "2000-02-29");
+    assert_eq!(
+      utc_date(SystemTime::UNIX_EPOCH - Duration::from_secs(1)),
+      "1970-01-01"
+    );
+  }
+}
@@ -0,0 +1,93 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, rename, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+
+const USAGE: &str = "renamelayer prefix|suffix [--quiet|--verbose] [--profile NAME] [--pattern REGEX] [--exact] [--ignore-case] [--normalize-width] [--protect-prefix PREFIX] [--include-folders] [--allow-special TYPE]... [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input TEXT [Output]";
+const OPTIONS: &[OptionSpec] = &[
+  ("--quiet", false),
+  ("--verbose", false),
+  ("--profile", true),
+  ("--pattern", true),
+  ("--exact", false),
+  ("--ignore-case", false),
+  ("--normalize-width", false),
+  ("--protect-prefix", true),
+  ("--include-folders", false),
+  ("--allow-special", true),
+];
+
+/// Brief.
+///
+/// `prefix` / `suffix` sub command: add text to the names of matching layers without numbering.
+///
+/// * `args`: arguments after the sub command name
+/// * `prefix`: `true` for `prefix`, `false` for `suffix`
+pub fn run(args: &[OsString], prefix: bool) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() < 2 || args.positional.len() > 3 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let text = match args.positional[1].to_str() {
+    Some(x) => x.to_string(),
+    None => {
+      println!("Error: TEXT is not valid UTF-8");
+      return 1;
+    }
+  };
+  let options = match rename::parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let (prefix, suffix) = if prefix {
+    (text.as_str(), "")
+  } else {
+    ("", text.as_str())
+  };
+
+  let mut io_args = vec![args.positional[0].clone()];
+  io_args.extend(args.positional.get(2).cloned());
+  let (input, output) = match prepare_io(&io_args, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+
+  let mut report = clip::RenameReport::default();
+  if let Err(e) = clip::edit_clip_file(&input, &output, |sql_path| {
+    report = clip::add_affix_in_sqlite(sql_path, &options, prefix, suffix)?;
+    Ok(())
+  }) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  backup.keep();
+  if args.flag("--verbose") {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !args.flag("--quiet") {
+    println!("{}", report.summary());
+  }
+  return 0;
+}
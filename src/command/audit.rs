@@ -0,0 +1,235 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::check;
+use renamelayer::clip;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "renamelayer audit [--quiet] [--report REPORT.json|REPORT.html] [--profile NAME] [--pattern REGEX] [--exact] [--ignore-case] [--normalize-width] [--protect-prefix PREFIX] [--include-folders] Dir|Input...";
+const AUDIT_OPTIONS: &[OptionSpec] = &[("--report", true)];
+
+/// Brief.
+///
+/// Naming convention violations of many files.
+#[derive(Debug, Default, Serialize)]
+struct AuditReport {
+  /// number of files
+  total_files: usize,
+  /// number of files with violations
+  files_with_violations: usize,
+  /// number of violating layers in all files
+  total_violations: usize,
+  files: Vec<FileReport>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct FileReport {
+  /// file path
+  path: String,
+  /// number of checked layers
+  total_layers: usize,
+  /// names of layers matching the rename pattern
+  violations: Vec<String>,
+  /// error message if the file could not be read
+  error: Option<String>,
+}
+
+/// Brief.
+///
+/// `audit` sub command: report layers matching the rename pattern in many files.
+///
+/// Directories are searched recursively for `*.clip` files (backups `*.bk.clip` are ignored).
+/// Files are not modified.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = check::OPTIONS
+    .iter()
+    .chain(AUDIT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse_with_environment(args, &spec, check::ENVIRONMENT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let options = match check::parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let report_path = args.path("--report");
+  if let Some(p) = report_path {
+    if report_format(p).is_none() {
+      println!(
+        "Error: unknown report format: {} (.json|.html)",
+        p.display()
+      );
+      return 1;
+    }
+  }
+  let include_folders = args.flag("--include-folders");
+
+  let mut files: Vec<PathBuf> = Vec::new();
+  for p in args.positional.iter().map(Path::new) {
+    if let Err(e) = collect_clip_files(p, &mut files) {
+      println!("Error: {}: {}", p.display(), e);
+      return 1;
+    }
+  }
+
+  let mut report = AuditReport::default();
+  for f in files.iter() {
+    let mut file = FileReport {
+      path: f.to_string_lossy().to_string(),
+      ..FileReport::default()
+    };
+    match check::matching_layers(f, &options, include_folders) {
+      Ok((total, layers)) => {
+        file.total_layers = total;
+        file.violations = layers.into_iter().map(|x| x.name).collect();
+      }
+      Err(e) => file.error = Some(e.to_string()),
+    }
+    if !file.violations.is_empty() {
+      report.files_with_violations += 1;
+      report.total_violations += file.violations.len();
+    }
+    report.files.push(file);
+  }
+  report.total_files = report.files.len();
+
+  if !args.flag("--quiet") {
+    for f in report.files.iter() {
+      if let Some(e) = &f.error {
+        println!("{}: Error: {}", f.path, e);
+      } else if !f.violations.is_empty() {
+        println!(
+          "{}: {} of {} layers",
+          f.path,
+          f.violations.len(),
+          f.total_layers
+        );
+      }
+    }
+    println!(
+      "{} of {} files have {} layers to rename",
+      report.files_with_violations, report.total_files, report.total_violations
+    );
+  }
+  if let Some(p) = report_path {
+    let text = match report_format(p) {
+      Some(ReportFormat::Json) => match serde_json::to_string_pretty(&report) {
+        Ok(x) => x,
+        Err(e) => {
+          println!("Error: {}", e);
+          return 1;
+        }
+      },
+      _ => to_html(&report),
+    };
+    if let Err(e) = fs::write(p, text) {
+      println!("Error: cannot write {}: {}", p.display(), e);
+      return 1;
+    }
+  }
+  return 0;
+}
+
+enum ReportFormat {
+  Json,
+  Html,
+}
+
+/// Report format from the file extension.
+fn report_format(path: &Path) -> Option<ReportFormat> {
+  let ext = path.extension()?.to_string_lossy().to_lowercase();
+  return match ext.as_str() {
+    "json" => Some(ReportFormat::Json),
+    "html" | "htm" => Some(ReportFormat::Html),
+    _ => None,
+  };
+}
+
+/// Collect clip files (`clip::is_clip_file`) in the directory recursively in the order of names.
+/// Backups made by `--in-place` (`*.bk.clip`) are not collected.
+fn collect_clip_files(path: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  if !path.is_dir() {
+    if !path.exists() {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "file not found",
+      ));
+    }
+    out.push(path.to_path_buf());
+    return Ok(());
+  }
+  let mut entries: Vec<PathBuf> = Vec::new();
+  for e in fs::read_dir(path)? {
+    entries.push(e?.path());
+  }
+  entries.sort();
+  for p in entries {
+    if p.is_dir() {
+      collect_clip_files(&p, out)?;
+      continue;
+    }
+    let name = p.to_string_lossy().to_lowercase();
+    if !name.ends_with(".bk.clip") && clip::is_clip_file(&p) {
+      out.push(p);
+    }
+  }
+  return Ok(());
+}
+
+fn to_html(report: &AuditReport) -> String {
+  let mut s = String::from(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>renamelayer audit</title>\n<style>\ntable { border-collapse: collapse; }\nth, td { border: 1px solid #999; padding: 2px 8px; vertical-align: top; }\n.ng { background: #fdd; }\n</style>\n</head>\n<body>\n",
+  );
+  s.push_str(&format!(
+    "<p>{} of {} files have {} layers to rename.</p>\n",
+    report.files_with_violations, report.total_files, report.total_violations
+  ));
+  s.push_str("<table>\n<tr><th>file</th><th>layers</th><th>to rename</th><th>names</th></tr>\n");
+  for f in report.files.iter() {
+    let ng = f.error.is_some() || !f.violations.is_empty();
+    s.push_str(&format!(
+      "<tr{}><td>{}</td>",
+      if ng { " class=\"ng\"" } else { "" },
+      escape_html(&f.path)
+    ));
+    match &f.error {
+      Some(e) => s.push_str(&format!("<td colspan=\"3\">Error: {}</td>", escape_html(e))),
+      None => {
+        let names: Vec<String> = f.violations.iter().map(|x| escape_html(x)).collect();
+        s.push_str(&format!(
+          "<td>{}</td><td>{}</td><td>{}</td>",
+          f.total_layers,
+          f.violations.len(),
+          names.join("<br>")
+        ));
+      }
+    }
+    s.push_str("</tr>\n");
+  }
+  s.push_str("</table>\n</body>\n</html>\n");
+  return s;
+}
+
+fn escape_html(text: &str) -> String {
+  return text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;");
+}
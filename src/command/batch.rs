@@ -0,0 +1,523 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{
+  file_hash, prepare_io, process_unique_path, rename, BackupGuard, Limits, OutputOptions,
+  Unchanged, INCREMENTAL_OPTIONS, LIMIT_OPTIONS, OUTPUT_OPTIONS,
+};
+use renamelayer::clip;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str =
+  "renamelayer batch (--out-dir DIR | --suffix-output SUFFIX | --in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]) [--resume MANIFEST] [--series] [--only-if-newer] [--max-layers N] [--max-file-size SIZE] [--strict] [rename options] Input...";
+const BATCH_OPTIONS: &[OptionSpec] =
+  &[("--out-dir", true), ("--resume", true), ("--series", false)];
+/// options which may be given by environment variables: `rename::ENVIRONMENT_OPTIONS` and the output directory
+const ENVIRONMENT_OPTIONS: &[&str] = &[
+  "--profile",
+  "--pattern",
+  "--template",
+  "--protect-prefix",
+  "--backup-suffix",
+  "--out-dir",
+];
+
+const DONE: &str = "done";
+const FAILED: &str = "failed";
+
+/// Brief.
+///
+/// Record of processed files, saved after every file so that an interrupted run can be resumed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+  files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+  /// input file path as given. Paths which are not UTF-8 cannot be recorded.
+  input: PathBuf,
+  /// output file path
+  output: PathBuf,
+  /// SHA-256 of the input before renaming
+  input_hash: String,
+  /// SHA-256 of the output. `None` if failed.
+  output_hash: Option<String>,
+  /// `done` or `failed`
+  status: String,
+  /// error message if failed
+  error: Option<String>,
+}
+
+impl Manifest {
+  fn load(path: &Path) -> Result<Manifest, String> {
+    if !path.exists() {
+      return Ok(Manifest::default());
+    }
+    let text = match fs::read_to_string(path) {
+      Ok(x) => x,
+      Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    return match serde_json::from_str(&text) {
+      Ok(x) => Ok(x),
+      Err(e) => Err(format!("broken manifest {}: {}", path.display(), e)),
+    };
+  }
+
+  /// Write to a temporary file and rename it, so that the manifest is never half written.
+  fn save(&self, path: &Path) -> Result<(), String> {
+    let text = match serde_json::to_string_pretty(self) {
+      Ok(x) => x,
+      Err(e) => return Err(format!("cannot write {}: {}", path.display(), e)),
+    };
+    // other processes may save the same manifest
+    let tmp = process_unique_path(path);
+    if let Err(e) = fs::write(&tmp, text) {
+      return Err(format!("cannot write {}: {}", tmp.display(), e));
+    }
+    if let Err(e) = fs::rename(&tmp, path) {
+      return Err(format!("cannot write {}: {}", path.display(), e));
+    }
+    return Ok(());
+  }
+
+  fn find(&self, input: &Path) -> Option<&ManifestEntry> {
+    return self.files.iter().find(|x| x.input == input);
+  }
+
+  fn record(&mut self, entry: ManifestEntry) {
+    self.files.retain(|x| x.input != entry.input);
+    self.files.push(entry);
+  }
+}
+
+impl ManifestEntry {
+  /// Whether the file was finished and neither the input nor the output was changed since.
+  fn is_finished(&self) -> bool {
+    if self.status != DONE {
+      return false;
+    }
+    let output = self.output.as_path();
+    if file_hash(output).ok() != self.output_hash {
+      return false;
+    }
+    // overwritten in place
+    if self.input == output {
+      return true;
+    }
+    return file_hash(&self.input).ok().as_deref() == Some(self.input_hash.as_str());
+  }
+}
+
+/// Brief.
+///
+/// `batch` sub command: rename layers of many files.
+///
+/// Files are written to `--out-dir`, or overwritten (with backups) with `--in-place`.
+/// With `--resume`, finished files are recorded in the manifest and skipped in the next run.
+/// With `--series`, the files are treated as pages of one work (`Series`).
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = rename::OPTIONS
+    .iter()
+    .filter(|x| !x.0.starts_with("--preview") && x.0 != "--timings")
+    .chain(BATCH_OPTIONS.iter())
+    .chain(INCREMENTAL_OPTIONS.iter())
+    .chain(LIMIT_OPTIONS.iter())
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse_with_environment(args, &spec, ENVIRONMENT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let options = match rename::parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let limits = match Limits::from_args(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let unchanged = match Unchanged::from_args(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let lock_folders = args.values("--lock-folder");
+  let quiet = args.flag("--quiet");
+  let out_dir = args.path("--out-dir");
+  let output_options = OutputOptions::from_args(&args);
+  let destinations = [
+    out_dir.is_some(),
+    output_options.in_place,
+    output_options.output_suffix.is_some(),
+  ];
+  if destinations.iter().filter(|x| **x).count() != 1 {
+    println!("Error: give one of --out-dir, --in-place and --suffix-output");
+    println!("{}", USAGE);
+    return 1;
+  }
+  if output_options.in_place && output_options.only_if_newer {
+    println!("Error: --only-if-newer cannot be used with --in-place");
+    return 1;
+  }
+  if output_options.update_batch_size == 0 {
+    println!("Error: --update-batch must be a positive number");
+    return 1;
+  }
+  if let Some(dir) = out_dir {
+    if let Err(e) = fs::create_dir_all(dir) {
+      println!("Error: cannot create {}: {}", dir.display(), e);
+      return 1;
+    }
+  }
+  let manifest_path = args.path("--resume");
+  if manifest_path.is_some() {
+    // JSON cannot record them
+    let paths = args.positional.iter().map(Path::new).chain(out_dir);
+    if let Some(p) = paths.into_iter().find(|x| x.to_str().is_none()) {
+      println!(
+        "Error: --resume cannot record {}: the path is not UTF-8",
+        p.display()
+      );
+      return 1;
+    }
+  }
+  let mut manifest = match manifest_path {
+    Some(p) => match Manifest::load(p) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    },
+    None => Manifest::default(),
+  };
+
+  let mut series = if args.flag("--series") {
+    let series = Series::scan(&args.positional);
+    series.report_missing_folders();
+    Some(series)
+  } else {
+    None
+  };
+
+  let mut failed = 0;
+  let mut skipped = 0;
+  for input in args.positional.iter().map(Path::new) {
+    if let Some(entry) = manifest.find(input) {
+      if entry.is_finished() {
+        skipped += 1;
+        if !quiet {
+          println!("{}: skipped (already done)", input.display());
+        }
+        continue;
+      }
+    }
+    let mut positional = vec![input.as_os_str().to_owned()];
+    if let Some(dir) = out_dir {
+      positional.push(dir.as_os_str().to_owned());
+    }
+    if let Some(output) = output_options.up_to_date(&positional) {
+      skipped += 1;
+      if !quiet {
+        println!(
+          "{}: skipped ({} is up to date)",
+          input.display(),
+          output.display()
+        );
+      }
+      continue;
+    }
+    let file_options = match &series {
+      Some(x) => x.options(&options),
+      None => options.clone(),
+    };
+    let (entry, report) = process(
+      input,
+      out_dir,
+      &output_options,
+      &limits,
+      unchanged,
+      &file_options,
+      &lock_folders,
+    );
+    if let (Some(series), Some(report)) = (&mut series, &report) {
+      series.record(input, report);
+    }
+    if entry.status == DONE {
+      if !quiet {
+        println!("{} -> {}", input.display(), entry.output.display());
+      }
+    } else {
+      failed += 1;
+      println!(
+        "Error: {}: {}",
+        input.display(),
+        entry.error.as_deref().unwrap_or_default()
+      );
+    }
+    if let Some(p) = manifest_path {
+      manifest.record(entry);
+      if let Err(e) = manifest.save(p) {
+        println!("Error: {}", e);
+        return 1;
+      }
+    }
+  }
+  if !quiet {
+    println!(
+      "processed {} files ({} skipped, {} failed)",
+      args.positional.len(),
+      skipped,
+      failed
+    );
+  }
+  return if failed == 0 { 0 } else { 1 };
+}
+
+/// Rename one file and make the manifest entry. The report is `None` if failed.
+fn process(
+  input: &Path,
+  out_dir: Option<&Path>,
+  output_options: &OutputOptions,
+  limits: &Limits,
+  unchanged: Unchanged,
+  options: &clip::RenameOptions,
+  lock_folders: &[&str],
+) -> (ManifestEntry, Option<clip::RenameReport>) {
+  let mut entry = ManifestEntry {
+    input: input.to_path_buf(),
+    output: PathBuf::new(),
+    input_hash: String::new(),
+    output_hash: None,
+    status: FAILED.to_string(),
+    error: None,
+  };
+  entry.input_hash = match file_hash(input) {
+    Ok(x) => x,
+    Err(e) => {
+      entry.error = Some(e.to_string());
+      return (entry, None);
+    }
+  };
+  if let Err(e) = limits.check_file(input) {
+    entry.error = Some(e);
+    return (entry, None);
+  }
+  let mut positional = vec![input.as_os_str().to_owned()];
+  if let Some(dir) = out_dir {
+    positional.push(dir.as_os_str().to_owned());
+  }
+  let (input_buf, output) = match prepare_io(&positional, output_options) {
+    Ok(x) => x,
+    Err(e) => {
+      entry.error = Some(e);
+      return (entry, None);
+    }
+  };
+  let backup = BackupGuard::guard();
+  let (written, report) = match rename_file(
+    &input_buf,
+    &output,
+    limits,
+    unchanged,
+    output_options,
+    options,
+    lock_folders,
+  ) {
+    Ok(x) => x,
+    Err(e) => {
+      entry.error = Some(e);
+      return (entry, None);
+    }
+  };
+  backup.keep();
+  // the input is the result when no output was written (`--unchanged skip`)
+  entry.output = if written { output } else { input.to_path_buf() };
+  entry.output_hash = match file_hash(&entry.output) {
+    Ok(x) => Some(x),
+    Err(e) => {
+      entry.error = Some(e.to_string());
+      return (entry, None);
+    }
+  };
+  entry.status = DONE.to_string();
+  return (entry, Some(report));
+}
+
+/// Rename one file. Returns `false` if no output was written (`Unchanged::Skip`).
+fn rename_file(
+  input: &Path,
+  output: &Path,
+  limits: &Limits,
+  unchanged: Unchanged,
+  output_options: &OutputOptions,
+  options: &clip::RenameOptions,
+  lock_folders: &[&str],
+) -> Result<(bool, clip::RenameReport), String> {
+  let mut doc = match clip::ClipDocument::open(input) {
+    Ok(x) => x,
+    Err(e) => return Err(e.to_string()),
+  };
+  doc.set_deterministic(output_options.deterministic);
+  doc.set_compact(output_options.compact);
+  doc.set_update_batch_size(output_options.update_batch_size);
+  limits.check_document(&doc)?;
+  let report = match rename::rename_document(&mut doc, options, lock_folders) {
+    Ok(x) => x,
+    Err(e) => return Err(e.to_string()),
+  };
+  if unchanged != Unchanged::Rebuild && rename::is_unchanged(&doc, &report, lock_folders) {
+    drop(doc);
+    let written = unchanged.write(input, output)?;
+    if written {
+      output_options.preserve_modified(input, output)?;
+    }
+    return Ok((written, report));
+  }
+  if let Err(e) = doc.save(output) {
+    return Err(e.to_string());
+  }
+  output_options.preserve_modified(input, output)?;
+  return Ok((true, report));
+}
+
+/// Brief.
+///
+/// Files of a series (e.g. `page001.clip` … `page050.clip`) renamed with the same folder names.
+///
+/// Before renaming, the folders of all files are compared and folders missing in some files are reported.
+/// A folder renamed in a file is renamed to the same name in the following files,
+/// even if the numbering would give another name there.
+struct Series {
+  /// folder paths (`A/B`) and main_ids of folders for each file
+  files: HashMap<PathBuf, (Vec<String>, HashSet<u64>)>,
+  /// old folder name → new name found so far
+  names: HashMap<String, String>,
+  /// old folder names renamed to different names, not mapped
+  conflicts: HashSet<String>,
+}
+
+impl Series {
+  /// Read the folders of the files. Files which cannot be read are reported when they are renamed.
+  fn scan(inputs: &[OsString]) -> Series {
+    let mut files: HashMap<PathBuf, (Vec<String>, HashSet<u64>)> = HashMap::new();
+    for input in inputs.iter().map(PathBuf::from) {
+      let layers = match clip::ClipDocument::open(&input)
+        .and_then(|doc| clip::list_layers_in_sqlite(doc.sql_path()))
+      {
+        Ok(x) => x,
+        Err(_) => continue,
+      };
+      let mut stack: Vec<&str> = Vec::new();
+      let mut paths: Vec<String> = Vec::new();
+      let mut ids: HashSet<u64> = HashSet::new();
+      for l in layers.iter() {
+        stack.truncate(l.depth);
+        if !l.folder {
+          continue;
+        }
+        stack.push(&l.name);
+        paths.push(stack.join("/"));
+        ids.insert(l.main_id);
+      }
+      files.insert(input, (paths, ids));
+    }
+    return Series {
+      files,
+      names: HashMap::new(),
+      conflicts: HashSet::new(),
+    };
+  }
+
+  /// Print folders which are not in all files.
+  fn report_missing_folders(&self) {
+    let mut inputs: Vec<&PathBuf> = self.files.keys().collect();
+    inputs.sort();
+    let mut all: Vec<&str> = Vec::new();
+    for input in inputs.iter() {
+      for path in self.files[*input].0.iter() {
+        if !all.contains(&path.as_str()) {
+          all.push(path);
+        }
+      }
+    }
+    for path in all {
+      let missing: Vec<String> = inputs
+        .iter()
+        .filter(|x| !self.files[**x].0.iter().any(|p| p == path))
+        .map(|x| x.display().to_string())
+        .collect();
+      if !missing.is_empty() {
+        println!(
+          "Warning: folder {} is missing in {} of {} files: {}",
+          path,
+          missing.len(),
+          inputs.len(),
+          missing.join(", ")
+        );
+      }
+    }
+  }
+
+  /// Options for the next file: folders renamed in the previous files get the same names.
+  fn options(&self, options: &clip::RenameOptions) -> clip::RenameOptions {
+    let mut options = options.clone();
+    for (name, new_name) in self.names.iter() {
+      options = options.dictionary_entry(name, new_name);
+    }
+    return options;
+  }
+
+  /// Record the folders renamed in a file.
+  fn record(&mut self, input: &Path, report: &clip::RenameReport) {
+    let folders = match self.files.get(input) {
+      Some(x) => &x.1,
+      None => return,
+    };
+    for r in report
+      .renamed
+      .iter()
+      .filter(|x| folders.contains(&x.main_id))
+    {
+      if self.conflicts.contains(&r.old_name) {
+        continue;
+      }
+      match self.names.get(&r.old_name) {
+        Some(x) if *x != r.new_name => {
+          println!(
+            "Warning: folder {} is renamed to {} and {}. The names are not unified.",
+            r.old_name, x, r.new_name
+          );
+          self.names.remove(&r.old_name);
+          self.conflicts.insert(r.old_name.clone());
+        }
+        Some(_) => {}
+        None => {
+          self.names.insert(r.old_name.clone(), r.new_name.clone());
+        }
+      }
+    }
+  }
+}
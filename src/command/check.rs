@@ -0,0 +1,147 @@
+use crate::args::{Args, OptionSpec};
+use regex::Regex;
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer check-clean [--quiet] [--profile NAME] [--pattern REGEX] [--exact] [--ignore-case] [--normalize-width] [--protect-prefix PREFIX] [--include-folders] [--allow-special TYPE]... Input...";
+pub const OPTIONS: &[OptionSpec] = &[
+  ("--quiet", false),
+  ("--profile", true),
+  ("--pattern", true),
+  ("--exact", false),
+  ("--ignore-case", false),
+  ("--normalize-width", false),
+  ("--protect-prefix", true),
+  ("--include-folders", false),
+  ("--allow-special", true),
+];
+/// options which may be given by environment variables (`Args::parse_with_environment`)
+pub const ENVIRONMENT_OPTIONS: &[&str] = &["--profile", "--pattern", "--protect-prefix"];
+
+/// Brief.
+///
+/// `check-clean` sub command: check that no layer matches the rename pattern.
+///
+/// * `args`: arguments after the sub command name
+///
+/// Return.
+///
+/// 0 if no layer matches, 1 if some layers match, 2 on errors.
+pub fn run(args: &[OsString]) -> i32 {
+  let args = match Args::parse_with_environment(args, OPTIONS, ENVIRONMENT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 2;
+    }
+  };
+  if args.positional.is_empty() {
+    println!("{}", USAGE);
+    return 2;
+  }
+
+  let options = match parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 2;
+    }
+  };
+  let include_folders = args.flag("--include-folders");
+  let quiet = args.flag("--quiet");
+
+  let mut status = 0;
+  for input in args.positional.iter().map(Path::new) {
+    let layers = match matching_layers(input, &options, include_folders) {
+      Ok(x) => x.1,
+      Err(e) => {
+        println!("Error: {}: {}", input.display(), e);
+        return 2;
+      }
+    };
+    if layers.is_empty() {
+      continue;
+    }
+    status = 1;
+    if !quiet {
+      for l in layers {
+        println!("{}: {}", input.display(), l.name);
+      }
+    }
+  }
+  return status;
+}
+
+/// Brief.
+///
+/// Options deciding which layers match (`--profile`, `--pattern`, `--exact`, `--ignore-case`,
+/// `--normalize-width`, `--protect-prefix`, `--allow-special`).
+pub fn parse_options(args: &Args) -> Result<clip::RenameOptions, String> {
+  let mut options = match args.value("--profile") {
+    Some(name) => match clip::Profile::find(name) {
+      Some(x) => x.options(),
+      None => {
+        let names: Vec<&str> = clip::PROFILES.iter().map(|x| x.name).collect();
+        return Err(format!("unknown profile: {} ({})", name, names.join("|")));
+      }
+    },
+    None => clip::RenameOptions::new(),
+  };
+  if let Some(pattern) = args.value("--pattern") {
+    options = match Regex::new(pattern) {
+      Ok(x) => options.pattern(x),
+      Err(e) => return Err(format!("invalid pattern: {}", e)),
+    };
+  } else if args.value("--profile").is_none() {
+    options = options.detect_language(true);
+  }
+  if args.flag("--exact") {
+    options = options.match_mode(clip::MatchMode::Exact);
+  }
+  options = options
+    .ignore_case(args.flag("--ignore-case"))
+    .normalize_width(args.flag("--normalize-width"));
+  if let Some(prefix) = args.value("--protect-prefix") {
+    options = options.protect_prefix(prefix);
+  }
+  for x in args.values("--allow-special") {
+    for name in x.split(',') {
+      match name.trim().parse() {
+        Ok(t) => options = options.allow_special(t),
+        Err(e) => return Err(e.to_string()),
+      }
+    }
+  }
+  return Ok(options);
+}
+
+/// Brief.
+///
+/// Layers which would be renamed by the pattern.
+/// Protected layers and special layers are not matched, as in renaming.
+///
+/// Return.
+///
+/// `(total number of checked layers, matching layers)`
+pub fn matching_layers(
+  input: &Path,
+  options: &clip::RenameOptions,
+  include_folders: bool,
+) -> Result<(usize, Vec<clip::LayerInfo>), clip::ClipError> {
+  let doc = clip::ClipDocument::open(input)?;
+  let layers: Vec<clip::LayerInfo> = clip::list_layers_in_sqlite(doc.sql_path())?
+    .into_iter()
+    .filter(|l| include_folders || !l.folder)
+    .collect();
+  let total = layers.len();
+  let options = options.for_layer_names(layers.iter().map(|l| l.name.as_str()));
+  return Ok((
+    total,
+    layers
+      .into_iter()
+      .filter(|l| options.need_rename(&l.name) && !options.is_kept(&l.name, l.special))
+      .collect(),
+  ));
+}
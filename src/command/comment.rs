@@ -0,0 +1,168 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer comment get Input [ID]...\nrenamelayer comment set [--quiet] [--append] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input ID TEXT [Output]";
+const SET_OPTIONS: &[OptionSpec] = &[("--quiet", false), ("--append", false)];
+
+/// Brief.
+///
+/// `comment` sub command: read or write the comments (search keywords) of layers.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  return match args.first().and_then(|x| x.to_str()) {
+    Some("get") => run_get(&args[1..]),
+    Some("set") => run_set(&args[1..]),
+    _ => {
+      println!("{}", USAGE);
+      1
+    }
+  };
+}
+
+/// Brief.
+///
+/// `comment get`: print `main_id<TAB>comment` of the given layers, or all layers with a comment.
+/// Line breaks and `\` in comments are written as `\n` and `\\`.
+fn run_get(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, &[]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let mut ids: Vec<u64> = Vec::new();
+  for x in args.positional[1..].iter() {
+    match x.to_str().and_then(|x| x.parse().ok()) {
+      Some(id) => ids.push(id),
+      None => {
+        println!("Error: invalid ID: {}", x.to_string_lossy());
+        return 1;
+      }
+    }
+  }
+  let input = Path::new(&args.positional[0]);
+  if !input.exists() {
+    println!("Error: {} file not found.", input.display());
+    return 1;
+  }
+  let doc = match clip::ClipDocument::open(input) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let comments: Result<Vec<(u64, String)>, clip::ClipError> = if ids.is_empty() {
+    doc.layer_comments()
+  } else {
+    ids
+      .iter()
+      .map(|id| Ok((*id, doc.layer_comment(*id)?.unwrap_or_default())))
+      .collect()
+  };
+  let comments = match comments {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  for (id, comment) in comments.iter() {
+    println!("{}\t{}", id, escape(comment));
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `comment set`: set the comment of a layer. An empty TEXT clears it.
+fn run_set(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = SET_OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() < 3 || args.positional.len() > 4 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let id: u64 = match args.positional[1].to_str().and_then(|x| x.parse().ok()) {
+    Some(x) => x,
+    None => {
+      println!(
+        "Error: invalid ID: {}",
+        args.positional[1].to_string_lossy()
+      );
+      return 1;
+    }
+  };
+  let text = match args.positional[2].to_str() {
+    Some(x) => x.to_string(),
+    None => {
+      println!("Error: TEXT is not valid UTF-8");
+      return 1;
+    }
+  };
+
+  let mut io_args = vec![args.positional[0].clone()];
+  io_args.extend(args.positional.get(3).cloned());
+  let (input, output) = match prepare_io(&io_args, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+  if let Err(e) = set_comment(&input, &output, id, &text, args.flag("--append")) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  backup.keep();
+  if !args.flag("--quiet") {
+    println!("set the comment of layer {}", id);
+  }
+  return 0;
+}
+
+fn set_comment(
+  input: &Path,
+  output: &Path,
+  id: u64,
+  text: &str,
+  append: bool,
+) -> Result<(), clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  let comment = match doc.layer_comment(id)? {
+    Some(old) if append && !text.is_empty() => format!("{}\n{}", old, text),
+    _ => text.to_string(),
+  };
+  doc.set_layer_comment(id, &comment)?;
+  return doc.save(output);
+}
+
+/// Write line breaks and `\` as `\n`, `\r` and `\\` to keep a comment in one line.
+fn escape(text: &str) -> String {
+  return text
+    .replace('\\', "\\\\")
+    .replace('\n', "\\n")
+    .replace('\r', "\\r");
+}
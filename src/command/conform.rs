@@ -0,0 +1,100 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer conform --reference REFERENCE [--quiet|--verbose] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const OPTIONS: &[OptionSpec] = &[
+  ("--reference", true),
+  ("--quiet", false),
+  ("--verbose", false),
+];
+
+/// Brief.
+///
+/// `conform` sub command: rename layers to the names of the layers at the same positions in a reference file.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  let reference = match args.path("--reference") {
+    Some(x) => x,
+    None => {
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() || args.positional.len() > 2 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let quiet = args.flag("--quiet");
+  if !reference.exists() {
+    println!("Error: {} file not found.", reference.display());
+    return 1;
+  }
+  // read before the input is backed up
+  let reference = match clip::ClipDocument::open(reference) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+  let report = match conform(&reference, &input, &output) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  backup.keep();
+  if !quiet {
+    for w in report.warnings.iter() {
+      println!("Note: {}", w);
+    }
+  }
+  if args.flag("--verbose") {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !quiet {
+    println!("{}", report.summary());
+  }
+  return 0;
+}
+
+fn conform(
+  reference: &clip::ClipDocument,
+  input: &Path,
+  output: &Path,
+) -> Result<clip::RenameReport, clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  let plan = doc.plan_conform(reference)?;
+  let report = doc.apply_plan(&plan)?;
+  doc.save(output)?;
+  return Ok(report);
+}
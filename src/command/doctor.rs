@@ -0,0 +1,200 @@
+use crate::args::Args;
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer doctor [Input]";
+
+/// Brief.
+///
+/// Result of the checks, printed as they are made.
+#[derive(Default)]
+struct Diagnosis {
+  problems: usize,
+}
+
+impl Diagnosis {
+  fn ok(&self, message: &str) {
+    println!("  ok    {}", message);
+  }
+
+  fn note(&self, message: &str) {
+    println!("  note  {}", message);
+  }
+
+  fn problem(&mut self, message: &str) {
+    self.problems += 1;
+    println!("  NG    {}", message);
+  }
+}
+
+/// Brief.
+///
+/// `doctor` sub command: check the environment (temporary directory, free space, SQLite)
+/// and then the input file (chunks, embedded data base, schema, root folders, layer tree),
+/// printing a readable diagnosis to attach to a bug report.
+///
+/// Exit code is 0 if no problem is found, 1 otherwise.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, &[]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() > 1 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let input = args.positional.first().map(Path::new);
+  let mut diagnosis = Diagnosis::default();
+  check_environment(&mut diagnosis, input);
+  if let Some(input) = input {
+    check_file(&mut diagnosis, input);
+  }
+  println!();
+  if diagnosis.problems == 0 {
+    println!("no problems found.");
+    return 0;
+  }
+  println!("{} problem(s) found.", diagnosis.problems);
+  return 1;
+}
+
+/// Check the temporary directory and the libraries.
+fn check_environment(diagnosis: &mut Diagnosis, input: Option<&Path>) {
+  println!("environment");
+  diagnosis.ok(&format!(
+    "renamelayer {} ({})",
+    env!("CARGO_PKG_VERSION"),
+    env!("RENAMELAYER_GIT_HASH")
+  ));
+  diagnosis.ok(&format!("SQLite {}", rusqlite::version()));
+
+  let dir = clip::temp_dir();
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    diagnosis.problem(&format!(
+      "temp dir {} cannot be created: {}",
+      dir.display(),
+      e
+    ));
+    return;
+  }
+  match tempfile::NamedTempFile::new_in(&dir) {
+    Ok(_) => diagnosis.ok(&format!("temp dir {} is writable", dir.display())),
+    Err(e) => {
+      diagnosis.problem(&format!(
+        "temp dir {} is not writable: {}",
+        dir.display(),
+        e
+      ));
+      return;
+    }
+  }
+  let available = match clip::available_space(&dir) {
+    Some(x) => x,
+    None => {
+      diagnosis.note(&format!("free space in {} is unknown", dir.display()));
+      return;
+    }
+  };
+  // the extracted data base and the rebuilt file
+  let required = input
+    .and_then(|x| std::fs::metadata(x).ok())
+    .map(|x| x.len().saturating_mul(2));
+  let message = format!(
+    "free space in {}: {}",
+    dir.display(),
+    clip::format_size(available)
+  );
+  match required {
+    Some(required) if available < required => diagnosis.problem(&format!(
+      "{} (need ~{} for the file)",
+      message,
+      clip::format_size(required)
+    )),
+    _ => diagnosis.ok(&message),
+  }
+}
+
+/// Probe the file in the order it is processed, stopping at the first step which fails.
+fn check_file(diagnosis: &mut Diagnosis, input: &Path) {
+  println!("file {}", input.display());
+  let info = match clip::info(input) {
+    Ok(x) => x,
+    Err(e) => {
+      diagnosis.problem(&format!("chunks: {}", e));
+      return;
+    }
+  };
+  // consecutive chunks of the same kind as "Exta x32"
+  let mut runs: Vec<(&str, usize)> = Vec::new();
+  for c in info.chunks.iter() {
+    match runs.last_mut() {
+      Some((kind, n)) if *kind == c.kind => *n += 1,
+      _ => runs.push((&c.kind, 1)),
+    }
+  }
+  let kinds: Vec<String> = runs
+    .iter()
+    .map(|(kind, n)| match n {
+      1 => kind.to_string(),
+      _ => format!("{} x{}", kind, n),
+    })
+    .collect();
+  diagnosis.ok(&format!(
+    "chunks: {} ({} bytes)",
+    kinds.join(", "),
+    info.file_size
+  ));
+  if info.chunk("SQLi").is_none() {
+    diagnosis.problem("no SQLi chunk (embedded data base) in the chunks");
+  }
+
+  let doc = match clip::ClipDocument::open(input) {
+    Ok(x) => x,
+    Err(e) => {
+      diagnosis.problem(&format!("embedded data base: {}", e));
+      return;
+    }
+  };
+  diagnosis.ok(&format!(
+    "embedded data base: {}",
+    clip::format_size(doc.load_statistics().sqlite_bytes)
+  ));
+  for w in doc.warnings() {
+    diagnosis.note(&w.to_string());
+  }
+
+  match clip::describe_schema_in_sqlite(doc.sql_path()) {
+    Ok(schema) => match schema.known {
+      Ok(name) => diagnosis.ok(&format!("schema: {}", name)),
+      Err(diff) => diagnosis.problem(&format!("schema: unknown ({})", diff)),
+    },
+    Err(e) => diagnosis.problem(&format!("schema: {}", e)),
+  }
+
+  let tree = match doc.layer_tree() {
+    Ok(x) => x,
+    Err(e) => {
+      diagnosis.problem(&format!("layers: {}", e));
+      return;
+    }
+  };
+  match tree.canvas_roots().len() {
+    0 => diagnosis.problem("root folder: not found in the Canvas table"),
+    1 => diagnosis.ok("root folder: 1 canvas"),
+    n => diagnosis.ok(&format!("root folders: {} canvases", n)),
+  }
+  let issues = tree.validate();
+  if issues.is_empty() {
+    diagnosis.ok(&format!("layer tree: {} layers", tree.layers().len()));
+  }
+  for issue in issues.iter() {
+    diagnosis.problem(&format!("layer tree: {}", issue));
+  }
+}
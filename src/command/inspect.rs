@@ -0,0 +1,344 @@
+use crate::args::{Args, OptionSpec};
+use regex::RegexBuilder;
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const LIST_USAGE: &str = "renamelayer list [--ids] Input";
+const INFO_USAGE: &str = "renamelayer info Input";
+const STATS_USAGE: &str = "renamelayer stats Input";
+const SCHEMA_USAGE: &str = "renamelayer schema Input";
+const FIND_USAGE: &str = "renamelayer find [--count|--quiet] [--ignore-case] Input REGEX";
+const MATRIX_USAGE: &str = "renamelayer matrix [--output MATRIX.csv|MATRIX.json] Input";
+const LIST_OPTIONS: &[OptionSpec] = &[("--ids", false)];
+const MATRIX_OPTIONS: &[OptionSpec] = &[("--output", true)];
+/// columns of the visibility matrix
+const MATRIX_HEADER: &str = "id,path,folder,visible,opacity,blend,locked,clipping";
+const FIND_OPTIONS: &[OptionSpec] = &[
+  ("--count", false),
+  ("--quiet", false),
+  ("--ignore-case", false),
+];
+
+/// Brief.
+///
+/// `list` sub command: print the layer tree.
+///
+/// Like `info` and `stats`, the file is only read (`clip::open_shared`),
+/// so it can be used while the file is opened in Clip Studio Paint.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_list(args: &[OsString]) -> i32 {
+  let args = match parse(args, LIST_OPTIONS, LIST_USAGE) {
+    Some(x) => x,
+    None => return 1,
+  };
+  let doc = match clip::ClipDocument::open(&args.positional[0]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let layers = match clip::list_layers_in_sqlite(doc.sql_path()) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  for l in layers.iter() {
+    let id = if args.flag("--ids") {
+      format!("{}\t", l.main_id)
+    } else {
+      String::new()
+    };
+    println!(
+      "{}{}{}{}",
+      id,
+      "  ".repeat(l.depth),
+      l.name,
+      if l.folder { "/" } else { "" }
+    );
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `find` sub command: print `main_id<TAB>path` of the layers and folders whose names match the regex.
+/// The path is the names of the folders and the layer joined with `/`.
+///
+/// Like `grep`, the exit code is 0 if any layer matches, 1 if none, and 2 on errors.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_find(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, FIND_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", FIND_USAGE);
+      return 2;
+    }
+  };
+  if args.positional.len() != 2 || (args.flag("--count") && args.flag("--quiet")) {
+    println!("{}", FIND_USAGE);
+    return 2;
+  }
+  let pattern = args.positional[1].to_string_lossy();
+  let pattern = match RegexBuilder::new(&pattern)
+    .case_insensitive(args.flag("--ignore-case"))
+    .build()
+  {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: invalid REGEX: {}", e);
+      return 2;
+    }
+  };
+  let input = Path::new(&args.positional[0]);
+  if !input.exists() {
+    println!("Error: {} file not found.", input.display());
+    return 2;
+  }
+  let layers =
+    clip::ClipDocument::open(input).and_then(|doc| clip::list_layers_in_sqlite(doc.sql_path()));
+  let layers = match layers {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 2;
+    }
+  };
+
+  let mut count = 0;
+  for l in layers.iter() {
+    if pattern.is_match(&l.name) {
+      count += 1;
+      if !args.flag("--count") && !args.flag("--quiet") {
+        println!("{}\t{}", l.main_id, l.path_string());
+      }
+    }
+  }
+  if args.flag("--count") {
+    println!("{}", count);
+  }
+  return if count == 0 { 1 } else { 0 };
+}
+
+/// Brief.
+///
+/// `info` sub command: print the chunk structure of the file.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_info(args: &[OsString]) -> i32 {
+  let args = match parse(args, &[], INFO_USAGE) {
+    Some(x) => x,
+    None => return 1,
+  };
+  let info = match clip::info(&args.positional[0]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  println!("file size: {}", info.file_size);
+  for c in info.chunks.iter() {
+    println!("{}\toffset {}\tlength {}", c.kind, c.offset, c.length);
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `stats` sub command: print the numbers of layers and the size of the embedded data base.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_stats(args: &[OsString]) -> i32 {
+  let args = match parse(args, &[], STATS_USAGE) {
+    Some(x) => x,
+    None => return 1,
+  };
+  let doc = match clip::ClipDocument::open(&args.positional[0]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let layers = match clip::list_layers_in_sqlite(doc.sql_path()) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let statistics = doc.load_statistics();
+  println!("layers: {}", layers.iter().filter(|x| !x.folder).count());
+  for kind in clip::LayerKind::ALL.iter() {
+    println!(
+      "  {}: {}",
+      kind.name(),
+      layers.iter().filter(|x| x.kind == Some(*kind)).count()
+    );
+  }
+  println!("folders: {}", layers.iter().filter(|x| x.folder).count());
+  println!(
+    "max depth: {}",
+    layers.iter().map(|x| x.depth + 1).max().unwrap_or(0)
+  );
+  println!("data base size: {}", statistics.sqlite_bytes);
+  if statistics.trailing_bytes != 0 {
+    println!("bytes after the footer: {}", statistics.trailing_bytes);
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `schema` sub command: print the tables, columns and row counts of the embedded data base,
+/// and whether the Layer table is a known schema.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_schema(args: &[OsString]) -> i32 {
+  let args = match parse(args, &[], SCHEMA_USAGE) {
+    Some(x) => x,
+    None => return 1,
+  };
+  let schema = clip::ClipDocument::open(&args.positional[0])
+    .and_then(|doc| clip::describe_schema_in_sqlite(doc.sql_path()));
+  let schema = match schema {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  for t in schema.tables.iter() {
+    println!("{}\t{} rows", t.name, t.rows);
+    for (name, ty) in t.columns.iter() {
+      println!("  {}\t{}", name, ty);
+    }
+  }
+  match &schema.known {
+    Ok(name) => println!("layer schema: {}", name),
+    Err(differences) => println!("layer schema: unknown ({})", differences),
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `matrix` sub command: export the display state of every layer and folder
+/// (visible, opacity, blending mode, locked, clipping) to review a file at a glance.
+///
+/// The format is decided by the extension of `--output`. Without `--output`, CSV is printed.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_matrix(args: &[OsString]) -> i32 {
+  let args = match parse(args, MATRIX_OPTIONS, MATRIX_USAGE) {
+    Some(x) => x,
+    None => return 1,
+  };
+  let output = args.path("--output");
+  let json = match output.and_then(|x| x.extension()) {
+    None => false,
+    Some(ext) => match ext.to_string_lossy().to_lowercase().as_str() {
+      "csv" => false,
+      "json" => true,
+      _ => {
+        println!(
+          "Error: unknown matrix format: {} (.csv|.json)",
+          output.unwrap().display()
+        );
+        return 1;
+      }
+    },
+  };
+  let states = clip::ClipDocument::open(&args.positional[0])
+    .and_then(|doc| clip::list_layer_states_in_sqlite(doc.sql_path()));
+  let states = match states {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let text = if json {
+    match serde_json::to_string_pretty(&states) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    }
+  } else {
+    to_csv(&states)
+  };
+  match output {
+    Some(p) => {
+      if let Err(e) = std::fs::write(p, text) {
+        println!("Error: cannot write {}: {}", p.display(), e);
+        return 1;
+      }
+    }
+    None => print!("{}", text),
+  }
+  return 0;
+}
+
+/// Visibility matrix in CSV. Unknown values (no column in the file) are empty.
+fn to_csv(states: &[clip::LayerState]) -> String {
+  fn flag(x: Option<bool>) -> String {
+    return x.map(|x| (x as u8).to_string()).unwrap_or_default();
+  }
+  fn number(x: Option<u64>) -> String {
+    return x.map(|x| x.to_string()).unwrap_or_default();
+  }
+  let mut s = format!("{}\n", MATRIX_HEADER);
+  for l in states.iter() {
+    s.push_str(&format!(
+      "{},{},{},{},{},{},{},{}\n",
+      l.main_id,
+      csv_field(&l.path),
+      l.folder as u8,
+      flag(l.visible),
+      number(l.opacity),
+      number(l.blend),
+      flag(l.locked),
+      flag(l.clipping)
+    ));
+  }
+  return s;
+}
+
+/// Quote a CSV field with `"` if needed.
+fn csv_field(text: &str) -> String {
+  if !text.contains([',', '"', '\n', '\r']) {
+    return text.to_string();
+  }
+  return format!("\"{}\"", text.replace('"', "\"\""));
+}
+
+/// Parse `[OPTIONS] Input`. The usage is printed on errors.
+fn parse(args: &[OsString], spec: &[OptionSpec], usage: &str) -> Option<Args> {
+  let args = match Args::parse(args, spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", usage);
+      return None;
+    }
+  };
+  if args.positional.len() != 1 {
+    println!("{}", usage);
+    return None;
+  }
+  let input = Path::new(&args.positional[0]);
+  if !input.exists() {
+    println!("Error: {} file not found.", input.display());
+    return None;
+  }
+  return Some(args);
+}
@@ -0,0 +1,154 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, rename, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use regex::Regex;
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer lint [--quiet] [--convention KIND=REGEX]... [--folder-convention FOLDER=REGEX]... Input\nrenamelayer lint --fix [--quiet|--verbose] [--convention KIND=REGEX]... [--folder-convention FOLDER=REGEX]... [rename options] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const OPTIONS: &[OptionSpec] = &[
+  ("--convention", true),
+  ("--folder-convention", true),
+  ("--fix", false),
+];
+
+/// Brief.
+///
+/// `lint` sub command: find layer names violating a naming convention,
+/// and rename them with the rename rules with `--fix`.
+///
+/// * `args`: arguments after the sub command name
+///
+/// Return.
+///
+/// Without `--fix`, 0 if no name violates the convention, 1 if some names do, 2 on errors.
+/// With `--fix`, 0 on success and 2 on errors.
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(rename::OPTIONS.iter())
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 2;
+    }
+  };
+  let fix = args.flag("--fix");
+  if args.positional.is_empty() || args.positional.len() > if fix { 2 } else { 1 } {
+    println!("{}", USAGE);
+    return 2;
+  }
+  let mut convention = clip::NamingConvention::new();
+  for rule in args.values("--convention") {
+    if let Err(e) = convention.add_kind_rule(rule) {
+      println!("Error: {}", e);
+      return 2;
+    }
+  }
+  for rule in args.values("--folder-convention") {
+    if let Err(e) = convention.add_folder_rule(rule) {
+      println!("Error: {}", e);
+      return 2;
+    }
+  }
+  if convention.is_empty() {
+    println!("Error: give --convention or --folder-convention");
+    return 2;
+  }
+  let quiet = args.flag("--quiet");
+
+  if !fix {
+    let input = Path::new(&args.positional[0]);
+    if !input.exists() {
+      println!("Error: {} file not found.", input.display());
+      return 2;
+    }
+    let issues = clip::ClipDocument::open(input)
+      .and_then(|doc| clip::lint_layers_in_sqlite(doc.sql_path(), &convention));
+    let issues = match issues {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 2;
+      }
+    };
+    if !quiet {
+      for i in issues.iter() {
+        println!("{}\t{}\t(expected {})", i.main_id, i.path, i.expected);
+      }
+    }
+    return if issues.is_empty() { 0 } else { 1 };
+  }
+
+  // every violating name is renamed, whatever the pattern is
+  let options = match rename::parse_options(&args) {
+    Ok(x) => x.pattern(Regex::new("").unwrap()),
+    Err(e) => {
+      println!("Error: {}", e);
+      return 2;
+    }
+  };
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 2;
+    }
+  };
+  let backup = BackupGuard::guard();
+  let (report, remaining) = match fix_names(&input, &output, &convention, options) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 2;
+    }
+  };
+  backup.keep();
+  if args.flag("--verbose") {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !quiet {
+    for i in remaining.iter() {
+      println!(
+        "Note: {} still violates the convention (expected {})",
+        i.path, i.expected
+      );
+    }
+    println!("fixed {} layers", report.renamed.len());
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// Rename the violating layers with the rename rules and write the file.
+///
+/// Return.
+///
+/// `(renamed layers, names still violating the convention)`
+fn fix_names(
+  input: &Path,
+  output: &Path,
+  convention: &clip::NamingConvention,
+  options: clip::RenameOptions,
+) -> Result<(clip::RenameReport, Vec<clip::LintIssue>), clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  let issues = clip::lint_layers_in_sqlite(doc.sql_path(), convention)?;
+  let options = options.selection(issues.iter().map(|x| x.main_id).collect());
+  let report = if issues.is_empty() {
+    clip::RenameReport::default()
+  } else {
+    let plan = doc.plan_renames(&options)?;
+    doc.apply_plan(&plan)?
+  };
+  let remaining = clip::lint_layers_in_sqlite(doc.sql_path(), convention)?;
+  doc.save(output)?;
+  return Ok((report, remaining));
+}
@@ -0,0 +1,62 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+
+const USAGE: &str = "renamelayer lock|unlock [--quiet] [--folder NAME]... [--all] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const OPTIONS: &[OptionSpec] = &[("--folder", true), ("--all", false), ("--quiet", false)];
+
+/// Brief.
+///
+/// `lock` / `unlock` sub command.
+///
+/// * `args`: arguments after the sub command name
+/// * `lock`: `true` for `lock`, `false` for `unlock`
+pub fn run(args: &[OsString], lock: bool) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  let folders = args.values("--folder");
+  let all = args.flag("--all");
+  if args.positional.is_empty() || args.positional.len() > 2 || (folders.is_empty() && !all) {
+    println!("{}", USAGE);
+    return 1;
+  }
+
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+
+  let mut count = 0;
+  if let Err(e) = clip::edit_clip_file(&input, &output, |sql_path| {
+    count = clip::lock_layers_in_sqlite(sql_path, &folders, all, lock)?;
+    Ok(())
+  }) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  backup.keep();
+  if !args.flag("--quiet") {
+    println!(
+      "{} {} layers",
+      if lock { "locked" } else { "unlocked" },
+      count
+    );
+  }
+  return 0;
+}
@@ -0,0 +1,91 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer panel --panels CSV [--quiet|--verbose] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const OPTIONS: &[OptionSpec] = &[("--panels", true), ("--quiet", false), ("--verbose", false)];
+
+/// Brief.
+///
+/// `panel` sub command: name each layer after the panel of a comic page containing it.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  let panels = match args.path("--panels") {
+    Some(x) => x,
+    None => {
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() || args.positional.len() > 2 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let quiet = args.flag("--quiet");
+  let panels = match clip::load_panels(panels) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}: {}", panels.display(), e);
+      return 1;
+    }
+  };
+
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+  let report = match rename_by_panels(&panels, &input, &output) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  backup.keep();
+  if !quiet {
+    for w in report.warnings.iter() {
+      println!("Note: {}", w);
+    }
+  }
+  if args.flag("--verbose") {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !quiet {
+    println!("{}", report.summary());
+  }
+  return 0;
+}
+
+fn rename_by_panels(
+  panels: &[clip::Panel],
+  input: &Path,
+  output: &Path,
+) -> Result<clip::RenameReport, clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  let plan = doc.plan_panels(panels)?;
+  let report = doc.apply_plan(&plan)?;
+  doc.save(output)?;
+  return Ok(report);
+}
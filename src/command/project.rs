@@ -0,0 +1,166 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::path::Path;
+
+const USAGE: &str = "renamelayer project-name get [--canvas N] Input\nrenamelayer project-name set [--quiet] [--canvas N] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input NAME [Output]";
+const GET_OPTIONS: &[OptionSpec] = &[("--canvas", true)];
+const SET_OPTIONS: &[OptionSpec] = &[("--quiet", false), ("--canvas", true)];
+
+/// Brief.
+///
+/// `project-name` sub command: read or write the project name (title) of a file,
+/// or the name of a canvas with `--canvas N`.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  return match args.first().and_then(|x| x.to_str()) {
+    Some("get") => run_get(&args[1..]),
+    Some("set") => run_set(&args[1..]),
+    _ => {
+      println!("{}", USAGE);
+      1
+    }
+  };
+}
+
+/// Brief.
+///
+/// `project-name get`: print the project name or the canvas name.
+fn run_get(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, GET_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() != 1 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let canvas = match parse_canvas(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let input = Path::new(&args.positional[0]);
+  if !input.exists() {
+    println!("Error: {} file not found.", input.display());
+    return 1;
+  }
+  let name = clip::ClipDocument::open(input).and_then(|doc| match canvas {
+    Some(n) => {
+      let names = doc.canvas_names()?;
+      match names.get(n - 1) {
+        Some(x) => Ok(Some(x.clone())),
+        None => Err(clip::ClipError::CanvasNotFound(n, names.len())),
+      }
+    }
+    None => doc.project_name(),
+  });
+  return match name {
+    Ok(Some(x)) => {
+      println!("{}", x);
+      0
+    }
+    Ok(None) => {
+      println!("Error: {} has no project name.", input.display());
+      1
+    }
+    Err(e) => {
+      println!("Error: {}", e);
+      1
+    }
+  };
+}
+
+/// Brief.
+///
+/// `project-name set`: set the project name or the canvas name.
+fn run_set(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = SET_OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() < 2 || args.positional.len() > 3 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let name = match args.positional[1].to_str() {
+    Some(x) => x.to_string(),
+    None => {
+      println!("Error: NAME is not valid UTF-8");
+      return 1;
+    }
+  };
+  let canvas = match parse_canvas(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+
+  let mut io_args = vec![args.positional[0].clone()];
+  io_args.extend(args.positional.get(2).cloned());
+  let (input, output) = match prepare_io(&io_args, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+  if let Err(e) = set_name(&input, &output, canvas, &name) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  backup.keep();
+  if !args.flag("--quiet") {
+    match canvas {
+      Some(n) => println!("set the name of canvas {} to {}", n, name),
+      None => println!("set the project name to {}", name),
+    }
+  }
+  return 0;
+}
+
+/// `--canvas N`: canvas number from 1
+fn parse_canvas(args: &Args) -> Result<Option<usize>, String> {
+  return match args.value("--canvas") {
+    Some(x) => match x.parse::<usize>() {
+      Ok(n) if n > 0 => Ok(Some(n)),
+      _ => Err(format!("invalid --canvas: {}", x)),
+    },
+    None => Ok(None),
+  };
+}
+
+/// Set the canvas name if `canvas` is given, or the project name.
+fn set_name(
+  input: &Path,
+  output: &Path,
+  canvas: Option<usize>,
+  name: &str,
+) -> Result<(), clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  match canvas {
+    Some(n) => doc.set_canvas_name(n, name)?,
+    None => doc.set_project_name(name)?,
+  }
+  return doc.save(output);
+}
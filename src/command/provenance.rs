@@ -0,0 +1,102 @@
+use crate::args::Args;
+use crate::command::file_hash;
+use renamelayer::clip;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Brief.
+///
+/// Run manifest (`--manifest`): what was done to which file, for asset management systems.
+///
+/// Only local files are written; nothing is sent anywhere.
+#[derive(Debug, Serialize)]
+pub struct RunManifest {
+  /// `renamelayer`
+  tool: &'static str,
+  /// version of this tool
+  version: &'static str,
+  /// options in the given order, including options from environment variables
+  options: Vec<ManifestOption>,
+  /// input file path as given
+  input: String,
+  /// SHA-256 of the input before renaming
+  input_hash: String,
+  /// output file path
+  output: String,
+  /// SHA-256 of the output. `None` if no output was written (`--unchanged skip`).
+  output_hash: Option<String>,
+  /// number of renamed layers
+  renamed: usize,
+  /// number of layers
+  total_layers: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestOption {
+  name: String,
+  /// `None` for flags
+  value: Option<String>,
+}
+
+impl RunManifest {
+  /// Brief.
+  ///
+  /// Make a manifest of a finished run.
+  ///
+  /// * `args`: parsed arguments. The first positional argument is the input file path.
+  /// * `input`: file having the contents of the input (the backup file with `--in-place`)
+  /// * `output`: output file path
+  /// * `written`: whether the output was written
+  /// * `report`: result of renaming
+  pub fn new(
+    args: &Args,
+    input: &Path,
+    output: &Path,
+    written: bool,
+    report: &clip::RenameReport,
+  ) -> Result<RunManifest, String> {
+    let input_hash = match file_hash(input) {
+      Ok(x) => x,
+      Err(e) => return Err(format!("cannot read {}: {}", input.display(), e)),
+    };
+    let output_hash = if written {
+      match file_hash(output) {
+        Ok(x) => Some(x),
+        Err(e) => return Err(format!("cannot read {}: {}", output.display(), e)),
+      }
+    } else {
+      None
+    };
+    return Ok(RunManifest {
+      tool: env!("CARGO_PKG_NAME"),
+      version: env!("CARGO_PKG_VERSION"),
+      options: args
+        .options()
+        .into_iter()
+        .map(|(name, value)| ManifestOption {
+          name: name.to_string(),
+          value: value.map(|x| x.to_string_lossy().to_string()),
+        })
+        .collect(),
+      input: args.positional[0].to_string_lossy().to_string(),
+      input_hash,
+      output: output.to_string_lossy().to_string(),
+      output_hash,
+      renamed: report.renamed.len(),
+      total_layers: report.total_layers,
+    });
+  }
+
+  /// Write as JSON.
+  pub fn save(&self, path: &Path) -> Result<(), String> {
+    let text = match serde_json::to_string_pretty(self) {
+      Ok(x) => x,
+      Err(e) => return Err(e.to_string()),
+    };
+    if let Err(e) = fs::write(path, text) {
+      return Err(format!("cannot write {}: {}", path.display(), e));
+    }
+    return Ok(());
+  }
+}
@@ -0,0 +1,740 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::provenance::RunManifest;
+use crate::command::{
+  is_same_file, prepare_io, BackupGuard, Limits, OutputOptions, Unchanged, INCREMENTAL_OPTIONS,
+  LIMIT_OPTIONS, OUTPUT_OPTIONS,
+};
+use regex::Regex;
+use renamelayer::clip;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "renamelayer [--quiet|--verbose] [--profile NAME] [--pattern REGEX] [--exact] [--ignore-case] [--normalize-width] [--template TEMPLATE] [--dict CSV] [--names-from FILE|-] [--selection FILE|-] [--rule KIND=TEMPLATE]... [--top-down] [--number-by position|id|created] [--material-names] [--color-labels] [--clipping-names] [--include-folders] [--global-counter] [--count-all-layers] [--canvas N] [--renumber-folders] [--frame-ranges] [--no-continue-numbering] [--on-collision overwrite|skip|suffix|error] [--protect-prefix PREFIX] [--keep-old-names] [--vector-names] [--keep-old suffix|prefix|compact] [--lock-folder NAME]... [--allow-special TYPE]... [--unchanged copy|link|skip|rebuild] [--deterministic] [--compact] [--update-batch N] [--timings] [--manifest JSON] [--emit PROFILE:OUTPUT]... [--max-layers N] [--max-file-size SIZE] [--strict] [--preview PNG [--preview-font FONT]] [--only-if-newer] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+pub const OPTIONS: &[OptionSpec] = &[
+  ("--quiet", false),
+  ("--verbose", false),
+  ("--profile", true),
+  ("--pattern", true),
+  ("--exact", false),
+  ("--ignore-case", false),
+  ("--normalize-width", false),
+  ("--template", true),
+  ("--dict", true),
+  ("--names-from", true),
+  ("--selection", true),
+  ("--rule", true),
+  ("--top-down", false),
+  ("--number-by", true),
+  ("--material-names", false),
+  ("--color-labels", false),
+  ("--clipping-names", false),
+  ("--include-folders", false),
+  ("--global-counter", false),
+  ("--count-all-layers", false),
+  ("--canvas", true),
+  ("--renumber-folders", false),
+  ("--frame-ranges", false),
+  ("--no-continue-numbering", false),
+  ("--on-collision", true),
+  ("--protect-prefix", true),
+  ("--keep-old-names", false),
+  ("--vector-names", false),
+  ("--keep-old", true),
+  ("--lock-folder", true),
+  ("--allow-special", true),
+  ("--unchanged", true),
+  ("--deterministic", false),
+  ("--compact", false),
+  ("--update-batch", true),
+  ("--timings", false),
+  ("--preview", true),
+  ("--preview-font", true),
+];
+/// options which may be given by environment variables (`Args::parse_with_environment`)
+pub const ENVIRONMENT_OPTIONS: &[&str] = &[
+  "--profile",
+  "--pattern",
+  "--template",
+  "--protect-prefix",
+  "--backup-suffix",
+];
+/// options only for a single input
+const EMIT_OPTIONS: &[OptionSpec] = &[("--emit", true), ("--manifest", true)];
+
+/// Brief.
+///
+/// Default command: rename layers.
+///
+/// * `args`: arguments without the program name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(EMIT_OPTIONS.iter())
+    .chain(INCREMENTAL_OPTIONS.iter())
+    .chain(LIMIT_OPTIONS.iter())
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse_with_environment(args, &spec, ENVIRONMENT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() || args.positional.len() > 2 {
+    println!("{}", USAGE);
+    return 1;
+  }
+
+  let options = match parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let output_options = OutputOptions::from_args(&args);
+  if output_options.only_if_newer && !args.values("--emit").is_empty() {
+    println!("Error: --only-if-newer cannot be used with --emit");
+    return 1;
+  }
+  if output_options.update_batch_size == 0 {
+    println!("Error: --update-batch must be a positive number");
+    return 1;
+  }
+  if let Some(output) = output_options.up_to_date(&args.positional) {
+    if !args.flag("--quiet") {
+      println!("{} is up to date. skipped.", output.display());
+    }
+    return 0;
+  }
+  let limits = match Limits::from_args(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  if let Err(e) = limits.check_file(Path::new(&args.positional[0])) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  let unchanged = match Unchanged::from_args(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let lock_folders = args.values("--lock-folder");
+  if !args.values("--emit").is_empty() {
+    if args.path("--names-from").is_some() {
+      println!("Error: --names-from cannot be used with --emit");
+      return 1;
+    }
+    if args.path("--manifest").is_some() {
+      println!("Error: --manifest cannot be used with --emit");
+      return 1;
+    }
+    return run_emit(&args, &limits, &lock_folders);
+  }
+  if args.path("--names-from").is_some() && args.path("--selection").is_some() {
+    println!("Error: --names-from cannot be used with --selection");
+    return 1;
+  }
+  let names = match args.path("--names-from") {
+    Some(p) => match read_names(p) {
+      Ok(x) => Some(x),
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    },
+    None => None,
+  };
+
+  let quiet = args.flag("--quiet");
+  let verbose = args.flag("--verbose");
+  let preview = match prepare_preview(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+
+  let (input_buf, output) = match prepare_io(&args.positional, &output_options) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  // the backup is restored on errors returned from here
+  let backup = BackupGuard::guard();
+
+  let mut doc = match clip::ClipDocument::open(&input_buf) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  doc.set_deterministic(output_options.deterministic);
+  doc.set_compact(output_options.compact);
+  doc.set_update_batch_size(output_options.update_batch_size);
+  if let Err(e) = limits.check_document(&doc) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  let before = match preview {
+    Some(_) => match clip::list_layers_in_sqlite(doc.sql_path()) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    },
+    None => Vec::new(),
+  };
+  let load_statistics = doc.load_statistics();
+  let update_start = Instant::now();
+  let result = match &names {
+    Some(names) => set_names(&mut doc, names, &lock_folders),
+    None => rename_document(&mut doc, &options, &lock_folders),
+  };
+  let report = match result {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let update_time = update_start.elapsed();
+  let save_start = Instant::now();
+  if unchanged != Unchanged::Rebuild && is_unchanged(&doc, &report, &lock_folders) {
+    drop(doc);
+    match unchanged.write(&input_buf, &output) {
+      Ok(true) => {}
+      Ok(false) => {
+        if let Err(e) = write_manifest(&args, &input_buf, &output, false, &report) {
+          println!("Error: {}", e);
+          return 1;
+        }
+        if !quiet {
+          println!("no layers to rename. {} was not written.", output.display());
+        }
+        backup.keep();
+        return 0;
+      }
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    }
+  } else if let Err(e) = doc.save(&output) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  if let Err(e) = output_options.preserve_modified(&input_buf, &output) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  let save_time = save_start.elapsed();
+  if let Err(e) = write_manifest(&args, &input_buf, &output, true, &report) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  if let Some(p) = preview {
+    if let Err(e) = p(&before, &report) {
+      println!("Error: preview: {}", e);
+      return 1;
+    }
+  }
+
+  if !quiet {
+    for w in report.warnings.iter() {
+      println!("Note: {}", w);
+    }
+  }
+  if verbose {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !quiet {
+    println!("{}", report.summary());
+  }
+  if args.flag("--timings") {
+    print_timings(
+      &load_statistics,
+      update_time,
+      save_time,
+      &input_buf,
+      &output,
+    );
+  }
+  backup.keep();
+  return 0;
+}
+
+/// Write the run manifest if `--manifest` is given.
+fn write_manifest(
+  args: &Args,
+  input: &Path,
+  output: &Path,
+  written: bool,
+  report: &clip::RenameReport,
+) -> Result<(), String> {
+  let path = match args.path("--manifest") {
+    Some(x) => x,
+    None => return Ok(()),
+  };
+  return RunManifest::new(args, input, output, written, report)?.save(path);
+}
+
+/// Brief.
+///
+/// Read `--names-from`: lines of `main_id<TAB>new name`. `-` is the standard input.
+/// Empty lines and lines starting with `#` are ignored.
+fn read_names(path: &Path) -> Result<Vec<(u64, String)>, String> {
+  let text = read_text(path)?;
+  let mut names: Vec<(u64, String)> = Vec::new();
+  for (i, line) in text.lines().enumerate() {
+    if line.trim().is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let parsed = line
+      .split_once('\t')
+      .and_then(|(id, name)| Some((id.trim().parse::<u64>().ok()?, name)));
+    match parsed {
+      Some((id, name)) if !name.is_empty() => names.push((id, name.to_string())),
+      _ => {
+        return Err(format!(
+          "--names-from line {}: expected main_id<TAB>name: {}",
+          i + 1,
+          line
+        ))
+      }
+    }
+  }
+  return Ok(names);
+}
+
+/// Brief.
+///
+/// Read `--selection`: main_ids of the layers to be renamed, separated by spaces, commas or new lines
+/// (e.g. written by a script of Clip Studio Paint). `-` is the standard input.
+fn read_selection(path: &Path) -> Result<HashSet<u64>, String> {
+  let text = read_text(path)?;
+  let mut ids: HashSet<u64> = HashSet::new();
+  for (i, line) in text.lines().enumerate() {
+    if line.starts_with('#') {
+      continue;
+    }
+    for x in line.split(|c: char| c == ',' || c.is_whitespace()) {
+      if x.is_empty() {
+        continue;
+      }
+      match x.parse::<u64>() {
+        Ok(id) => ids.insert(id),
+        Err(_) => {
+          return Err(format!(
+            "--selection line {}: invalid main_id: {}",
+            i + 1,
+            x
+          ))
+        }
+      };
+    }
+  }
+  return Ok(ids);
+}
+
+/// Read a text file. `-` is the standard input.
+fn read_text(path: &Path) -> Result<String, String> {
+  if path == Path::new("-") {
+    let mut text = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut text) {
+      return Err(format!("cannot read the standard input: {}", e));
+    }
+    return Ok(text);
+  }
+  return match fs::read_to_string(path) {
+    Ok(x) => Ok(x),
+    Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+  };
+}
+
+/// Brief.
+///
+/// Set the names given by `--names-from` and lock folders.
+fn set_names(
+  doc: &mut clip::ClipDocument,
+  names: &[(u64, String)],
+  lock_folders: &[&str],
+) -> Result<clip::RenameReport, clip::ClipError> {
+  let report = doc.set_layer_names(names)?;
+  if !lock_folders.is_empty() {
+    clip::lock_layers_in_sqlite(doc.sql_path(), lock_folders, false, true)?;
+  }
+  return Ok(report);
+}
+
+/// An output of `--emit`.
+struct Emit {
+  profile: String,
+  output: PathBuf,
+}
+
+impl Emit {
+  /// Parse `PROFILE:OUTPUT` (or `name=PROFILE:OUTPUT`).
+  fn parse(text: &str) -> Result<Emit, String> {
+    let body = text.strip_prefix("name=").unwrap_or(text);
+    return match body.split_once(':') {
+      Some((profile, output)) if !profile.is_empty() && !output.is_empty() => Ok(Emit {
+        profile: profile.to_string(),
+        output: PathBuf::from(output),
+      }),
+      _ => Err(format!("invalid --emit: {} (PROFILE:OUTPUT)", text)),
+    };
+  }
+}
+
+/// Brief.
+///
+/// `--emit`: write a variant for each profile from one scan of the input.
+///
+/// The other rename options are applied to every variant on top of the profile.
+fn run_emit(args: &Args, limits: &Limits, lock_folders: &[&str]) -> i32 {
+  if args.positional.len() != 1 || args.flag("--in-place") {
+    println!("Error: --emit takes only Input and cannot be used with --in-place");
+    return 1;
+  }
+  if args.path("--preview").is_some() {
+    println!("Error: --preview cannot be used with --emit");
+    return 1;
+  }
+  let input = Path::new(&args.positional[0]);
+  let mut variants: Vec<(Emit, clip::RenameOptions)> = Vec::new();
+  for x in args.values("--emit") {
+    let emit = match Emit::parse(x) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    };
+    if is_same_file(input, &emit.output)
+      || variants
+        .iter()
+        .any(|(e, _)| is_same_file(&e.output, &emit.output))
+    {
+      println!(
+        "Error: --emit output is used twice: {}",
+        emit.output.display()
+      );
+      return 1;
+    }
+    let options = match parse_options_with_profile(args, Some(&emit.profile)) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    };
+    variants.push((emit, options));
+  }
+
+  let mut doc = match clip::ClipDocument::open(input) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  // copied to each variant by `try_clone`
+  doc.set_deterministic(args.flag("--deterministic"));
+  doc.set_compact(args.flag("--compact"));
+  doc.set_update_batch_size(OutputOptions::from_args(args).update_batch_size);
+  if let Err(e) = limits.check_document(&doc) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  for (emit, options) in variants.iter() {
+    let mut copy = match doc.try_clone() {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    };
+    let result = rename_document(&mut copy, options, lock_folders);
+    let report = match result.and_then(|r| copy.save(&emit.output).map(|_| r)) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}: {}", emit.output.display(), e);
+        return 1;
+      }
+    };
+    if args.flag("--verbose") {
+      for r in report.renamed.iter() {
+        println!("{} -> {}", r.old_name, r.new_name);
+      }
+    }
+    if !args.flag("--quiet") {
+      println!(
+        "{} ({}): {}",
+        emit.output.display(),
+        emit.profile,
+        report.summary()
+      );
+    }
+  }
+  return 0;
+}
+
+/// Print the time of each step and the processed bytes (`--timings`).
+fn print_timings(
+  load: &clip::LoadStatistics,
+  update: Duration,
+  save: Duration,
+  input: &Path,
+  output: &Path,
+) {
+  let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+  let size = |p: &Path| fs::metadata(p).map(|x| x.len()).unwrap_or(0);
+  println!(
+    "scan        {:10.1} ms ({} bytes)",
+    ms(load.scan),
+    load.scanned_bytes
+  );
+  println!(
+    "extract     {:10.1} ms ({} bytes)",
+    ms(load.extract),
+    load.sqlite_bytes
+  );
+  println!("sql update  {:10.1} ms", ms(update));
+  println!("reassembly  {:10.1} ms ({} bytes)", ms(save), size(output));
+  println!(
+    "total       {:10.1} ms ({} bytes read)",
+    ms(load.scan + load.extract + update + save),
+    size(input)
+  );
+}
+
+/// Brief.
+///
+/// Build rename options from `--profile` and the other rename options.
+pub fn parse_options(args: &Args) -> Result<clip::RenameOptions, String> {
+  return parse_options_with_profile(args, args.value("--profile"));
+}
+
+/// `parse_options` with the profile given instead of `--profile`.
+fn parse_options_with_profile(
+  args: &Args,
+  profile: Option<&str>,
+) -> Result<clip::RenameOptions, String> {
+  let profile = match profile {
+    Some(name) => match clip::Profile::find(name) {
+      Some(x) => Some(x),
+      None => {
+        let names: Vec<&str> = clip::PROFILES.iter().map(|x| x.name).collect();
+        return Err(format!("unknown profile: {} ({})", name, names.join("|")));
+      }
+    },
+    None => None,
+  };
+  let mut options = match profile {
+    Some(p) => p.options(),
+    None => clip::RenameOptions::new(),
+  };
+  if args.flag("--renumber-folders") {
+    options = options
+      .renumber_folders(true)
+      .pattern(Regex::new(clip::DEFAULT_FOLDER_PATTERN).unwrap())
+      .template(clip::DEFAULT_SCENE_TEMPLATE)
+      .unwrap();
+  }
+  if args.flag("--frame-ranges") {
+    options = options.frame_ranges(true);
+  }
+  if let Some(pattern) = args.value("--pattern") {
+    options = match Regex::new(pattern) {
+      Ok(x) => options.pattern(x),
+      Err(e) => return Err(format!("invalid pattern: {}", e)),
+    };
+  } else if profile.is_none() && !args.flag("--renumber-folders") {
+    // "Layer N" in English files, "图层 N" in Chinese files, ...
+    options = options.detect_language(true);
+  }
+  if args.flag("--exact") {
+    options = options.match_mode(clip::MatchMode::Exact);
+  }
+  if args.flag("--ignore-case") {
+    options = options.ignore_case(true);
+  }
+  if args.flag("--normalize-width") {
+    options = options.normalize_width(true);
+  }
+  if let Some(template) = args.value("--template") {
+    options = match options.template(template) {
+      Ok(x) => x,
+      Err(e) => return Err(e.to_string()),
+    };
+  }
+  if let Some(path) = args.path("--dict") {
+    options = match clip::load_dictionary(path) {
+      Ok(x) => options.dictionary(x),
+      Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+  }
+  for rule in args.values("--rule") {
+    options = match options.kind_rule(rule) {
+      Ok(x) => x,
+      Err(e) => return Err(e.to_string()),
+    };
+  }
+  if args.flag("--top-down") {
+    options = options.order(clip::NumberingOrder::TopDown);
+  }
+  match args.value("--number-by") {
+    None | Some("position") => {}
+    Some(x) if args.flag("--top-down") => {
+      return Err(format!("--top-down cannot be used with --number-by {}", x))
+    }
+    Some("id") => options = options.order(clip::NumberingOrder::Id),
+    Some("created") => options = options.order(clip::NumberingOrder::Created),
+    Some(x) => return Err(format!("invalid --number-by: {} (position|id|created)", x)),
+  }
+  match args.value("--on-collision") {
+    None | Some("overwrite") => {}
+    Some("skip") => options = options.on_collision(clip::CollisionPolicy::Skip),
+    Some("suffix") => options = options.on_collision(clip::CollisionPolicy::Suffix),
+    Some("error") => options = options.on_collision(clip::CollisionPolicy::Error),
+    Some(x) => {
+      return Err(format!(
+        "invalid --on-collision: {} (overwrite|skip|suffix|error)",
+        x
+      ))
+    }
+  }
+  if args.flag("--material-names") {
+    options = options.material_names(true);
+  }
+  if args.flag("--color-labels") {
+    options = options.color_labels(true);
+  }
+  if args.flag("--clipping-names") {
+    options = options.clipping_names(true);
+  }
+  if let Some(prefix) = args.value("--protect-prefix") {
+    options = options.protect_prefix(prefix);
+  }
+  if args.flag("--keep-old-names") {
+    options = options.keep_old_names(true);
+  }
+  if args.flag("--vector-names") {
+    options = options.vector_names(true);
+  }
+  if let Some(x) = args.value("--keep-old") {
+    match clip::OldNameStyle::parse(x) {
+      Some(style) => options = options.keep_old_in_name(Some(style)),
+      None => return Err(format!("invalid --keep-old: {} (suffix|prefix|compact)", x)),
+    }
+  }
+  if args.flag("--include-folders") {
+    options = options.include_folders(true);
+  }
+  if args.flag("--global-counter") {
+    options = options.global_counter(true);
+  }
+  if args.flag("--count-all-layers") {
+    options = options.count_all_layers(true);
+  }
+  if let Some(x) = args.value("--canvas") {
+    match x.parse::<usize>() {
+      Ok(n) if n > 0 => options = options.canvas(n),
+      _ => return Err(format!("invalid --canvas: {}", x)),
+    }
+  }
+  options = options.continue_numbering(!args.flag("--no-continue-numbering"));
+  if let Some(p) = args.path("--selection") {
+    options = options.selection(read_selection(p)?);
+  }
+  for x in args.values("--allow-special") {
+    for name in x.split(',') {
+      match name.trim().parse() {
+        Ok(t) => options = options.allow_special(t),
+        Err(e) => return Err(e.to_string()),
+      }
+    }
+  }
+  return Ok(options);
+}
+
+/// Whether the input can be used as the output as it is: no layer was renamed, no folder is locked
+/// and the embedded data base needs no checkpoint.
+pub fn is_unchanged(
+  doc: &clip::ClipDocument,
+  report: &clip::RenameReport,
+  lock_folders: &[&str],
+) -> bool {
+  let state = doc.database_state();
+  return report.renamed.is_empty()
+    && lock_folders.is_empty()
+    && !state.wal
+    && !state.inconsistent_size;
+}
+
+/// Brief.
+///
+/// Rename layers of the opened document and lock folders.
+pub fn rename_document(
+  doc: &mut clip::ClipDocument,
+  options: &clip::RenameOptions,
+  lock_folders: &[&str],
+) -> Result<clip::RenameReport, clip::ClipError> {
+  let report = doc.rename_layers(options)?;
+  if !lock_folders.is_empty() {
+    clip::lock_layers_in_sqlite(doc.sql_path(), lock_folders, false, true)?;
+  }
+  return Ok(report);
+}
+
+/// Writes the preview image from the layers before renaming and the result of renaming.
+type PreviewWriter =
+  Box<dyn FnOnce(&[clip::LayerInfo], &clip::RenameReport) -> Result<(), clip::ClipError>>;
+
+/// Brief.
+///
+/// Parse `--preview` and `--preview-font`. The font is loaded here to fail before renaming.
+#[cfg(feature = "preview")]
+fn prepare_preview(args: &Args) -> Result<Option<PreviewWriter>, String> {
+  let path = match args.path("--preview") {
+    Some(x) => x.to_path_buf(),
+    None => return Ok(None),
+  };
+  let font = args.path("--preview-font");
+  let font = match clip::load_preview_font(font) {
+    Ok(x) => x,
+    Err(e) => return Err(format!("{} (use --preview-font)", e)),
+  };
+  return Ok(Some(Box::new(move |before, report| {
+    return clip::write_rename_preview(before, report, &font, &path);
+  })));
+}
+
+#[cfg(not(feature = "preview"))]
+fn prepare_preview(args: &Args) -> Result<Option<PreviewWriter>, String> {
+  if args.path("--preview").is_some() {
+    return Err("--preview is not supported. Build with `--features preview`.".to_string());
+  }
+  return Ok(None);
+}
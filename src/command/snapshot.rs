@@ -0,0 +1,252 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use renamelayer::clip;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const SNAPSHOT_USAGE: &str = "renamelayer snapshot [--quiet] [--compress gzip] Input -o SNAPSHOT";
+const RESTORE_USAGE: &str =
+  "renamelayer restore [--quiet] [--verbose] --from SNAPSHOT [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const SNAPSHOT_OPTIONS: &[OptionSpec] = &[
+  ("--quiet", false),
+  ("-o", true),
+  ("--output", true),
+  ("--compress", true),
+];
+/// first bytes of gzip data
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const RESTORE_OPTIONS: &[OptionSpec] =
+  &[("--quiet", false), ("--verbose", false), ("--from", true)];
+
+/// Brief.
+///
+/// Names of all layers of a file, saved as JSON.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+  /// input file path as given
+  source: String,
+  layers: Vec<SnapshotLayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotLayer {
+  main_id: u64,
+  name: String,
+}
+
+/// Brief.
+///
+/// `snapshot` sub command: save the names of all layers.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_snapshot(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, SNAPSHOT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", SNAPSHOT_USAGE);
+      return 1;
+    }
+  };
+  let output = args.path("-o").or_else(|| args.path("--output"));
+  let output = match output {
+    Some(x) if args.positional.len() == 1 => x,
+    _ => {
+      println!("{}", SNAPSHOT_USAGE);
+      return 1;
+    }
+  };
+  let compress = match args.value("--compress") {
+    None => false,
+    Some("gzip") => true,
+    Some(x) => {
+      println!("Error: unsupported --compress: {} (gzip)", x);
+      return 1;
+    }
+  };
+  let input = Path::new(&args.positional[0]);
+  if !input.exists() {
+    println!("Error: {} file not found.", input.display());
+    return 1;
+  }
+  let snapshot = match take_snapshot(input) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let text = match serde_json::to_string_pretty(&snapshot) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let data = if compress {
+    match gzip(text.as_bytes()) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    }
+  } else {
+    text.into_bytes()
+  };
+  if let Err(e) = fs::write(output, data) {
+    println!("Error: cannot write {}: {}", output.display(), e);
+    return 1;
+  }
+  if !args.flag("--quiet") {
+    println!("saved {} layer names", snapshot.layers.len());
+  }
+  return 0;
+}
+
+/// Brief.
+///
+/// `restore` sub command: set the layer names saved by `snapshot`.
+///
+/// Compressed snapshots (`snapshot --compress`) are read as they are.
+/// Layers which are not in the file any more are ignored.
+///
+/// * `args`: arguments after the sub command name
+pub fn run_restore(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = RESTORE_OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", RESTORE_USAGE);
+      return 1;
+    }
+  };
+  let from = match args.path("--from") {
+    Some(x) if !args.positional.is_empty() && args.positional.len() <= 2 => x,
+    _ => {
+      println!("{}", RESTORE_USAGE);
+      return 1;
+    }
+  };
+  let snapshot: Snapshot = match read_snapshot_text(from) {
+    Ok(text) => match serde_json::from_str(&text) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: broken snapshot {}: {}", from.display(), e);
+        return 1;
+      }
+    },
+    Err(e) => {
+      println!("Error: cannot read {}: {}", from.display(), e);
+      return 1;
+    }
+  };
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+  let (report, missing) = match restore(&input, &output, &snapshot) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  backup.keep();
+  if args.flag("--verbose") {
+    for r in report.renamed.iter() {
+      println!("{} -> {}", r.old_name, r.new_name);
+    }
+  }
+  if !args.flag("--quiet") {
+    println!(
+      "restored {} layer names ({} not found)",
+      report.renamed.len(),
+      missing
+    );
+  }
+  return 0;
+}
+
+/// gzip compressed `data`
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+  let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+  encoder.write_all(data)?;
+  return encoder.finish();
+}
+
+/// Brief.
+///
+/// Read a snapshot file, decompressing it if it is gzip data.
+fn read_snapshot_text(path: &Path) -> std::io::Result<String> {
+  let data = fs::read(path)?;
+  if !data.starts_with(&GZIP_MAGIC) {
+    return match String::from_utf8(data) {
+      Ok(x) => Ok(x),
+      Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    };
+  }
+  let mut text = String::new();
+  GzDecoder::new(data.as_slice()).read_to_string(&mut text)?;
+  return Ok(text);
+}
+
+fn take_snapshot(input: &Path) -> Result<Snapshot, clip::ClipError> {
+  let doc = clip::ClipDocument::open(input)?;
+  let mut layers = doc.layers()?;
+  let mut snapshot = Snapshot {
+    source: input.to_string_lossy().to_string(),
+    layers: Vec::new(),
+  };
+  for l in layers.iter()? {
+    let l = l?;
+    snapshot.layers.push(SnapshotLayer {
+      main_id: l.main_id,
+      name: l.name,
+    });
+  }
+  return Ok(snapshot);
+}
+
+/// Return.
+///
+/// `(renamed layers, number of layers not found in the file)`
+fn restore(
+  input: &Path,
+  output: &Path,
+  snapshot: &Snapshot,
+) -> Result<(clip::RenameReport, usize), clip::ClipError> {
+  let mut doc = clip::ClipDocument::open(input)?;
+  let mut ids: HashSet<u64> = HashSet::new();
+  {
+    let mut layers = doc.layers()?;
+    for l in layers.iter()? {
+      ids.insert(l?.main_id);
+    }
+  }
+  let names: Vec<(u64, String)> = snapshot
+    .layers
+    .iter()
+    .filter(|x| ids.contains(&x.main_id))
+    .map(|x| (x.main_id, x.name.clone()))
+    .collect();
+  let report = doc.set_layer_names(&names)?;
+  doc.save(output)?;
+  return Ok((report, snapshot.layers.len() - names.len()));
+}
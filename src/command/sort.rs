@@ -0,0 +1,69 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::{prepare_io, BackupGuard, OutputOptions, OUTPUT_OPTIONS};
+use renamelayer::clip;
+use std::ffi::OsString;
+
+const USAGE: &str = "renamelayer sort [--quiet] [--folder NAME]... [--recursive] [--collation byte|natural|japanese] [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+const OPTIONS: &[OptionSpec] = &[
+  ("--folder", true),
+  ("--recursive", false),
+  ("--collation", true),
+  ("--quiet", false),
+];
+
+/// Brief.
+///
+/// `sort` sub command: sort layers by name.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = OPTIONS
+    .iter()
+    .chain(OUTPUT_OPTIONS.iter())
+    .cloned()
+    .collect();
+  let args = match Args::parse(args, &spec) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() || args.positional.len() > 2 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let collation: clip::Collation = match args.value("--collation").unwrap_or("natural").parse() {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let folders = args.values("--folder");
+  let recursive = args.flag("--recursive");
+
+  let (input, output) = match prepare_io(&args.positional, &OutputOptions::from_args(&args)) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let backup = BackupGuard::guard();
+
+  let mut count = 0;
+  if let Err(e) = clip::edit_clip_file(&input, &output, |sql_path| {
+    count = clip::sort_layers_in_sqlite(sql_path, &folders, recursive, collation)?;
+    Ok(())
+  }) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  backup.keep();
+  if !args.flag("--quiet") {
+    println!("sorted {} folders", count);
+  }
+  return 0;
+}
@@ -0,0 +1,148 @@
+use crate::args::{Args, OptionSpec};
+use crate::command::rename;
+use regex::Regex;
+use renamelayer::clip;
+use std::ffi::OsString;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const USAGE: &str = "renamelayer test-pattern [rename options] Input";
+const HELP: &str = "commands:
+  pattern REGEX      set the pattern of layer names to be renamed
+  template TEMPLATE  set the default template
+  rule KIND=TEMPLATE add a per kind rule
+  profile NAME       use a profile
+  layers             list all layers
+  show (or empty)    show the layers to be renamed and their new names
+  help               show this message
+  quit";
+
+/// Brief.
+///
+/// `test-pattern` sub command: try patterns and templates interactively.
+///
+/// The input file is never modified. Each try renames a copy of the extracted data base.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let spec: Vec<OptionSpec> = rename::OPTIONS
+    .iter()
+    .filter(|x| !x.0.starts_with("--preview") && x.0 != "--timings")
+    .cloned()
+    .collect();
+  let args = match Args::parse_with_environment(args, &spec, rename::ENVIRONMENT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.len() != 1 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let mut options = match rename::parse_options(&args) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+  let doc = match clip::ClipDocument::open(&args.positional[0]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      return 1;
+    }
+  };
+
+  println!("{}", HELP);
+  show(doc.sql_path(), &options);
+  let stdin = std::io::stdin();
+  let mut lines = stdin.lock().lines();
+  loop {
+    print!("> ");
+    let _ = std::io::stdout().flush();
+    let line = match lines.next() {
+      Some(Ok(x)) => x,
+      _ => break,
+    };
+    let line = line.trim();
+    let (command, value) = match line.find(' ') {
+      Some(i) => (&line[..i], line[i + 1..].trim()),
+      None => (line, ""),
+    };
+    let result = match command {
+      "" | "show" => Ok(options.clone()),
+      "pattern" => match Regex::new(value) {
+        Ok(x) => Ok(options.clone().pattern(x)),
+        Err(e) => Err(format!("invalid pattern: {}", e)),
+      },
+      "template" => options.clone().template(value).map_err(|e| e.to_string()),
+      "rule" => options.clone().kind_rule(value).map_err(|e| e.to_string()),
+      "profile" => match clip::Profile::find(value) {
+        Some(x) => Ok(x.options()),
+        None => Err(format!("unknown profile: {}", value)),
+      },
+      "layers" => {
+        list_layers(doc.sql_path());
+        continue;
+      }
+      "help" => {
+        println!("{}", HELP);
+        continue;
+      }
+      "quit" | "exit" => break,
+      _ => Err(format!("unknown command: {}", command)),
+    };
+    match result {
+      Ok(x) => {
+        options = x;
+        show(doc.sql_path(), &options);
+      }
+      Err(e) => println!("Error: {}", e),
+    }
+  }
+  return 0;
+}
+
+/// Rename a copy of the data base and print the result.
+fn show(sql_path: &Path, options: &clip::RenameOptions) {
+  let copy = match tempfile::NamedTempFile::new_in(clip::temp_dir()) {
+    Ok(x) => x,
+    Err(_) => {
+      println!("Error: {}", clip::ClipError::TmpDirError);
+      return;
+    }
+  };
+  if let Err(_) = std::fs::copy(sql_path, copy.path()) {
+    println!("Error: {}", clip::ClipError::IOError);
+    return;
+  }
+  match clip::rename_layers_in_sqlite(copy.path(), options) {
+    Ok(report) => {
+      for r in report.renamed.iter() {
+        println!("  {} -> {}", r.old_name, r.new_name);
+      }
+      println!("{}", report.summary());
+    }
+    Err(e) => println!("Error: {}", e),
+  }
+}
+
+fn list_layers(sql_path: &Path) {
+  match clip::list_layers_in_sqlite(sql_path) {
+    Ok(layers) => {
+      for l in layers {
+        println!(
+          "{}{}{}",
+          "  ".repeat(l.depth + 1),
+          l.name,
+          if l.folder { "/" } else { "" }
+        );
+      }
+    }
+    Err(e) => println!("Error: {}", e),
+  }
+}
@@ -0,0 +1,369 @@
+use crate::args::Args;
+use crate::command::{OutputOptions, OUTPUT_OPTIONS};
+use std::ffi::OsString;
+
+const USAGE: &str =
+  "renamelayer tui [--suffix-output SUFFIX] [--in-place [--backup-suffix SUFFIX] [--backup-dir DIR] [--backup-keep N]] Input [Output]";
+
+/// Brief.
+///
+/// `tui` sub command: edit layer names in a terminal UI.
+///
+/// Names are edited in memory and written to the file at once when saved.
+///
+/// * `args`: arguments after the sub command name
+pub fn run(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, OUTPUT_OPTIONS) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if args.positional.is_empty() || args.positional.len() > 2 {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let output_options = OutputOptions::from_args(&args);
+  if let Err(e) = output_options.check(&args.positional) {
+    println!("Error: {}", e);
+    return 1;
+  }
+  return editor::run(&args.positional, &output_options);
+}
+
+#[cfg(not(feature = "tui"))]
+mod editor {
+  use crate::command::OutputOptions;
+  use std::ffi::OsString;
+
+  pub fn run(_positional: &[OsString], _output_options: &OutputOptions) -> i32 {
+    println!("Error: tui is not supported. Build with `--features tui`.");
+    return 1;
+  }
+}
+
+#[cfg(feature = "tui")]
+mod editor {
+  use crate::command::{prepare_io, BackupGuard, OutputOptions};
+  use crossterm::cursor::{Hide, MoveTo, Show};
+  use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+  use crossterm::style::{Attribute, Print, SetAttribute};
+  use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+  use crossterm::{execute, queue};
+  use renamelayer::clip;
+  use std::ffi::OsString;
+  use std::io::{self, Write};
+  use std::path::Path;
+
+  const HELP: &str = "↑↓:move  ←→/space:fold  enter:edit  w:save and quit  q:quit";
+
+  /// A row of the tree.
+  struct Node {
+    layer: clip::LayerInfo,
+    /// edited name
+    name: String,
+    /// contents of the folder are hidden
+    collapsed: bool,
+  }
+
+  /// What the key input does.
+  enum Mode {
+    Browse,
+    /// editing the name of the current layer
+    Edit(String),
+    /// asking whether to discard the changes
+    ConfirmQuit,
+  }
+
+  /// Result of the editor.
+  enum Exit {
+    Save,
+    Discard,
+  }
+
+  struct Editor {
+    nodes: Vec<Node>,
+    /// index of `nodes`
+    cursor: usize,
+    /// first visible row
+    scroll: usize,
+    mode: Mode,
+  }
+
+  /// Raw mode and the alternate screen are restored when dropped, even on errors.
+  struct TerminalGuard;
+
+  impl TerminalGuard {
+    fn new() -> io::Result<TerminalGuard> {
+      terminal::enable_raw_mode()?;
+      if let Err(e) = execute!(io::stdout(), EnterAlternateScreen, Hide) {
+        let _ = terminal::disable_raw_mode();
+        return Err(e);
+      }
+      return Ok(TerminalGuard);
+    }
+  }
+
+  impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+      let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+      let _ = terminal::disable_raw_mode();
+    }
+  }
+
+  pub fn run(positional: &[OsString], output_options: &OutputOptions) -> i32 {
+    let input = Path::new(&positional[0]);
+    if !input.exists() {
+      println!("Error: {} file not found.", input.display());
+      return 1;
+    }
+    let layers = match list_layers(input) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    };
+    if layers.is_empty() {
+      println!("no layers");
+      return 0;
+    }
+    let mut editor = Editor {
+      nodes: layers
+        .into_iter()
+        .map(|x| Node {
+          name: x.name.clone(),
+          layer: x,
+          collapsed: false,
+        })
+        .collect(),
+      cursor: 0,
+      scroll: 0,
+      mode: Mode::Browse,
+    };
+    let exit = {
+      let _guard = match TerminalGuard::new() {
+        Ok(x) => x,
+        Err(e) => {
+          println!("Error: {}", e);
+          return 1;
+        }
+      };
+      match editor.main_loop() {
+        Ok(x) => x,
+        Err(e) => {
+          drop(_guard);
+          println!("Error: {}", e);
+          return 1;
+        }
+      }
+    };
+
+    let names = editor.changed_names();
+    match exit {
+      Exit::Discard => return 0,
+      Exit::Save if names.is_empty() => {
+        println!("no changes");
+        return 0;
+      }
+      Exit::Save => {}
+    }
+    let (input, output) = match prepare_io(positional, output_options) {
+      Ok(x) => x,
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    };
+    let backup = BackupGuard::guard();
+    match save(&input, &output, &names) {
+      Ok(report) => {
+        backup.keep();
+        println!("{}", report.summary());
+        return 0;
+      }
+      Err(e) => {
+        println!("Error: {}", e);
+        return 1;
+      }
+    }
+  }
+
+  fn list_layers(input: &Path) -> Result<Vec<clip::LayerInfo>, clip::ClipError> {
+    let doc = clip::ClipDocument::open(input)?;
+    return clip::list_layers_in_sqlite(doc.sql_path());
+  }
+
+  fn save(
+    input: &Path,
+    output: &Path,
+    names: &[(u64, String)],
+  ) -> Result<clip::RenameReport, clip::ClipError> {
+    let mut doc = clip::ClipDocument::open(input)?;
+    let report = doc.set_layer_names(names)?;
+    doc.save(output)?;
+    return Ok(report);
+  }
+
+  impl Editor {
+    fn main_loop(&mut self) -> io::Result<Exit> {
+      loop {
+        self.draw()?;
+        let key = match event::read()? {
+          Event::Key(x) if x.kind != KeyEventKind::Release => x,
+          _ => continue,
+        };
+        if let Some(exit) = self.handle_key(key) {
+          return Ok(exit);
+        }
+      }
+    }
+
+    /// Return.
+    ///
+    /// `Some` when the editor is closed.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Exit> {
+      let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+      match &mut self.mode {
+        Mode::Edit(buf) => match key.code {
+          _ if ctrl_c => self.mode = Mode::Browse,
+          KeyCode::Esc => self.mode = Mode::Browse,
+          KeyCode::Enter => {
+            if !buf.is_empty() {
+              self.nodes[self.cursor].name = std::mem::take(buf);
+            }
+            self.mode = Mode::Browse;
+          }
+          KeyCode::Backspace => {
+            buf.pop();
+          }
+          KeyCode::Char(c) => buf.push(c),
+          _ => {}
+        },
+        Mode::ConfirmQuit => match key.code {
+          KeyCode::Char('y') => return Some(Exit::Discard),
+          _ => self.mode = Mode::Browse,
+        },
+        Mode::Browse => match key.code {
+          _ if ctrl_c => return Some(Exit::Discard),
+          KeyCode::Up | KeyCode::Char('k') => self.move_cursor(false),
+          KeyCode::Down | KeyCode::Char('j') => self.move_cursor(true),
+          KeyCode::Left | KeyCode::Char('h') => self.set_collapsed(Some(true)),
+          KeyCode::Right | KeyCode::Char('l') => self.set_collapsed(Some(false)),
+          KeyCode::Char(' ') => self.set_collapsed(None),
+          KeyCode::Enter | KeyCode::F(2) => {
+            self.mode = Mode::Edit(self.nodes[self.cursor].name.clone());
+          }
+          KeyCode::Char('w') => return Some(Exit::Save),
+          KeyCode::Char('q') | KeyCode::Esc => {
+            if self.changed_names().is_empty() {
+              return Some(Exit::Discard);
+            }
+            self.mode = Mode::ConfirmQuit;
+          }
+          _ => {}
+        },
+      }
+      return None;
+    }
+
+    /// indices of `nodes` not hidden by collapsed folders
+    fn visible(&self) -> Vec<usize> {
+      let mut rows: Vec<usize> = Vec::new();
+      let mut hidden_depth: Option<usize> = None;
+      for (i, n) in self.nodes.iter().enumerate() {
+        if let Some(d) = hidden_depth {
+          if n.layer.depth > d {
+            continue;
+          }
+          hidden_depth = None;
+        }
+        rows.push(i);
+        if n.layer.folder && n.collapsed {
+          hidden_depth = Some(n.layer.depth);
+        }
+      }
+      return rows;
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+      let rows = self.visible();
+      let pos = rows.iter().position(|x| *x == self.cursor).unwrap_or(0);
+      let pos = if down {
+        std::cmp::min(pos + 1, rows.len() - 1)
+      } else {
+        pos.saturating_sub(1)
+      };
+      self.cursor = rows[pos];
+    }
+
+    /// Collapse (`Some(true)`), expand (`Some(false)`) or toggle (`None`) the current folder.
+    fn set_collapsed(&mut self, collapsed: Option<bool>) {
+      let node = &mut self.nodes[self.cursor];
+      if node.layer.folder {
+        node.collapsed = collapsed.unwrap_or(!node.collapsed);
+      }
+    }
+
+    fn changed_names(&self) -> Vec<(u64, String)> {
+      return self
+        .nodes
+        .iter()
+        .filter(|x| x.name != x.layer.name)
+        .map(|x| (x.layer.main_id, x.name.clone()))
+        .collect();
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+      let (_, height) = terminal::size()?;
+      // the last line is the status line
+      let lines = std::cmp::max(height as usize, 2) - 1;
+      let rows = self.visible();
+      let pos = rows.iter().position(|x| *x == self.cursor).unwrap_or(0);
+      if pos < self.scroll {
+        self.scroll = pos;
+      } else if pos >= self.scroll + lines {
+        self.scroll = pos + 1 - lines;
+      }
+
+      let mut out = io::stdout();
+      queue!(out, Clear(ClearType::All))?;
+      for (y, i) in rows.iter().skip(self.scroll).take(lines).enumerate() {
+        let n = &self.nodes[*i];
+        let mark = if !n.layer.folder {
+          "  "
+        } else if n.collapsed {
+          "▸ "
+        } else {
+          "▾ "
+        };
+        let name = match &self.mode {
+          Mode::Edit(buf) if *i == self.cursor => format!("{}_", buf),
+          _ => n.name.clone(),
+        };
+        let changed = if n.name != n.layer.name { " *" } else { "" };
+        let line = format!("{}{}{}{}", "  ".repeat(n.layer.depth), mark, name, changed);
+        queue!(out, MoveTo(0, y as u16))?;
+        if *i == self.cursor {
+          queue!(
+            out,
+            SetAttribute(Attribute::Reverse),
+            Print(line),
+            SetAttribute(Attribute::Reset)
+          )?;
+        } else {
+          queue!(out, Print(line))?;
+        }
+      }
+      let status = match self.mode {
+        Mode::Browse => HELP,
+        Mode::Edit(_) => "enter:ok  esc:cancel",
+        Mode::ConfirmQuit => "discard changes? (y/n)",
+      };
+      queue!(out, MoveTo(0, lines as u16), Print(status))?;
+      return out.flush();
+    }
+  }
+}
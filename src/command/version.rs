@@ -0,0 +1,44 @@
+use crate::args::Args;
+use renamelayer::clip;
+use std::ffi::OsString;
+
+const USAGE: &str = "renamelayer --version|-v [--json]";
+
+/// Brief.
+///
+/// `--version`: print the crate version, the git commit hash of the build and the supported data base schemas.
+///
+/// With `--json`, a JSON object is printed for compatibility checks in pipelines.
+///
+/// * `args`: arguments after `--version`
+pub fn run(args: &[OsString]) -> i32 {
+  let args = match Args::parse(args, &[("--json", false)]) {
+    Ok(x) => x,
+    Err(e) => {
+      println!("Error: {}", e);
+      println!("{}", USAGE);
+      return 1;
+    }
+  };
+  if !args.positional.is_empty() {
+    println!("{}", USAGE);
+    return 1;
+  }
+  let json = args.flag("--json");
+  let version = env!("CARGO_PKG_VERSION");
+  let git_hash = env!("RENAMELAYER_GIT_HASH");
+  let schemas = clip::known_schema_names();
+  if json {
+    let value = serde_json::json!({
+      "name": env!("CARGO_PKG_NAME"),
+      "version": version,
+      "git_hash": git_hash,
+      "schemas": schemas,
+    });
+    println!("{}", value);
+  } else {
+    println!("renamelayer {} ({})", version, git_hash);
+    println!("supported schemas: {}", schemas.join(", "));
+  }
+  return 0;
+}
@@ -0,0 +1,6 @@
+#![forbid(unsafe_code)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_pattern_matching)]
+#![allow(clippy::vec_box)]
+
+pub mod clip;
@@ -1,47 +1,55 @@
-mod clip;
-use regex::Regex;
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_pattern_matching)]
+
+mod args;
+mod command;
+use renamelayer::clip;
 use std::env;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::path::PathBuf;
 
 fn main() {
-  let replace_layer_name_reg = r"レイヤー \d+";
-  let root_layer_name = "ルートレイヤ ";
-
-  let args: Vec<String> = env::args().collect();
-  if args.len() <= 1 || args.len() > 3 {
-    println!("renamelayer Input [Output]");
-    std::process::exit(1);
-  }
-
-  if args[1] == "-v" {
-    println!("v0.1.0");
-    return;
-  }
-
-  let mut input_buf = PathBuf::from(&args[1]);
-  let output = Path::new(&args[if args.len() == 2 { 1 } else { 2 }]);
-
-  if !input_buf.exists() {
-    println!("Error: {} file not found.", input_buf.display());
-    std::process::exit(1);
-  }
-
-  // backup
-  if input_buf == output {
-    input_buf.set_extension("bk.clip");
-    if let Err(e) = fs::rename(&args[1], &input_buf) {
-      println!("Fail to create backup :{}", e);
+  let mut args: Vec<OsString> = env::args_os().collect();
+  command::install_interrupt_handler();
+  let mut rest = args.split_off(1);
+  match args::take_temp_dir(&mut rest) {
+    Ok(Some(dir)) => clip::set_temp_dir(Some(PathBuf::from(dir))),
+    Ok(None) => {}
+    Err(e) => {
+      println!("Error: {}", e);
+      std::process::exit(1);
     }
   }
-
-  let re = Regex::new(replace_layer_name_reg).unwrap();
-  if let Err(e) =
-    clip::create_layer_renamed_clip_file(&input_buf, &output, root_layer_name, |name| {
-      re.is_match(name)
-    })
-  {
-    println!("Error: {}", e);
-    std::process::exit(1);
+  args.append(&mut rest);
+  if args.len() > 1 {
+    match args[1].to_str().unwrap_or_default() {
+      "--version" | "-v" => command::exit(command::version::run(&args[2..])),
+      "batch" => command::exit(command::batch::run(&args[2..])),
+      "audit" => command::exit(command::audit::run(&args[2..])),
+      "check-clean" => command::exit(command::check::run(&args[2..])),
+      "test-pattern" => command::exit(command::test_pattern::run(&args[2..])),
+      "prefix" => command::exit(command::affix::run(&args[2..], true)),
+      "suffix" => command::exit(command::affix::run(&args[2..], false)),
+      "snapshot" => command::exit(command::snapshot::run_snapshot(&args[2..])),
+      "restore" => command::exit(command::snapshot::run_restore(&args[2..])),
+      "tui" => command::exit(command::tui::run(&args[2..])),
+      "lock" => command::exit(command::lock::run(&args[2..], true)),
+      "unlock" => command::exit(command::lock::run(&args[2..], false)),
+      "list" => command::exit(command::inspect::run_list(&args[2..])),
+      "info" => command::exit(command::inspect::run_info(&args[2..])),
+      "find" => command::exit(command::inspect::run_find(&args[2..])),
+      "schema" => command::exit(command::inspect::run_schema(&args[2..])),
+      "doctor" => command::exit(command::doctor::run(&args[2..])),
+      "stats" => command::exit(command::inspect::run_stats(&args[2..])),
+      "matrix" => command::exit(command::inspect::run_matrix(&args[2..])),
+      "comment" => command::exit(command::comment::run(&args[2..])),
+      "project-name" => command::exit(command::project::run(&args[2..])),
+      "lint" => command::exit(command::lint::run(&args[2..])),
+      "conform" => command::exit(command::conform::run(&args[2..])),
+      "panel" => command::exit(command::panel::run(&args[2..])),
+      "sort" => command::exit(command::sort::run(&args[2..])),
+      _ => {}
+    }
   }
+  command::exit(command::rename::run(&args[1..]));
 }
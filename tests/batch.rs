@@ -0,0 +1,26 @@
+#![allow(clippy::needless_return)]
+
+use renamelayer::clip::testing::{ClipBuilder, TestLayer};
+use std::process::Command;
+
+#[test]
+fn out_dir_from_environment() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("in.clip");
+  ClipBuilder::new()
+    .layer(TestLayer::folder(
+      "線画",
+      vec![TestLayer::raster("レイヤー 1")],
+    ))
+    .write(&input)
+    .unwrap();
+  let out_dir = dir.path().join("out");
+  let out = Command::new(env!("CARGO_BIN_EXE_renamelayer"))
+    .env("RENAMELAYER_OUT_DIR", &out_dir)
+    .arg("batch")
+    .arg(&input)
+    .output()
+    .unwrap();
+  assert!(out.status.success(), "{:?}", out);
+  assert!(out_dir.join("in.clip").exists());
+}
@@ -0,0 +1,154 @@
+#![allow(clippy::needless_return)]
+
+use renamelayer::clip::testing::{ClipBuilder, TestLayer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// `in.clip` with two layers to be renamed in a new directory
+fn write_input() -> (TempDir, PathBuf) {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("in.clip");
+  ClipBuilder::new()
+    .layer(TestLayer::folder(
+      "線画",
+      vec![TestLayer::raster("レイヤー 1"), TestLayer::raster("X")],
+    ))
+    .write(&input)
+    .unwrap();
+  return (dir, input);
+}
+
+fn run(args: &[&str], input: &Path) -> Output {
+  return Command::new(env!("CARGO_BIN_EXE_renamelayer"))
+    .args(args)
+    .arg(input)
+    .output()
+    .unwrap();
+}
+
+/// file names in the directory, sorted
+fn files(dir: &Path) -> Vec<String> {
+  let mut names: Vec<String> = fs::read_dir(dir)
+    .unwrap()
+    .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+    .collect();
+  names.sort();
+  return names;
+}
+
+#[test]
+fn in_place_keeps_backup() {
+  let (dir, input) = write_input();
+  let original = fs::read(&input).unwrap();
+  let out = run(&["--in-place"], &input);
+  assert!(out.status.success(), "{:?}", out);
+  assert_eq!(files(dir.path()), vec!["in.bk.clip", "in.clip"]);
+  assert_eq!(fs::read(dir.path().join("in.bk.clip")).unwrap(), original);
+  assert_ne!(fs::read(&input).unwrap(), original);
+}
+
+#[test]
+fn in_place_error_restores_input() {
+  let (dir, input) = write_input();
+  let original = fs::read(&input).unwrap();
+  let out = run(
+    &["--in-place", "--template", "X", "--on-collision", "error"],
+    &input,
+  );
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(files(dir.path()), vec!["in.clip"]);
+  assert_eq!(fs::read(&input).unwrap(), original);
+}
+
+#[test]
+fn in_place_limit_restores_input() {
+  let (dir, input) = write_input();
+  let out = run(&["--in-place", "--max-layers", "1"], &input);
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(files(dir.path()), vec!["in.clip"]);
+}
+
+#[test]
+fn backup_keep_prunes_after_success_only() {
+  let (dir, input) = write_input();
+  let old = ["in.2020-01-01.aaaaaa.clip", "in.2020-01-02.bbbbbb.clip"];
+  for name in old.iter() {
+    fs::copy(&input, dir.path().join(name)).unwrap();
+  }
+  let out = run(
+    &["--in-place", "--backup-keep", "2", "--max-layers", "1"],
+    &input,
+  );
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(files(dir.path()), vec![old[0], old[1], "in.clip"]);
+
+  let out = run(&["--in-place", "--backup-keep", "2"], &input);
+  assert!(out.status.success(), "{:?}", out);
+  let files = files(dir.path());
+  assert_eq!(files.len(), 3, "{:?}", files);
+  assert!(!files.iter().any(|x| x == old[0]), "{:?}", files);
+  assert!(files.iter().any(|x| x == old[1]), "{:?}", files);
+}
+
+#[test]
+fn in_place_is_not_read_from_environment() {
+  let (dir, input) = write_input();
+  let original = fs::read(&input).unwrap();
+  let output = dir.path().join("out.clip");
+  let out = Command::new(env!("CARGO_BIN_EXE_renamelayer"))
+    .env("RENAMELAYER_IN_PLACE", "1")
+    .env("RENAMELAYER_TEMPLATE", "{parent}_{n}")
+    .arg(&input)
+    .arg(&output)
+    .output()
+    .unwrap();
+  assert!(out.status.success(), "{:?}", out);
+  assert_eq!(fs::read(&input).unwrap(), original);
+  assert_eq!(files(dir.path()), vec!["in.clip", "out.clip"]);
+  let stdout = String::from_utf8_lossy(&out.stdout);
+  assert!(stdout.contains("renamed 1 of 2 layers"), "{}", stdout);
+}
+
+/// `in.clip` cut in the middle
+fn write_truncated_input() -> (TempDir, PathBuf) {
+  let (dir, input) = write_input();
+  let data = fs::read(&input).unwrap();
+  fs::write(&input, &data[..data.len() / 2]).unwrap();
+  return (dir, input);
+}
+
+#[test]
+fn failed_run_keeps_existing_backup() {
+  let (dir, input) = write_truncated_input();
+  let backup = dir.path().join("in.bk.clip");
+  fs::write(&backup, b"former backup").unwrap();
+  let out = run(&["--in-place"], &input);
+  assert_eq!(out.status.code(), Some(1));
+  let out = run(&["lock", "--in-place", "--all"], &input);
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(files(dir.path()), vec!["in.bk.clip", "in.clip"]);
+  assert_eq!(fs::read(&backup).unwrap(), b"former backup");
+}
+
+#[test]
+fn failed_sub_commands_leave_no_backup() {
+  let (dir, input) = write_truncated_input();
+  // arguments before and after the input
+  let commands: &[(&[&str], &[&str])] = &[
+    (&["lock", "--in-place", "--all"], &[]),
+    (&["sort", "--in-place"], &[]),
+    (&["prefix", "--in-place"], &["a_"]),
+  ];
+  for (before, after) in commands.iter() {
+    let out = Command::new(env!("CARGO_BIN_EXE_renamelayer"))
+      .args(before.iter())
+      .arg(&input)
+      .args(after.iter())
+      .output()
+      .unwrap();
+    assert_eq!(out.status.code(), Some(1), "{:?} {:?}", before, out);
+    assert_eq!(files(dir.path()), vec!["in.clip"], "{:?}", before);
+  }
+}
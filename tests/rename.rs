@@ -0,0 +1,294 @@
+#![allow(clippy::needless_return)]
+
+use renamelayer::clip::testing::{ClipBuilder, TestLayer};
+use renamelayer::clip::{self, ClipDocument, ClipError, CollisionPolicy, RenameOptions};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Write the file built by `builder` to `in.clip` in a new directory.
+fn write_input(builder: ClipBuilder) -> (TempDir, std::path::PathBuf) {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("in.clip");
+  builder.write(&input).unwrap();
+  return (dir, input);
+}
+
+/// `(main_id, name)` of all layers of the clip file in the order of the layer palette.
+fn layer_names(path: &Path) -> Vec<(u64, String)> {
+  let doc = ClipDocument::open(path).unwrap();
+  return clip::list_layers_in_sqlite(doc.sql_path())
+    .unwrap()
+    .into_iter()
+    .map(|x| (x.main_id, x.name))
+    .collect();
+}
+
+/// Rename `input` to `out.clip` next to it, and return the names of the output.
+fn rename(input: &Path, options: &RenameOptions) -> Result<Vec<(u64, String)>, ClipError> {
+  let output = input.with_file_name("out.clip");
+  clip::create_layer_renamed_clip_file(input, &output, options)?;
+  return Ok(layer_names(&output));
+}
+
+fn names(v: &[(u64, &str)]) -> Vec<(u64, String)> {
+  return v.iter().map(|(id, x)| (*id, x.to_string())).collect();
+}
+
+/// a folder with a layer named "X" between two layers matching the default pattern
+fn collision_input() -> ClipBuilder {
+  return ClipBuilder::new().layer(TestLayer::folder(
+    "線画",
+    vec![
+      TestLayer::raster("レイヤー 1"),
+      TestLayer::raster("X"),
+      TestLayer::raster("レイヤー 2"),
+    ],
+  ));
+}
+
+#[test]
+fn rename_by_parent_folder() {
+  let (_dir, input) = write_input(
+    ClipBuilder::new()
+      .layer(TestLayer::paper("用紙"))
+      .layer(TestLayer::raster("レイヤー 5"))
+      .layer(TestLayer::folder(
+        "線画",
+        vec![
+          TestLayer::raster("レイヤー 1"),
+          TestLayer::vector("レイヤー 2"),
+          TestLayer::raster("ラフ"),
+        ],
+      )),
+  );
+  let renamed = rename(&input, &RenameOptions::new()).unwrap();
+  assert_eq!(
+    renamed,
+    names(&[
+      (5, "線画"),
+      (8, "ラフ"),
+      (7, "線画 2"),
+      (6, "線画 1"),
+      (4, "ルートレイヤ  1"),
+      (3, "用紙"),
+    ])
+  );
+}
+
+#[test]
+fn rename_twice_changes_nothing() {
+  let (_dir, input) = write_input(ClipBuilder::new().layer(TestLayer::folder(
+    "線画",
+    vec![
+      TestLayer::raster("レイヤー 1"),
+      TestLayer::raster("レイヤー 2"),
+    ],
+  )));
+  let output = input.with_file_name("out.clip");
+  let report =
+    clip::create_layer_renamed_clip_file(&input, &output, &RenameOptions::new()).unwrap();
+  assert_eq!(report.renamed.len(), 2);
+  let again = output.with_file_name("again.clip");
+  let report =
+    clip::create_layer_renamed_clip_file(&output, &again, &RenameOptions::new()).unwrap();
+  assert!(report.renamed.is_empty());
+  assert_eq!(layer_names(&output), layer_names(&again));
+}
+
+#[test]
+fn collision_overwrite() {
+  let (_dir, input) = write_input(collision_input());
+  let options = RenameOptions::new()
+    .template("X")
+    .unwrap()
+    .on_collision(CollisionPolicy::Overwrite);
+  assert_eq!(
+    rename(&input, &options).unwrap(),
+    names(&[(3, "線画"), (6, "X"), (5, "X"), (4, "X")])
+  );
+}
+
+#[test]
+fn collision_skip() {
+  let (_dir, input) = write_input(collision_input());
+  let options = RenameOptions::new()
+    .template("X")
+    .unwrap()
+    .on_collision(CollisionPolicy::Skip);
+  assert_eq!(
+    rename(&input, &options).unwrap(),
+    names(&[(3, "線画"), (6, "レイヤー 2"), (5, "X"), (4, "レイヤー 1")])
+  );
+}
+
+#[test]
+fn collision_suffix() {
+  let (_dir, input) = write_input(collision_input());
+  let options = RenameOptions::new()
+    .template("X")
+    .unwrap()
+    .on_collision(CollisionPolicy::Suffix);
+  assert_eq!(
+    rename(&input, &options).unwrap(),
+    names(&[(3, "線画"), (6, "X (3)"), (5, "X"), (4, "X (2)")])
+  );
+}
+
+#[test]
+fn collision_error() {
+  let (_dir, input) = write_input(collision_input());
+  let options = RenameOptions::new()
+    .template("X")
+    .unwrap()
+    .on_collision(CollisionPolicy::Error);
+  match rename(&input, &options) {
+    Err(ClipError::NameCollision(4, name)) => assert_eq!(name, "X"),
+    x => panic!("unexpected result: {:?}", x.map(|_| ())),
+  }
+  assert!(!input.with_file_name("out.clip").exists());
+}
+
+#[test]
+fn frame_ranges() {
+  let (_dir, input) = write_input(ClipBuilder::new().layer(TestLayer::folder(
+    "A",
+    vec![
+      TestLayer::raster("a"),
+      TestLayer::raster("b (1-3)"),
+      TestLayer::raster("c"),
+    ],
+  )));
+  // cels: a on 1-8, b on 9, c is not on the timeline
+  let mut doc = ClipDocument::open(&input).unwrap();
+  doc
+    .with_connection(|conn| {
+      conn.execute_batch(
+        "CREATE TABLE TimeLineCel (_PW_ID INTEGER PRIMARY KEY, LayerId INTEGER, StartFrame INTEGER, EndFrame INTEGER);
+         INSERT INTO TimeLineCel (LayerId, StartFrame, EndFrame) VALUES (4, 1, 8), (5, 9, 9);",
+      )
+    })
+    .unwrap();
+  let output = input.with_file_name("out.clip");
+  let report = doc
+    .rename_layers(&RenameOptions::new().frame_ranges(true))
+    .unwrap();
+  assert_eq!(report.renamed.len(), 2);
+  doc.save(&output).unwrap();
+  assert_eq!(
+    layer_names(&output),
+    names(&[(3, "A"), (6, "c"), (5, "b (9)"), (4, "a (1-8)")])
+  );
+}
+
+/// two canvases: main_id 2 is the root of the first, 5 is the root of the second
+fn two_canvases() -> ClipBuilder {
+  return ClipBuilder::new()
+    .layer(TestLayer::folder(
+      "表紙",
+      vec![TestLayer::raster("レイヤー 1")],
+    ))
+    .canvas()
+    .layer(TestLayer::folder(
+      "本文",
+      vec![TestLayer::raster("レイヤー 1")],
+    ));
+}
+
+#[test]
+fn multi_canvas_renames_each_canvas() {
+  let (_dir, input) = write_input(two_canvases());
+  assert_eq!(
+    rename(&input, &RenameOptions::new()).unwrap(),
+    names(&[(3, "表紙"), (4, "表紙 1"), (6, "本文"), (7, "本文 1")])
+  );
+}
+
+#[test]
+fn multi_canvas_only_given_canvas() {
+  let (_dir, input) = write_input(two_canvases());
+  assert_eq!(
+    rename(&input, &RenameOptions::new().canvas(2)).unwrap(),
+    names(&[(3, "表紙"), (4, "レイヤー 1"), (6, "本文"), (7, "本文 1")])
+  );
+  match rename(&input, &RenameOptions::new().canvas(3)) {
+    Err(ClipError::CanvasNotFound(3, 2)) => {}
+    x => panic!("unexpected result: {:?}", x.map(|_| ())),
+  }
+}
+
+#[test]
+fn create_folder_and_move_layer() {
+  let (_dir, input) = write_input(
+    ClipBuilder::new()
+      .layer(TestLayer::raster("a"))
+      .layer(TestLayer::raster("b")),
+  );
+  let mut doc = ClipDocument::open(&input).unwrap();
+  doc.set_deterministic(true);
+  let folder = doc.create_folder(2, "F").unwrap();
+  assert_eq!(folder, 5);
+  doc.move_layer(3, folder, 0).unwrap();
+  match doc.move_layer(2, folder, 0) {
+    Err(ClipError::InvalidLayerMove(2)) => {}
+    x => panic!("unexpected result: {:?}", x),
+  }
+  let output = input.with_file_name("out.clip");
+  doc.save(&output).unwrap();
+  assert_eq!(layer_names(&output), names(&[(5, "F"), (3, "a"), (4, "b")]));
+}
+
+#[test]
+fn no_root_layer() {
+  let (_dir, input) = write_input(ClipBuilder::new().layer(TestLayer::raster("レイヤー 1")));
+  let mut doc = ClipDocument::open(&input).unwrap();
+  doc
+    .with_connection(|conn| conn.execute("DELETE FROM Layer WHERE MainId = 2", []))
+    .unwrap();
+  match doc.rename_layers(&RenameOptions::new()) {
+    Err(ClipError::UnknownFileStruct) => {}
+    x => panic!("unexpected result: {:?}", x.map(|_| ())),
+  }
+}
+
+/// a vector layer (main_id 4) whose name is copied to `VectorObjectList.ObjectName`
+fn vector_names_input() -> (TempDir, std::path::PathBuf) {
+  let (dir, input) = write_input(ClipBuilder::new().layer(TestLayer::folder(
+    "線画",
+    vec![TestLayer::vector("レイヤー 1")],
+  )));
+  let mut doc = ClipDocument::open(&input).unwrap();
+  doc
+    .with_connection(|conn| {
+      conn.execute_batch(
+        "CREATE TABLE VectorObjectList (_PW_ID INTEGER PRIMARY KEY, LayerId INTEGER, ObjectName TEXT);
+         INSERT INTO VectorObjectList (LayerId, ObjectName) VALUES (4, 'レイヤー 1');",
+      )
+    })
+    .unwrap();
+  doc.save(&input).unwrap();
+  return (dir, input);
+}
+
+fn object_name(path: &Path) -> String {
+  let mut doc = ClipDocument::open(path).unwrap();
+  return doc
+    .with_connection(|conn| {
+      conn.query_row("SELECT ObjectName FROM VectorObjectList", [], |row| {
+        row.get::<_, String>(0)
+      })
+    })
+    .unwrap();
+}
+
+#[test]
+fn vector_names_are_opt_in() {
+  let (_dir, input) = vector_names_input();
+  let output = input.with_file_name("out.clip");
+  clip::create_layer_renamed_clip_file(&input, &output, &RenameOptions::new()).unwrap();
+  assert_eq!(layer_names(&output)[1].1, "線画 1");
+  assert_eq!(object_name(&output), "レイヤー 1");
+
+  let options = RenameOptions::new().vector_names(true);
+  clip::create_layer_renamed_clip_file(&input, &output, &options).unwrap();
+  assert_eq!(object_name(&output), "線画 1");
+}
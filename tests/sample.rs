@@ -0,0 +1,49 @@
+#![allow(clippy::needless_return)]
+
+use renamelayer::clip::{self, ClipDocument, RenameOptions};
+use std::path::Path;
+
+const SAMPLE: &str = "resource/sample.clip";
+
+#[test]
+fn empty_rule_renames_only_raster_layers() {
+  let dir = tempfile::tempdir().unwrap();
+  let output = dir.path().join("out.clip");
+  let options = RenameOptions::new().kind_rule("empty=empty {n}").unwrap();
+  let report = clip::create_layer_renamed_clip_file(Path::new(SAMPLE), &output, &options).unwrap();
+  // レイヤー 2 (6) and レイヤー 5 (9) are vector layers
+  for r in report.renamed.iter() {
+    if r.main_id == 6 || r.main_id == 9 {
+      assert!(!r.new_name.starts_with("empty"), "{:?}", r);
+    }
+  }
+  assert!(report
+    .renamed
+    .iter()
+    .any(|r| r.main_id == 3 && r.new_name == "empty 1"));
+}
+
+#[test]
+fn vector_names_on_sample() {
+  let dir = tempfile::tempdir().unwrap();
+  let output = dir.path().join("out.clip");
+  let input = Path::new(SAMPLE);
+  let expected =
+    clip::create_layer_renamed_clip_file(input, &output, &RenameOptions::new()).unwrap();
+  let options = RenameOptions::new().vector_names(true);
+  let report = clip::create_layer_renamed_clip_file(input, &output, &options).unwrap();
+  assert_eq!(report.renamed, expected.renamed);
+}
+
+#[test]
+fn canvas_name_on_sample() {
+  let dir = tempfile::tempdir().unwrap();
+  let output = dir.path().join("out.clip");
+  let mut doc = ClipDocument::open(SAMPLE).unwrap();
+  assert_eq!(doc.canvas_names().unwrap(), vec![""]);
+  doc.set_canvas_name(1, "EP03_cut12").unwrap();
+  doc.save(&output).unwrap();
+  let doc = ClipDocument::open(&output).unwrap();
+  assert_eq!(doc.canvas_names().unwrap(), vec!["EP03_cut12"]);
+  assert_eq!(doc.project_name().unwrap(), Some("16".to_string()));
+}